@@ -0,0 +1,7 @@
+fn main() {
+    previous_build::Builder::new()
+        .schema("schemas/*.pr")
+        .target(previous::Target::Rust)
+        .compile()
+        .unwrap_or_else(|e| panic!("{}", e.format()));
+}
@@ -0,0 +1,4 @@
+//! Structs generated by previous-build's Rust target, compiled as a crate
+//! would use them
+
+include!(concat!(env!("OUT_DIR"), "/previous.rs"));
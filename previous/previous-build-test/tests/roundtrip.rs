@@ -0,0 +1,39 @@
+use previous::{compile_inputs, BinaryEncoder, IRType, Value, Wire};
+use previous_build_test::billing::{Invoice, Line};
+use previous_build_test::{Order, Tag};
+use serde_json::json;
+
+#[test]
+fn test_generated_structs_match_binary_encoder() {
+    let order = Order {
+        id: "o-1".to_string(),
+        paid: None,
+        tags: vec![Tag { r#type: "gift".to_string() }],
+        invoice: Invoice {
+            id: "i-1".to_string(),
+            total: Some(Some(4200)),
+            lines: vec![Line { sku: "ab-12".to_string(), quantity: 3 }],
+        },
+    };
+
+    let ir = compile_inputs(&["schemas/*.pr".into()]).unwrap().ir;
+    let order_type = IRType::ResourceRef(ir.get_resource_index("Order").unwrap());
+    let value = Value::from_json(
+        json!({
+            "id": "o-1",
+            "paid": null,
+            "tags": [{ "type": "gift" }],
+            "invoice": { "id": "i-1", "total": 4200, "lines": [{ "sku": "ab-12", "quantity": 3 }] },
+        }),
+        &order_type,
+        &ir,
+    )
+    .unwrap();
+    let mut encoder = BinaryEncoder::new();
+    encoder.encode_value(&value, &order_type, &ir).unwrap();
+
+    let bytes = order.to_bytes();
+    assert_eq!(bytes, encoder.finish());
+    assert_eq!(Order::from_bytes(&bytes).unwrap(), order);
+    assert!(Order::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
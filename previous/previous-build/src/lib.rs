@@ -0,0 +1,189 @@
+//! Generate code from Previous schemas in a `build.rs`, so generated files
+//! don't have to be committed
+//!
+//! ```ignore
+//! // build.rs
+//! fn main() {
+//!     previous_build::Builder::new()
+//!         .schema("schemas/*.pr")
+//!         .target(previous::Target::Rust)
+//!         .target(previous::Target::OpenApi)
+//!         .compile()
+//!         .unwrap_or_else(|e| panic!("{}", e.format()));
+//! }
+//! ```
+//!
+//! Outputs are written to `OUT_DIR` unless another directory is given, e.g.
+//! `include!(concat!(env!("OUT_DIR"), "/previous.rs"))` for the Rust structs
+//! or `include_str!(concat!(env!("OUT_DIR"), "/openapi.json"))`. Cargo is told
+//! to rerun the build script when a schema file changes, which covers every
+//! dependency for the same reason as [`previous::SchemaWatcher`].
+
+use std::path::{Path, PathBuf};
+
+use previous::{
+    compile_file, expand_inputs, CliOptions, CodegenOptions, CompileError, DiagnosticKind, OutputLayout,
+    RebuildReport, Target,
+};
+
+/// Schemas to compile and the backends to run on them
+#[derive(Debug, Clone)]
+pub struct Builder {
+    options: CliOptions,
+    out_dir: Option<PathBuf>,
+    emit_rerun_if_changed: bool,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Builder::new()
+    }
+}
+
+impl Builder {
+    /// No schemas yet, TypeScript unless other targets are added
+    pub fn new() -> Self {
+        Builder {
+            options: CliOptions { inputs: Vec::new(), targets: Vec::new(), ..CliOptions::default() },
+            out_dir: None,
+            emit_rerun_if_changed: true,
+        }
+    }
+
+    /// Add a schema file or glob pattern, relative to the crate's Cargo.toml;
+    /// every schema is compiled as one program
+    pub fn schema(mut self, path: impl AsRef<Path>) -> Self {
+        self.options.inputs.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn schemas<P: AsRef<Path>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.options.inputs.extend(paths.into_iter().map(|path| path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Add an output target, see `Target::file_names` for the files written
+    pub fn target(mut self, target: Target) -> Self {
+        if !self.options.targets.contains(&target) {
+            self.options.targets.push(target);
+        }
+        self
+    }
+
+    pub fn codegen(mut self, codegen: CodegenOptions) -> Self {
+        self.options.codegen = codegen;
+        self
+    }
+
+    pub fn layout(mut self, layout: OutputLayout) -> Self {
+        self.options.layout = layout;
+        self
+    }
+
+    /// Write outputs here instead of `OUT_DIR`
+    pub fn out_dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.out_dir = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Print `cargo:rerun-if-changed` lines, on by default
+    pub fn emit_rerun_if_changed(mut self, emit: bool) -> Self {
+        self.emit_rerun_if_changed = emit;
+        self
+    }
+
+    /// Compile the schemas and write every target, leaving outputs that are
+    /// already up to date untouched
+    pub fn compile(mut self) -> Result<RebuildReport, CompileError> {
+        self.options.output_dir = match self.out_dir.take() {
+            Some(out_dir) => out_dir,
+            None => std::env::var_os("OUT_DIR").map(PathBuf::from).ok_or_else(|| {
+                CompileError::from(DiagnosticKind::Io {
+                    operation: "find the output directory".to_string(),
+                    path: PathBuf::from("OUT_DIR"),
+                    error: "OUT_DIR is not set, call out_dir() outside of a build script".to_string(),
+                })
+            })?,
+        };
+        if self.options.targets.is_empty() {
+            self.options.targets.push(Target::TypeScript);
+        }
+
+        // Printed before compiling, so fixing a broken schema reruns the script
+        if self.emit_rerun_if_changed {
+            for path in rerun_paths(&self.options.inputs)? {
+                println!("cargo:rerun-if-changed={}", path.display());
+            }
+        }
+        compile_file(&self.options)
+    }
+}
+
+/// Every schema file, plus the directory of each glob pattern so files
+/// added later are picked up
+fn rerun_paths(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, CompileError> {
+    let mut paths = expand_inputs(inputs)?;
+    for pattern in inputs {
+        let literal: PathBuf = pattern
+            .components()
+            .take_while(|component| !component.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+            .collect();
+        if literal != *pattern && !paths.contains(&literal) {
+            paths.push(literal);
+        }
+    }
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_builder() {
+        let dir = std::env::temp_dir().join(format!("previous_build_{}", std::process::id()));
+        let schemas = dir.join("schemas");
+        fs::create_dir_all(&schemas).unwrap();
+        fs::write(schemas.join("user.pr"), "resource User {\n    string name\n}\n").unwrap();
+        fs::write(schemas.join("post.pr"), "resource Post {\n    User author\n}\n").unwrap();
+        let out = dir.join("out");
+        let pattern = schemas.join("*.pr");
+
+        let report = Builder::new()
+            .schema(&pattern)
+            .target(Target::TypeScript)
+            .target(Target::OpenApi)
+            .target(Target::Rust)
+            .out_dir(&out)
+            .emit_rerun_if_changed(false)
+            .compile()
+            .unwrap();
+        assert_eq!(report.written.len(), 4);
+        let openapi = fs::read_to_string(out.join("openapi.json")).unwrap();
+        assert!(openapi.contains("\"Post\"") && openapi.contains("\"User\""));
+        let rust = fs::read_to_string(out.join("previous.rs")).unwrap();
+        assert!(rust.contains("pub struct Post {\n    pub author: User,\n}"));
+
+        // A second build leaves up to date outputs alone
+        let report = Builder::new().schema(&pattern).out_dir(&out).emit_rerun_if_changed(false).compile().unwrap();
+        assert!(report.written.is_empty());
+        assert_eq!(report.unchanged.len(), 2);
+
+        assert_eq!(
+            rerun_paths(&[pattern]).unwrap(),
+            vec![schemas.join("post.pr"), schemas.join("user.pr"), schemas.clone()]
+        );
+
+        fs::write(schemas.join("user.pr"), "resource User {\n    Missing name\n}\n").unwrap();
+        let err = Builder::new()
+            .schema(schemas.join("user.pr"))
+            .out_dir(&out)
+            .emit_rerun_if_changed(false)
+            .compile()
+            .unwrap_err();
+        assert_eq!(err.diagnostics[0].code(), "P0003");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,368 @@
+//! `#[derive(Previous)]`, checks a struct against a resource of a `.pr`
+//! schema at compile time and implements `previous::Wire` for it
+//!
+//! ```ignore
+//! #[derive(Previous)]
+//! #[previous(schema = "schema.pr", resource = "User")]
+//! struct User {
+//!     name: String,
+//!     age: Option<i64>,
+//!     tags: Vec<Tag>,
+//! }
+//! ```
+//!
+//! The schema path is relative to the crate's Cargo.toml. Every field of the
+//! resource must be declared, in the schema's order, with the Rust type of
+//! its schema type:
+//! - `string`, `number` and `bool` are `String`, `i64` and `bool`
+//! - `list T` is `Vec<T>` and a resource is a type of the same name that
+//!   implements `Wire`, usually derived too
+//! - `optional` and `nullable` each wrap the type in an `Option`
+//!
+//! Rust field names may be the snake_case form of the schema's (`created_at`
+//! for `createdAt`). Any drift is a compile error on the offending field.
+
+use std::path::PathBuf;
+
+use previous::{snake_case, IRField, IRProgram, IRResource, IRType};
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, GenericArgument, LitStr, PathArguments, Type};
+
+#[proc_macro_derive(Previous, attributes(previous))]
+pub fn derive_previous(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+/// Arguments of `#[previous(schema = "...", resource = "...")]`
+struct Attributes {
+    schema: LitStr,
+    resource: LitStr,
+}
+
+fn attributes(input: &DeriveInput) -> syn::Result<Attributes> {
+    let mut schema = None;
+    let mut resource = None;
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("previous")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("schema") {
+                schema = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("resource") {
+                resource = Some(meta.value()?.parse()?);
+            } else {
+                return Err(meta.error("expected `schema` or `resource`"));
+            }
+            Ok(())
+        })?;
+    }
+    match (schema, resource) {
+        (Some(schema), Some(resource)) => Ok(Attributes { schema, resource }),
+        _ => Err(syn::Error::new(
+            input.ident.span(),
+            "expected #[previous(schema = \"<file>.pr\", resource = \"<Name>\")]",
+        )),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let attributes = attributes(&input)?;
+    let fields: Vec<&syn::Field> = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => named.named.iter().collect(),
+            _ => return Err(syn::Error::new(input.ident.span(), "Previous resources must have named fields")),
+        },
+        _ => return Err(syn::Error::new(input.ident.span(), "Previous can only be derived for structs")),
+    };
+
+    let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let path = PathBuf::from(manifest_dir).join(attributes.schema.value());
+    let source = std::fs::read_to_string(&path).map_err(|e| {
+        syn::Error::new(attributes.schema.span(), format!("Failed to read '{}': {}", path.display(), e))
+    })?;
+    let ir = previous::resolve_schema(&source).map_err(|e| {
+        let messages: Vec<String> = e.diagnostics.iter().map(|d| format!("[{}] {}", d.code(), d.message())).collect();
+        syn::Error::new(attributes.schema.span(), format!("Invalid schema '{}': {}", path.display(), messages.join("; ")))
+    })?;
+    let resource = ir.get_resource(&attributes.resource.value()).ok_or_else(|| {
+        syn::Error::new(
+            attributes.resource.span(),
+            format!("No resource {} in '{}'", attributes.resource.value(), path.display()),
+        )
+    })?;
+    check_fields(&input.ident, &fields, resource, &ir)?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let idents: Vec<_> = fields.iter().map(|field| &field.ident).collect();
+    let types: Vec<_> = fields.iter().map(|field| &field.ty).collect();
+    // Recompile whenever the schema changes
+    let schema_path = path.display().to_string();
+    Ok(quote! {
+        const _: &[u8] = include_bytes!(#schema_path);
+
+        impl #impl_generics ::previous::Wire for #name #ty_generics #where_clause {
+            const MIN_SIZE: usize = 0 #( + <#types as ::previous::Wire>::MIN_SIZE )*;
+
+            fn encode(&self, encoder: &mut ::previous::BinaryEncoder) {
+                #( ::previous::Wire::encode(&self.#idents, encoder); )*
+            }
+
+            fn decode(decoder: &mut ::previous::BinaryDecoder<'_>) -> Result<Self, ::previous::CompileError> {
+                Ok(#name {
+                    #( #idents: ::previous::Wire::decode(decoder)?, )*
+                })
+            }
+        }
+    })
+}
+
+/// Check the struct declares the resource's fields, in order, with matching
+/// types; every problem found is reported
+fn check_fields(ident: &syn::Ident, fields: &[&syn::Field], resource: &IRResource, ir: &IRProgram) -> syn::Result<()> {
+    let mut errors: Vec<syn::Error> = Vec::new();
+    let names: Vec<String> = fields.iter().map(|field| field_name(field)).collect();
+    let positions: Vec<Option<usize>> = names
+        .iter()
+        .map(|name| resource.fields.iter().position(|ir_field| matches_name(&ir_field.name, name)))
+        .collect();
+
+    for ((field, name), position) in fields.iter().zip(&names).zip(&positions) {
+        if position.is_none() {
+            errors.push(syn::Error::new(
+                field.span(),
+                format!("`{}` is not a field of {}", name, resource.qualified_name()),
+            ));
+        }
+    }
+    for (index, ir_field) in resource.fields.iter().enumerate() {
+        if !positions.contains(&Some(index)) {
+            errors.push(syn::Error::new(
+                ident.span(),
+                format!(
+                    "missing field `{}` of {}, expected `{}: {}`",
+                    ir_field.name,
+                    resource.qualified_name(),
+                    snake_case(&ir_field.name),
+                    rust_type(ir_field, ir)
+                ),
+            ));
+        }
+    }
+    // Positions only mean something once the fields are the same set
+    if errors.is_empty() {
+        for (index, (field, position)) in fields.iter().zip(&positions).enumerate() {
+            let position = position.unwrap_or(index);
+            if position != index {
+                errors.push(syn::Error::new(
+                    field.span(),
+                    format!(
+                        "field `{}` is out of order, {} declares it at position {}",
+                        resource.fields[position].name,
+                        resource.qualified_name(),
+                        position + 1
+                    ),
+                ));
+            }
+        }
+    }
+    for (field, position) in fields.iter().zip(&positions) {
+        let Some(ir_field) = position.map(|position| &resource.fields[position]) else { continue };
+        if !field_type_matches(&field.ty, ir_field, ir) {
+            errors.push(syn::Error::new(
+                field.ty.span(),
+                format!(
+                    "field `{}` of {} is `{}`, expected type `{}`",
+                    ir_field.name,
+                    resource.qualified_name(),
+                    schema_type(ir_field, ir),
+                    rust_type(ir_field, ir)
+                ),
+            ));
+        }
+    }
+
+    let mut errors = errors.into_iter();
+    match errors.next() {
+        Some(mut first) => {
+            first.extend(errors);
+            Err(first)
+        }
+        None => Ok(()),
+    }
+}
+
+fn field_name(field: &syn::Field) -> String {
+    let name = field.ident.as_ref().map(|ident| ident.to_string()).unwrap_or_default();
+    name.strip_prefix("r#").map(str::to_string).unwrap_or(name)
+}
+
+/// A Rust field matches a schema field of the same name, or its snake_case form
+fn matches_name(schema_name: &str, rust_name: &str) -> bool {
+    schema_name == rust_name || snake_case(schema_name) == rust_name
+}
+
+/// Schema syntax of a field's type, e.g. `optional list Tag`
+fn schema_type(field: &IRField, ir: &IRProgram) -> String {
+    let mut modifiers = String::new();
+    if field.optional {
+        modifiers.push_str("optional ");
+    }
+    if field.nullable {
+        modifiers.push_str("nullable ");
+    }
+    format!("{}{}", modifiers, ir.type_name(&field.field_type))
+}
+
+/// Rust type a field must be declared with, e.g. `Option<Vec<Tag>>`
+fn rust_type(field: &IRField, ir: &IRProgram) -> String {
+    let mut rust = rust_type_of(&field.field_type, ir);
+    for _ in 0..(field.optional as usize + field.nullable as usize) {
+        rust = format!("Option<{}>", rust);
+    }
+    rust
+}
+
+fn rust_type_of(ir_type: &IRType, ir: &IRProgram) -> String {
+    match ir_type {
+        IRType::Primitive(p) => primitive_rust_type(p).to_string(),
+        IRType::ResourceRef(idx) => ir.resources[*idx].name.clone(),
+        IRType::List(inner) => format!("Vec<{}>", rust_type_of(inner, ir)),
+    }
+}
+
+fn primitive_rust_type(primitive: &str) -> &'static str {
+    match primitive {
+        "string" => "String",
+        "number" => "i64",
+        _ => "bool",
+    }
+}
+
+fn field_type_matches(ty: &Type, field: &IRField, ir: &IRProgram) -> bool {
+    let mut ty = ty;
+    for _ in 0..(field.optional as usize + field.nullable as usize) {
+        match generic_argument(ty, "Option") {
+            Some(inner) => ty = inner,
+            None => return false,
+        }
+    }
+    type_matches(ty, &field.field_type, ir)
+}
+
+/// Types are compared by the last segment of their path, so `std::vec::Vec`
+/// and an imported `Vec` both match a list
+fn type_matches(ty: &Type, ir_type: &IRType, ir: &IRProgram) -> bool {
+    match ir_type {
+        IRType::Primitive(p) => is_plain(ty, primitive_rust_type(p)),
+        IRType::ResourceRef(idx) => is_plain(ty, &ir.resources[*idx].name),
+        IRType::List(inner) => generic_argument(ty, "Vec").is_some_and(|item| type_matches(item, inner, ir)),
+    }
+}
+
+fn last_segment(ty: &Type) -> Option<&syn::PathSegment> {
+    match ty {
+        Type::Path(path) if path.qself.is_none() => path.path.segments.last(),
+        Type::Group(group) => last_segment(&group.elem),
+        _ => None,
+    }
+}
+
+/// `ty` is `name`, without generic arguments
+fn is_plain(ty: &Type, name: &str) -> bool {
+    last_segment(ty).is_some_and(|segment| segment.ident == name && segment.arguments.is_none())
+}
+
+/// `T` when `ty` is `wrapper<T>`
+fn generic_argument<'a>(ty: &'a Type, wrapper: &str) -> Option<&'a Type> {
+    let segment = last_segment(ty).filter(|segment| segment.ident == wrapper)?;
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else { return None };
+    match arguments.args.iter().collect::<Vec<_>>().as_slice() {
+        [GenericArgument::Type(inner)] => Some(inner),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    fn check(input: DeriveInput) -> Result<(), Vec<String>> {
+        let ir = previous::resolve_schema(
+            "resource Tag { string name }\nresource User {\n    string name\n    optional number age\n    optional nullable bool active\n    list Tag tags\n    string createdAt\n}",
+        )
+        .unwrap();
+        let Data::Struct(data) = &input.data else { panic!("expected a struct") };
+        let fields: Vec<&syn::Field> = data.fields.iter().collect();
+        check_fields(&input.ident, &fields, ir.get_resource("User").unwrap(), &ir)
+            .map_err(|e| e.into_iter().map(|e| e.to_string()).collect())
+    }
+
+    #[test]
+    fn test_check_fields() {
+        assert_eq!(
+            check(parse_quote! {
+                struct User {
+                    name: String,
+                    age: Option<i64>,
+                    active: Option<Option<bool>>,
+                    tags: std::vec::Vec<Tag>,
+                    created_at: String,
+                }
+            }),
+            Ok(())
+        );
+
+        assert_eq!(
+            check(parse_quote! {
+                struct User {
+                    name: String,
+                    age: i64,
+                    active: Option<bool>,
+                    tags: Vec<String>,
+                    createdAt: String,
+                }
+            }),
+            Err(vec![
+                "field `age` of User is `optional number`, expected type `Option<i64>`".to_string(),
+                "field `active` of User is `optional nullable bool`, expected type `Option<Option<bool>>`".to_string(),
+                "field `tags` of User is `list Tag`, expected type `Vec<Tag>`".to_string(),
+            ])
+        );
+
+        assert_eq!(
+            check(parse_quote! {
+                struct User {
+                    name: String,
+                    email: String,
+                    tags: Vec<Tag>,
+                }
+            }),
+            Err(vec![
+                "`email` is not a field of User".to_string(),
+                "missing field `age` of User, expected `age: Option<i64>`".to_string(),
+                "missing field `active` of User, expected `active: Option<Option<bool>>`".to_string(),
+                "missing field `createdAt` of User, expected `created_at: String`".to_string(),
+            ])
+        );
+
+        assert_eq!(
+            check(parse_quote! {
+                struct User {
+                    age: Option<i64>,
+                    name: String,
+                    active: Option<Option<bool>>,
+                    tags: Vec<Tag>,
+                    created_at: String,
+                }
+            }),
+            Err(vec![
+                "field `age` is out of order, User declares it at position 2".to_string(),
+                "field `name` is out of order, User declares it at position 1".to_string(),
+            ])
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use previous::{BinaryEncoder, IRType, Value, Wire};
+use previous_derive::Previous;
+use serde_json::json;
+
+#[derive(Debug, PartialEq, Previous)]
+#[previous(schema = "tests/user.pr", resource = "Tag")]
+struct Tag {
+    name: String,
+}
+
+#[derive(Debug, PartialEq, Previous)]
+#[previous(schema = "tests/user.pr", resource = "User")]
+struct User {
+    name: String,
+    age: Option<i64>,
+    active: Option<bool>,
+    tags: Vec<Tag>,
+    created_at: String,
+}
+
+#[test]
+fn test_derive_matches_binary_encoder() {
+    let user = User {
+        name: "Ada".to_string(),
+        age: None,
+        active: Some(true),
+        tags: vec![Tag { name: "admin".to_string() }],
+        created_at: "1815-12-10".to_string(),
+    };
+
+    let ir = previous::resolve_schema(include_str!("user.pr")).unwrap();
+    let user_type = IRType::ResourceRef(ir.get_resource_index("User").unwrap());
+    let value = Value::from_json(
+        json!({ "name": "Ada", "active": true, "tags": [{ "name": "admin" }], "createdAt": "1815-12-10" }),
+        &user_type,
+        &ir,
+    )
+    .unwrap();
+    let mut encoder = BinaryEncoder::new();
+    encoder.encode_value(&value, &user_type, &ir).unwrap();
+
+    let bytes = user.to_bytes();
+    assert_eq!(bytes, encoder.finish());
+    assert_eq!(User::from_bytes(&bytes).unwrap(), user);
+    assert!(User::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn test_derive_bounds_list_counts() {
+    // Four length prefixes, two flags
+    assert_eq!(User::MIN_SIZE, 14);
+
+    // Two users can't fit in the bytes left after the count
+    let mut bytes = 2u32.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&[0; 20]);
+    let err = Vec::<User>::from_bytes(&bytes).unwrap_err();
+    assert_eq!(
+        err.diagnostics[0].message(),
+        "Malformed payload at byte 4: list of 2 items needs at least 14 bytes per item, 20 left"
+    );
+}
+
+/// Hand-written impl relying on the default `MIN_SIZE`
+#[derive(Debug, PartialEq)]
+struct Marker;
+
+impl Wire for Marker {
+    fn encode(&self, _encoder: &mut BinaryEncoder) {}
+
+    fn decode(_decoder: &mut previous::BinaryDecoder<'_>) -> Result<Self, previous::CompileError> {
+        Ok(Marker)
+    }
+}
+
+#[test]
+fn test_hand_written_wire_defaults_min_size() {
+    let markers = vec![Marker, Marker, Marker];
+    let bytes = markers.to_bytes();
+    assert_eq!(bytes, 3u32.to_le_bytes());
+    assert_eq!(Vec::<Marker>::from_bytes(&bytes).unwrap(), markers);
+    assert!(Vec::<Marker>::from_bytes(&u32::MAX.to_le_bytes()).is_err());
+}
@@ -3,6 +3,7 @@
         Previous Schema Language (PSL) is a domain-specific language used to define Resources, the primary units of data exchanged between the server and client in the Previous BFF framework.
         A schema file (*.pr) describes:
             - the data structures (Resources)
+            - the HTTP endpoints exchanging them (Services)
             - fields and their types
             - attributes of these fields (e.g., repeated)
             - documentation for generated code
@@ -10,9 +11,9 @@
         Schemas are input to the previousc compiler, which generates code for server and client implementations.
 
     2. Compiler
-        Only one input file is allowed
+        Several input files (or glob patterns) can be given, they are compiled as one program
         Imports are not supported
-        The compiler should garantee uniqueness in Resource names
+        The compiler should garantee uniqueness in Resource names, across every input file
         The compiler should garantee uniqueness within Resource field names
         The output directory can be specified with `--out` param
         Cyclic dependencies are not supported
@@ -26,7 +27,8 @@
         3.1. White space
             Whitespace (space, tab, newline) is ignored except where required to separate tokens.
         3.2. Comments
-            There is no specification for comments.
+            Line comments start with `//` and run to the end of the line.
+            Comments are kept in the AST so `previouscc fmt` can preserve them.
         3.3. Identifiers
             Identifiers must start with letters. eg.: [a-zA-Z_]
             Identifiers are case-sensitive
@@ -34,7 +36,9 @@
             Symbols or special characters are not allowed
             Resource identifiers must follow PascalCase
         3.4. Keywords
+            `package`
             `resource`
+            `service`
             `string`
             `number`
             `bool`
@@ -79,6 +83,36 @@
                 - `optional`: Make the field optional
                 - `default(value)`: Create a default value for the field. The default value must be the same type as the field
                 - `list`: The field support zero or more items of the given type
+        3.7. Packages
+            A file can start with `package billing` (dotted names such as `acme.billing` are allowed)
+            Resource names only need to be unique within their package
+            Other packages' resources are referred to by qualified name, eg.: `billing.Invoice`
+            A plain name refers to the file's own package first, then to resources outside any package
+            TypeScript output wraps each package in a `namespace`, Rust output in a `mod`, proto and GraphQL use `billing_Invoice`
+
+            Example:
+            ```
+            package billing
+
+            resource Invoice {
+                shop.User buyer
+            }
+            ```
+        3.8. Services
+            A service lists HTTP endpoints, one per line, a `;` may end the line
+            An endpoint is a method (`get`, `post`, `put`, `patch`, `delete`), a path, an optional request resource and an optional `-> Response`
+            Paths start with `/`, `:name` segments are parameters and must be unique within the path
+            `get` and `delete` endpoints cannot have a request body
+            Service names are unique within their package, method and path unique within the service
+            Request and response types must be resources
+
+            Example:
+            ```
+            service UserApi {
+                get "/users/:id" -> User;
+                post "/users" UserInput -> User
+            }
+            ```
     4. Binary Encoding Model
         4.1. Field Ordering
             Fields are encoded in the order they appear in the Resource.
@@ -95,10 +129,16 @@
         ```
 
     5. BNF
-        <program> ::= <resource_list>
+        <program> ::= <package_decl> <declaration_list>
+            | <declaration_list>
+
+        <package_decl> ::= "package" <qualified_identifier>
 
-        <resource_list> ::= <resource>
-            |  <resource> <resource_list>
+        <declaration_list> ::= <declaration>
+            |  <declaration> <declaration_list>
+
+        <declaration> ::= <resource>
+            | <service>
 
         ;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;
         ;; RESOURCE DECLARATIONS
@@ -112,9 +152,28 @@
         <field> ::= <attributes> <type> <identifier>
             | <type> <identifier>
 
+        ;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;
+        ;; SERVICE DECLARATIONS
+        ;; one endpoint per line, an optional ";" may end it
+        ;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;
+
+        <service> ::= "service" <resource_identifier> "{" <endpoint_list> "}"
+
+        <endpoint_list> ::= ε
+            |  <endpoint> <endpoint_list>
+
+        <endpoint> ::= <http_method> <string_literal> <request> <response>
+
+        <http_method> ::= "get" | "post" | "put" | "patch" | "delete"
+
+        <request> ::= <type> | ε
+
+        <response> ::= "->" <type> | ε
+
         ;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;
         ;; ATTRIBUTES
         ;; order: zero or more attributes BEFORE the type
+        ;; (attributes after the type are accepted, `previouscc fmt` moves them before it)
         ;; examples:
         ;;   optional number age
         ;;   nullable list User
@@ -145,6 +204,7 @@
             | "bool"
             | "list" <type>
             | <resource_identifier>
+            | <qualified_identifier> "." <resource_identifier>
 
         ;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;;
         ;; IDENTIFIERS
@@ -160,6 +220,9 @@
 
         <resource_identifier> ::= <capital_letter> <identifier_rest_optional>
 
+        <qualified_identifier> ::= <identifier>
+            | <identifier> "." <qualified_identifier>
+
         <identifier_rest_optional> ::= <identifier_rest>
             | ε
 
@@ -220,6 +283,32 @@ pub struct DefaultValue {
     pub value: Literal,
 }
 
+/// Location of a token in the source, 1-based line and column in characters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Span {
+    /// Whether the 1-based `line` and `column` fall inside the span
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        self.line == line && column >= self.column && column <= self.column + self.length
+    }
+}
+
+/// Comments and blank lines attached to a node, kept for the formatter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Trivia {
+    /// `//` comments on the lines before the node (without the slashes)
+    pub leading: Vec<String>,
+    /// `//` comment at the end of the node's line
+    pub trailing: Option<String>,
+    /// Whether the node was separated from the previous one by a blank line
+    pub blank_line_before: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Field {
     pub name: String,
@@ -228,17 +317,171 @@ pub struct Field {
     pub optional: bool,
     pub default: Option<DefaultValue>,
     pub index: usize,
+    pub trivia: Trivia,
+    /// Span of the field name
+    pub name_span: Span,
+    /// Span of the innermost type token, e.g. `User` in `list User`
+    pub type_span: Span,
 }
 
 #[derive(Debug, Clone)]
 pub struct Resource {
     pub name: String,
+    /// Package from the file's `package` declaration
+    pub package: Option<String>,
     pub fields: Vec<Field>,
+    /// Span of the resource name
+    pub name_span: Span,
+    /// Leading comments and the comment on the `resource Name {` line
+    pub trivia: Trivia,
+    /// Comments after the last field, before the closing brace
+    pub closing_comments: Vec<String>,
+    /// Schema file the resource was declared in, set when compiling
+    /// several files together
+    pub file: Option<PathBuf>,
+}
+
+/// HTTP method of an endpoint, written in lowercase in schemas
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl HttpMethod {
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "get" => Some(HttpMethod::Get),
+            "post" => Some(HttpMethod::Post),
+            "put" => Some(HttpMethod::Put),
+            "patch" => Some(HttpMethod::Patch),
+            "delete" => Some(HttpMethod::Delete),
+            _ => None,
+        }
+    }
+
+    /// Keyword used in schemas, e.g. `get`
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            HttpMethod::Get => "get",
+            HttpMethod::Post => "post",
+            HttpMethod::Put => "put",
+            HttpMethod::Patch => "patch",
+            HttpMethod::Delete => "delete",
+        }
+    }
+
+    /// Whether requests may carry a body
+    pub fn has_body(&self) -> bool {
+        !matches!(self, HttpMethod::Get | HttpMethod::Delete)
+    }
+}
+
+impl std::fmt::Display for HttpMethod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.keyword().to_uppercase())
+    }
+}
+
+/// `post "/users" UserInput -> User` inside a service
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub method: HttpMethod,
+    /// Path with `:name` parameters, e.g. `/users/:id`
+    pub path: String,
+    /// Resource sent as the request body
+    pub request: Option<ASTType>,
+    /// Resource sent back, after `->`
+    pub response: Option<ASTType>,
+    pub trivia: Trivia,
+    /// Span of the method keyword
+    pub span: Span,
+    /// Span of the innermost request type token, like `Field::type_span`
+    pub request_span: Span,
+    /// Span of the innermost response type token
+    pub response_span: Span,
+}
+
+impl Endpoint {
+    /// `GET /users/:id`, as used in messages
+    pub fn describe(&self) -> String {
+        format!("{} {}", self.method, self.path)
+    }
+}
+
+/// `service UserApi { ... }`: endpoints exchanging resources
+#[derive(Debug, Clone)]
+pub struct Service {
+    pub name: String,
+    /// Package from the file's `package` declaration
+    pub package: Option<String>,
+    pub endpoints: Vec<Endpoint>,
+    /// Span of the service name
+    pub name_span: Span,
+    /// Leading comments and the comment on the `service Name {` line
+    pub trivia: Trivia,
+    /// Comments after the last endpoint, before the closing brace
+    pub closing_comments: Vec<String>,
+    /// Schema file the service was declared in, see `Resource::file`
+    pub file: Option<PathBuf>,
+}
+
+impl Service {
+    pub fn qualified_name(&self) -> String {
+        qualify(self.package.as_deref(), &self.name)
+    }
+}
+
+/// `package billing` at the top of a file
+#[derive(Debug, Clone)]
+pub struct PackageDecl {
+    /// Package name, possibly dotted, e.g. `acme.billing`
+    pub name: String,
+    pub span: Span,
+    pub trivia: Trivia,
 }
 
 #[derive(Debug, Clone)]
 pub struct Program {
+    pub package: Option<PackageDecl>,
     pub resources: Vec<Resource>,
+    pub services: Vec<Service>,
+    /// Comments after the last resource
+    pub trailing_comments: Vec<String>,
+}
+
+/// `package.Name`, or just `Name` outside of any package
+fn qualify(package: Option<&str>, name: &str) -> String {
+    match package {
+        Some(package) => format!("{}.{}", package, name),
+        None => name.to_string(),
+    }
+}
+
+impl Resource {
+    /// Name other packages refer to the resource by, e.g. `billing.Invoice`
+    pub fn qualified_name(&self) -> String {
+        qualify(self.package.as_deref(), &self.name)
+    }
+}
+
+impl Program {
+    /// Index of the resource a type name written in `package` refers to
+    ///
+    /// Qualified names (`billing.Invoice`) are looked up as written. Plain
+    /// names are looked up in `package` first, then outside any package.
+    pub fn resolve_name(&self, package: Option<&str>, name: &str) -> Option<usize> {
+        let find = |qualified: &str| self.resources.iter().position(|r| r.qualified_name() == qualified);
+        if name.contains('.') {
+            return find(name);
+        }
+        package
+            .and_then(|package| find(&qualify(Some(package), name)))
+            .or_else(|| find(name))
+    }
 }
 
 // ============================================================================
@@ -268,1507 +511,8432 @@ pub struct IRField {
 #[derive(Debug, Clone)]
 pub struct IRResource {
     pub name: String,
+    /// Package the resource was declared in, see `Resource::package`
+    pub package: Option<String>,
     pub fields: Vec<IRField>,
+    /// Schema file the resource was declared in, see `Resource::file`
+    pub file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct IREndpoint {
+    pub method: HttpMethod,
+    pub path: String,
+    /// Index of the request body resource in IRProgram.resources
+    pub request: Option<usize>,
+    /// Index of the response resource in IRProgram.resources
+    pub response: Option<usize>,
+}
+
+impl IREndpoint {
+    /// Names of the `:name` path parameters, in order
+    pub fn params(&self) -> Vec<&str> {
+        self.path
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix(':'))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IRService {
+    pub name: String,
+    pub package: Option<String>,
+    pub endpoints: Vec<IREndpoint>,
+    pub file: Option<PathBuf>,
+}
+
+impl IRService {
+    pub fn qualified_name(&self) -> String {
+        qualify(self.package.as_deref(), &self.name)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct IRProgram {
     pub resources: Vec<IRResource>,
+    pub services: Vec<IRService>,
+}
+
+impl IRResource {
+    /// Name other packages refer to the resource by, e.g. `billing.Invoice`
+    pub fn qualified_name(&self) -> String {
+        qualify(self.package.as_deref(), &self.name)
+    }
 }
 
 impl IRProgram {
-    /// Find a resource by name, returning its index
+    /// Find a resource by qualified name, returning its index
     pub fn get_resource_index(&self, name: &str) -> Option<usize> {
-        self.resources.iter().position(|r| r.name == name)
+        self.resources.iter().position(|r| r.qualified_name() == name)
     }
 
-    /// Find a resource by name
+    /// Find a resource by qualified name, e.g. `User` or `billing.Invoice`
     pub fn get_resource(&self, name: &str) -> Option<&IRResource> {
-        self.resources.iter().find(|r| r.name == name)
+        self.resources.iter().find(|r| r.qualified_name() == name)
+    }
+
+    /// Schema syntax of a type, e.g. `list User`
+    pub fn type_name(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => p.clone(),
+            IRType::ResourceRef(idx) => self
+                .resources
+                .get(*idx)
+                .map(|r| r.qualified_name())
+                .unwrap_or_else(|| format!("<resource {}>", idx)),
+            IRType::List(inner) => format!("list {}", self.type_name(inner)),
+        }
+    }
+
+    /// The resources at `roots`, the services at `services` and every
+    /// resource they reference, in their original order, with resource
+    /// indices renumbered
+    pub fn subset(&self, roots: &[usize], services: &[usize]) -> IRProgram {
+        let mut included = vec![false; self.resources.len()];
+        let mut stack = roots.to_vec();
+        for &service in services {
+            for endpoint in &self.services[service].endpoints {
+                stack.extend(endpoint.request.into_iter().chain(endpoint.response));
+            }
+        }
+        while let Some(idx) = stack.pop() {
+            if std::mem::replace(&mut included[idx], true) {
+                continue;
+            }
+            for field in &self.resources[idx].fields {
+                let mut field_type = &field.field_type;
+                while let IRType::List(inner) = field_type {
+                    field_type = inner;
+                }
+                if let IRType::ResourceRef(target) = field_type {
+                    stack.push(*target);
+                }
+            }
+        }
+
+        let mut new_index = vec![None; self.resources.len()];
+        let mut resources = Vec::new();
+        for (idx, resource) in self.resources.iter().enumerate() {
+            if included[idx] {
+                new_index[idx] = Some(resources.len());
+                resources.push(resource.clone());
+            }
+        }
+
+        fn renumber(ir_type: &mut IRType, new_index: &[Option<usize>]) {
+            match ir_type {
+                IRType::Primitive(_) => {}
+                IRType::ResourceRef(idx) => *idx = new_index[*idx].expect("referenced resources are included"),
+                IRType::List(inner) => renumber(inner, new_index),
+            }
+        }
+        for resource in &mut resources {
+            for field in &mut resource.fields {
+                renumber(&mut field.field_type, &new_index);
+            }
+        }
+
+        let services = services
+            .iter()
+            .map(|&service| {
+                let mut service = self.services[service].clone();
+                for endpoint in &mut service.endpoints {
+                    endpoint.request = endpoint.request.and_then(|idx| new_index[idx]);
+                    endpoint.response = endpoint.response.and_then(|idx| new_index[idx]);
+                }
+                service
+            })
+            .collect();
+
+        IRProgram { resources, services }
     }
 }
 
 // ============================================================================
-// BINARY ENCODING MODEL (Phase 3)
+// DIAGNOSTICS
 // ============================================================================
 //
-// Binary Encoding Specification:
-// - string:    u32 length (little-endian) + UTF-8 bytes
-// - number:    i64 (8 bytes, little-endian)
-// - bool:      1 byte (0x00 = false, 0x01 = true)
-// - list:      u32 count (little-endian) + each item encoded recursively
-// - nullable:  1 byte (0x00 = null, 0x01 = present) + value if present
-// - optional:  1 byte (0x00 = absent, 0x01 = present) + value if present
-// - resource:  fields encoded in order (field index is implicit)
+// Every stage reports problems as `Diagnostic`s: a typed kind with a stable
+// error code, a severity and, when known, the span in the source. They are
+// collected in a `CompileError`, which renders them rustc-style:
+//
+//   error[P0003]: Undefined type: Missing
+//    --> schema.pr:3:5
+//     |
+//   3 |     Missing other
+//     |     ^^^^^^^
 //
 
-/// Runtime value representation for encoding
-#[derive(Debug, Clone, PartialEq)]
-pub enum Value {
-    String(String),
-    Number(i64),
-    Bool(bool),
-    List(Vec<Value>),
-    Resource(Vec<FieldValue>),
-    Null,
-    Absent,
+/// Severity of a reported problem
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Warning,
+    Error,
 }
 
-/// Field value with optional/nullable handling
-#[derive(Debug, Clone, PartialEq)]
-pub struct FieldValue {
-    pub name: String,
-    pub value: Value,
-    pub is_optional: bool,
-    pub is_nullable: bool,
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
 }
 
-/// Binary encoder for Previous values
-pub struct BinaryEncoder {
-    buffer: Vec<u8>,
+/// Compiler stage a diagnostic comes from, ordered like the pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorCategory {
+    /// Reading the schema or writing outputs failed
+    Io,
+    /// The schema could not be parsed
+    Syntax,
+    /// The schema parsed but is invalid, or a value does not match it
+    Semantic,
+    /// A target could not be generated
+    Codegen,
+    /// The project configuration is invalid
+    Config,
 }
 
-impl BinaryEncoder {
-    pub fn new() -> Self {
-        BinaryEncoder { buffer: Vec::new() }
+impl ErrorCategory {
+    /// Process exit code reported by the CLI, stable across releases
+    ///
+    /// 0 is success and 2 is left to command line usage errors.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCategory::Io => 1,
+            ErrorCategory::Syntax => 3,
+            ErrorCategory::Semantic => 4,
+            ErrorCategory::Codegen => 5,
+            ErrorCategory::Config => 6,
+        }
     }
 
-    /// Get the encoded bytes
-    pub fn finish(self) -> Vec<u8> {
-        self.buffer
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCategory::Io => "io",
+            ErrorCategory::Syntax => "syntax",
+            ErrorCategory::Semantic => "semantic",
+            ErrorCategory::Codegen => "codegen",
+            ErrorCategory::Config => "config",
+        }
     }
+}
 
-    /// Encode a value based on its type
-    pub fn encode_value(&mut self, value: &Value, ir_type: &IRType, ir_program: &IRProgram) -> Result<(), String> {
-        match (value, ir_type) {
-            (Value::String(s), IRType::Primitive(p)) if p == "string" => {
-                self.encode_string(s);
-                Ok(())
-            }
-            (Value::Number(n), IRType::Primitive(p)) if p == "number" => {
-                self.encode_number(*n);
-                Ok(())
+/// What went wrong, each kind has a stable error code
+#[derive(Debug, Clone, PartialEq)]
+pub enum DiagnosticKind {
+    /// P0001: two resources share a name
+    DuplicateResource { name: String },
+    /// P0002: two fields of a resource share a name
+    DuplicateField { resource: String, field: String },
+    /// P0003: a field type names no primitive or resource
+    UndefinedType { name: String },
+    /// P0004: resources reference each other in a loop, the path starts and
+    /// ends with the same resource
+    Cycle { path: Vec<String> },
+    /// P0005: a value does not match its declared type
+    TypeMismatch { expected: String, got: String },
+    /// P0006: the parser found a token it did not expect
+    UnexpectedToken { expected: String, got: String },
+    /// P0007: a resource name is not PascalCase
+    InvalidResourceName { name: String },
+    /// P0008: a file could not be read or written
+    Io { operation: String, path: PathBuf, error: String },
+    /// P0009: a target could not be generated from a valid schema
+    Codegen { target: String, message: String },
+    /// P0010: two services share a name
+    DuplicateService { name: String },
+    /// P0011: two endpoints, in the same or different services, share a
+    /// method and path
+    DuplicateEndpoint { service: String, endpoint: String },
+    /// P0012: an endpoint's path or body is not allowed
+    InvalidEndpoint { service: String, endpoint: String, reason: String },
+    /// P0013: a payload does not follow the binary encoding of its type
+    Decode { offset: usize, reason: String },
+    /// P0014: a `previous.toml` could not be understood
+    InvalidConfig { message: String },
+    /// P0015: a `.proto` file could not be imported
+    InvalidProto { message: String },
+    /// P0016: a field name is not one of the resource's
+    UnknownField { resource: String, field: String },
+    /// P0017: a value leaves out a required field
+    MissingField { resource: String, field: String },
+    /// P0018: a value could not be serialized, as reported by serde
+    Serialize { message: String },
+}
+
+impl DiagnosticKind {
+    /// Stable error code, e.g. `P0001`
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticKind::DuplicateResource { .. } => "P0001",
+            DiagnosticKind::DuplicateField { .. } => "P0002",
+            DiagnosticKind::UndefinedType { .. } => "P0003",
+            DiagnosticKind::Cycle { .. } => "P0004",
+            DiagnosticKind::TypeMismatch { .. } => "P0005",
+            DiagnosticKind::UnexpectedToken { .. } => "P0006",
+            DiagnosticKind::InvalidResourceName { .. } => "P0007",
+            DiagnosticKind::Io { .. } => "P0008",
+            DiagnosticKind::Codegen { .. } => "P0009",
+            DiagnosticKind::DuplicateService { .. } => "P0010",
+            DiagnosticKind::DuplicateEndpoint { .. } => "P0011",
+            DiagnosticKind::InvalidEndpoint { .. } => "P0012",
+            DiagnosticKind::Decode { .. } => "P0013",
+            DiagnosticKind::InvalidConfig { .. } => "P0014",
+            DiagnosticKind::InvalidProto { .. } => "P0015",
+            DiagnosticKind::UnknownField { .. } => "P0016",
+            DiagnosticKind::MissingField { .. } => "P0017",
+            DiagnosticKind::Serialize { .. } => "P0018",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            DiagnosticKind::UnexpectedToken { .. }
+            | DiagnosticKind::InvalidResourceName { .. }
+            | DiagnosticKind::InvalidProto { .. } => ErrorCategory::Syntax,
+            DiagnosticKind::DuplicateResource { .. }
+            | DiagnosticKind::DuplicateField { .. }
+            | DiagnosticKind::UndefinedType { .. }
+            | DiagnosticKind::Cycle { .. }
+            | DiagnosticKind::TypeMismatch { .. }
+            | DiagnosticKind::DuplicateService { .. }
+            | DiagnosticKind::DuplicateEndpoint { .. }
+            | DiagnosticKind::InvalidEndpoint { .. }
+            | DiagnosticKind::Decode { .. }
+            | DiagnosticKind::UnknownField { .. }
+            | DiagnosticKind::MissingField { .. }
+            | DiagnosticKind::Serialize { .. } => ErrorCategory::Semantic,
+            DiagnosticKind::Io { .. } => ErrorCategory::Io,
+            DiagnosticKind::Codegen { .. } => ErrorCategory::Codegen,
+            DiagnosticKind::InvalidConfig { .. } => ErrorCategory::Config,
+        }
+    }
+}
+
+impl std::fmt::Display for DiagnosticKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticKind::DuplicateResource { name } => write!(f, "Duplicate resource name: {}", name),
+            DiagnosticKind::DuplicateField { resource, field } => {
+                write!(f, "Duplicate field name in {}: {}", resource, field)
             }
-            (Value::Bool(b), IRType::Primitive(p)) if p == "bool" => {
-                self.encode_bool(*b);
-                Ok(())
+            DiagnosticKind::UndefinedType { name } => write!(f, "Undefined type: {}", name),
+            DiagnosticKind::Cycle { path } => write!(f, "Cyclic dependency detected: {}", path.join(" → ")),
+            DiagnosticKind::TypeMismatch { expected, got } => {
+                write!(f, "Type mismatch: expected {}, got {}", expected, got)
             }
-            (Value::List(items), IRType::List(inner_type)) => {
-                self.encode_list(items, inner_type, ir_program)
+            DiagnosticKind::UnexpectedToken { expected, got } => write!(f, "Expected {}, got {}", expected, got),
+            DiagnosticKind::InvalidResourceName { name } => {
+                write!(f, "Resource name must be PascalCase: {}", name)
             }
-            (Value::Resource(fields), IRType::ResourceRef(idx)) => {
-                self.encode_resource(fields, *idx, ir_program)
+            DiagnosticKind::Io { operation, path, error } => {
+                write!(f, "Failed to {} '{}': {}", operation, path.display(), error)
             }
-            (Value::Null, _) => {
-                // Null should be handled by nullable wrapper
-                Err("Cannot encode null value without nullable wrapper".to_string())
+            DiagnosticKind::Codegen { target, message } => write!(f, "Failed to generate {}: {}", target, message),
+            DiagnosticKind::DuplicateService { name } => write!(f, "Duplicate service name: {}", name),
+            DiagnosticKind::DuplicateEndpoint { service, endpoint } => {
+                write!(f, "Duplicate endpoint in {}: {}", service, endpoint)
             }
-            (Value::Absent, _) => {
-                // Absent should be handled by optional wrapper
-                Err("Cannot encode absent value without optional wrapper".to_string())
+            DiagnosticKind::InvalidEndpoint { service, endpoint, reason } => {
+                write!(f, "Invalid endpoint {} in {}: {}", endpoint, service, reason)
             }
-            _ => Err(format!("Type mismatch: value {:?} does not match type {:?}", value, ir_type)),
+            DiagnosticKind::Decode { offset, reason } => write!(f, "Malformed payload at byte {}: {}", offset, reason),
+            DiagnosticKind::InvalidConfig { message } => write!(f, "Invalid configuration: {}", message),
+            DiagnosticKind::InvalidProto { message } => write!(f, "Invalid proto file: {}", message),
+            DiagnosticKind::UnknownField { resource, field } => write!(f, "No field `{}` in {}", field, resource),
+            DiagnosticKind::MissingField { resource, field } => write!(f, "Missing field `{}` of {}", field, resource),
+            DiagnosticKind::Serialize { message } => write!(f, "Failed to serialize: {}", message),
         }
     }
+}
 
-    /// Encode a field with optional/nullable handling
-    pub fn encode_field(&mut self, field_value: &FieldValue, ir_field: &IRField, ir_program: &IRProgram) -> Result<(), String> {
-        // Handle optional fields
-        if ir_field.optional {
-            match &field_value.value {
-                Value::Absent => {
-                    self.buffer.push(0x00); // absent
-                    return Ok(());
-                }
-                _ => {
-                    self.buffer.push(0x01); // present
-                }
-            }
-        }
+/// A single problem found in a schema
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    pub severity: Severity,
+    /// Where the problem is, when it can be traced back to the source
+    pub span: Option<Span>,
+    /// File the span points into, when it differs from `CompileError::file`
+    pub file: Option<PathBuf>,
+    /// Extra context printed under the snippet, e.g. where a duplicate
+    /// resource was first defined
+    pub notes: Vec<String>,
+}
 
-        // Handle nullable fields
-        if ir_field.nullable {
-            match &field_value.value {
-                Value::Null => {
-                    self.buffer.push(0x00); // null
-                    return Ok(());
-                }
-                _ => {
-                    self.buffer.push(0x01); // not null
-                }
-            }
+impl Diagnostic {
+    /// An error without a location
+    pub fn error(kind: DiagnosticKind) -> Self {
+        Diagnostic {
+            kind,
+            severity: Severity::Error,
+            span: None,
+            file: None,
+            notes: Vec::new(),
         }
+    }
 
-        // Encode the actual value
-        self.encode_value(&field_value.value, &ir_field.field_type, ir_program)
+    pub fn with_span(mut self, span: Span) -> Self {
+        self.span = Some(span);
+        self
     }
 
-    // Primitive encoders
+    /// Attribute the diagnostic to the file a resource came from, if known
+    pub fn in_file(mut self, file: Option<&PathBuf>) -> Self {
+        if let Some(file) = file {
+            self.file = Some(file.clone());
+        }
+        self
+    }
 
-    fn encode_string(&mut self, s: &str) {
-        let bytes = s.as_bytes();
-        let len = bytes.len() as u32;
-        self.buffer.extend_from_slice(&len.to_le_bytes());
-        self.buffer.extend_from_slice(bytes);
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
     }
 
-    fn encode_number(&mut self, n: i64) {
-        self.buffer.extend_from_slice(&n.to_le_bytes());
+    pub fn code(&self) -> &'static str {
+        self.kind.code()
     }
 
-    fn encode_bool(&mut self, b: bool) {
-        self.buffer.push(if b { 0x01 } else { 0x00 });
+    pub fn message(&self) -> String {
+        self.kind.to_string()
     }
 
-    fn encode_list(&mut self, items: &[Value], inner_type: &IRType, ir_program: &IRProgram) -> Result<(), String> {
-        let count = items.len() as u32;
-        self.buffer.extend_from_slice(&count.to_le_bytes());
+    /// Machine-readable form, one object per diagnostic
+    ///
+    /// `line` and `column` are 1-based and null when the diagnostic has no span.
+    /// `file` is used when the diagnostic does not name its own file.
+    pub fn to_json(&self, file: Option<&Path>) -> serde_json::Value {
+        json!({
+            "type": "diagnostic",
+            "code": self.code(),
+            "category": self.kind.category().name(),
+            "severity": self.severity.to_string(),
+            "message": self.message(),
+            "file": self.file.as_deref().or(file).map(|f| f.display().to_string()),
+            "line": self.span.map(|s| s.line),
+            "column": self.span.map(|s| s.column),
+            "length": self.span.map(|s| s.length),
+            "notes": self.notes,
+        })
+    }
+}
 
-        for item in items {
-            self.encode_value(item, inner_type, ir_program)?;
-        }
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code(), self.kind)
+    }
+}
 
-        Ok(())
+impl From<DiagnosticKind> for Diagnostic {
+    fn from(kind: DiagnosticKind) -> Self {
+        Diagnostic::error(kind)
     }
+}
 
-    fn encode_resource(&mut self, fields: &[FieldValue], resource_idx: usize, ir_program: &IRProgram) -> Result<(), String> {
-        let ir_resource = &ir_program.resources.get(resource_idx)
-            .ok_or_else(|| format!("Invalid resource index: {}", resource_idx))?;
+/// Diagnostics reported by a failed stage, with the file context needed to
+/// render them
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    pub diagnostics: Vec<Diagnostic>,
+    pub file: Option<PathBuf>,
+    /// Schema source, used to print the offending lines
+    pub source: Option<String>,
+    /// Sources of every input when several files were compiled together,
+    /// looked up by `Diagnostic::file`
+    pub sources: Vec<(PathBuf, String)>,
+}
 
-        // Encode fields in order
-        if fields.len() != ir_resource.fields.len() {
-            return Err(format!(
-                "Field count mismatch: expected {} fields for resource '{}', got {}",
-                ir_resource.fields.len(),
-                ir_resource.name,
-                fields.len()
-            ));
+impl CompileError {
+    pub fn new(diagnostics: Vec<Diagnostic>) -> Self {
+        CompileError {
+            diagnostics,
+            file: None,
+            source: None,
+            sources: Vec::new(),
+        }
+    }
+
+    pub fn with_file(mut self, file: PathBuf) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn with_source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    pub fn with_sources(mut self, sources: &[(PathBuf, String)]) -> Self {
+        self.sources = sources.to_vec();
+        self
+    }
+
+    /// Source text a diagnostic points into
+    fn source_of(&self, diagnostic: &Diagnostic) -> Option<&str> {
+        match &diagnostic.file {
+            Some(file) => self
+                .sources
+                .iter()
+                .find(|(path, _)| path == file)
+                .map(|(_, source)| source.as_str()),
+            None => self.source.as_deref(),
+        }
+    }
+
+    /// Category of the earliest stage that failed
+    pub fn category(&self) -> Option<ErrorCategory> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .map(|d| d.kind.category())
+            .min()
+    }
+
+    /// Exit code for the failure, see `ErrorCategory::exit_code`
+    pub fn exit_code(&self) -> i32 {
+        self.category().map(|c| c.exit_code()).unwrap_or(1)
+    }
+
+    /// Render every diagnostic rustc-style, with a source snippet and carets
+    /// under the span when the source is known
+    pub fn format(&self) -> String {
+        let mut out = String::new();
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                out.push('\n');
+            }
+            out.push_str(&diagnostic.to_string());
+            out.push('\n');
+
+            let span = match diagnostic.span {
+                Some(span) => span,
+                None => {
+                    for note in &diagnostic.notes {
+                        out.push_str(&format!("  = note: {}\n", note));
+                    }
+                    continue;
+                }
+            };
+            let file = diagnostic
+                .file
+                .as_ref()
+                .or(self.file.as_ref())
+                .map(|f| f.display().to_string())
+                .unwrap_or_else(|| "<input>".to_string());
+            let line_text = self
+                .source_of(diagnostic)
+                .and_then(|source| source.lines().nth(span.line.saturating_sub(1)));
+            let gutter = " ".repeat(span.line.to_string().len());
+            out.push_str(&format!("{}--> {}:{}:{}\n", gutter, file, span.line, span.column));
+
+            if let Some(text) = line_text {
+                // Tabs are kept so the carets line up under the same columns
+                let padding: String = text
+                    .chars()
+                    .take(span.column.saturating_sub(1))
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+                out.push_str(&format!("{} |\n", gutter));
+                out.push_str(&format!("{} | {}\n", span.line, text));
+                out.push_str(&format!("{} | {}{}\n", gutter, padding, "^".repeat(span.length.max(1))));
+            }
+            for note in &diagnostic.notes {
+                out.push_str(&format!("{} = note: {}\n", gutter, note));
+            }
+        }
+        out
+    }
+}
+
+impl std::fmt::Display for CompileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.format().trim_end())
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+impl From<Diagnostic> for CompileError {
+    fn from(diagnostic: Diagnostic) -> Self {
+        CompileError::new(vec![diagnostic])
+    }
+}
+
+impl From<DiagnosticKind> for CompileError {
+    fn from(kind: DiagnosticKind) -> Self {
+        CompileError::new(vec![Diagnostic::error(kind)])
+    }
+}
+
+// ============================================================================
+// BINARY ENCODING MODEL (Phase 3)
+// ============================================================================
+//
+// Binary Encoding Specification:
+// - string:    u32 length (little-endian) + UTF-8 bytes
+// - number:    i64 (8 bytes, little-endian)
+// - bool:      1 byte (0x00 = false, 0x01 = true)
+// - list:      u32 count (little-endian) + each item encoded recursively
+// - nullable:  1 byte (0x00 = null, 0x01 = present) + value if present
+// - optional:  1 byte (0x00 = absent, 0x01 = present) + value if present
+// - resource:  fields encoded in order (field index is implicit)
+//
+
+/// Runtime value representation for encoding
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(i64),
+    Bool(bool),
+    List(Vec<Value>),
+    Resource(Vec<FieldValue>),
+    Null,
+    Absent,
+}
+
+impl Value {
+    /// Short description of the value's shape, used in type mismatch errors
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Number(_) => "number",
+            Value::Bool(_) => "bool",
+            Value::List(_) => "list",
+            Value::Resource(_) => "resource",
+            Value::Null => "null",
+            Value::Absent => "absent",
+        }
+    }
+
+    /// Value of a JSON document, checked against `ir_type`
+    ///
+    /// Resources are objects keyed by field name. A missing key is an absent
+    /// optional field, or the default of a field that has one; null is only
+    /// accepted for nullable fields. Errors note where in the document they are.
+    pub fn from_json(json: serde_json::Value, ir_type: &IRType, ir_program: &IRProgram) -> Result<Value, CompileError> {
+        Value::from_json_at(json, ir_type, ir_program, "$")
+    }
+
+    fn from_json_at(json: serde_json::Value, ir_type: &IRType, ir_program: &IRProgram, at: &str) -> Result<Value, CompileError> {
+        let located = |kind: DiagnosticKind| CompileError::from(Diagnostic::error(kind).with_note(format!("at {}", at)));
+        let mismatch = |expected: String, got: &str| located(DiagnosticKind::TypeMismatch { expected, got: got.to_string() });
+        let got = json_kind_name(&json);
+        match (ir_type, json) {
+            (IRType::Primitive(p), serde_json::Value::String(s)) if p == "string" => Ok(Value::String(s)),
+            (IRType::Primitive(p), serde_json::Value::Number(n)) if p == "number" => match n.as_i64() {
+                Some(n) => Ok(Value::Number(n)),
+                None => Err(mismatch("a 64-bit integer".to_string(), &n.to_string())),
+            },
+            (IRType::Primitive(p), serde_json::Value::Bool(b)) if p == "bool" => Ok(Value::Bool(b)),
+            (IRType::List(inner), serde_json::Value::Array(items)) => items
+                .into_iter()
+                .enumerate()
+                .map(|(i, item)| Value::from_json_at(item, inner, ir_program, &format!("{}[{}]", at, i)))
+                .collect::<Result<Vec<_>, _>>()
+                .map(Value::List),
+            (IRType::ResourceRef(idx), serde_json::Value::Object(mut object)) => {
+                let resource = ir_program.resources.get(*idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+                    name: format!("<resource {}>", idx),
+                })?;
+                let mut fields = Vec::with_capacity(resource.fields.len());
+                for field in &resource.fields {
+                    let field_at = format!("{}.{}", at, field.name);
+                    let value = match object.remove(&field.name) {
+                        None if field.optional => Value::Absent,
+                        None => match &field.default {
+                            Some(default) => match &default.value {
+                                Literal::String(s) => Value::String(s.clone()),
+                                Literal::Number(n) => Value::Number(*n),
+                                Literal::Bool(b) => Value::Bool(*b),
+                            },
+                            None => {
+                                return Err(located(DiagnosticKind::MissingField {
+                                    resource: resource.qualified_name(),
+                                    field: field.name.clone(),
+                                }))
+                            }
+                        },
+                        Some(serde_json::Value::Null) if field.nullable => Value::Null,
+                        Some(json) => Value::from_json_at(json, &field.field_type, ir_program, &field_at)?,
+                    };
+                    fields.push(FieldValue {
+                        name: field.name.clone(),
+                        value,
+                        is_optional: field.optional,
+                        is_nullable: field.nullable,
+                    });
+                }
+                if let Some(key) = object.keys().next() {
+                    return Err(located(DiagnosticKind::UnknownField {
+                        resource: resource.qualified_name(),
+                        field: key.clone(),
+                    }));
+                }
+                Ok(Value::Resource(fields))
+            }
+            _ => Err(mismatch(ir_program.type_name(ir_type), got)),
+        }
+    }
+
+    /// JSON form of the value, the inverse of `from_json`: resources become
+    /// objects keyed by field name, absent fields are left out and null
+    /// stays null
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            Value::String(s) => json!(s),
+            Value::Number(n) => json!(n),
+            Value::Bool(b) => json!(b),
+            Value::List(items) => serde_json::Value::Array(items.iter().map(Value::to_json).collect()),
+            Value::Resource(fields) => serde_json::Value::Object(
+                fields
+                    .iter()
+                    .filter(|field| field.value != Value::Absent)
+                    .map(|field| (field.name.clone(), field.value.to_json()))
+                    .collect(),
+            ),
+            Value::Null | Value::Absent => serde_json::Value::Null,
+        }
+    }
+}
+
+fn json_kind_name(json: &serde_json::Value) -> &'static str {
+    match json {
+        serde_json::Value::Null => "null",
+        serde_json::Value::Bool(_) => "bool",
+        serde_json::Value::Number(_) => "number",
+        serde_json::Value::String(_) => "string",
+        serde_json::Value::Array(_) => "list",
+        serde_json::Value::Object(_) => "object",
+    }
+}
+
+/// Field value with optional/nullable handling
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldValue {
+    pub name: String,
+    pub value: Value,
+    pub is_optional: bool,
+    pub is_nullable: bool,
+}
+
+/// Binary encoder for Previous values
+#[derive(Default)]
+pub struct BinaryEncoder {
+    buffer: Vec<u8>,
+}
+
+impl BinaryEncoder {
+    pub fn new() -> Self {
+        BinaryEncoder { buffer: Vec::new() }
+    }
+
+    /// Get the encoded bytes
+    pub fn finish(self) -> Vec<u8> {
+        self.buffer
+    }
+
+    /// Encode a value based on its type
+    pub fn encode_value(&mut self, value: &Value, ir_type: &IRType, ir_program: &IRProgram) -> Result<(), CompileError> {
+        match (value, ir_type) {
+            (Value::String(s), IRType::Primitive(p)) if p == "string" => {
+                self.encode_string(s);
+                Ok(())
+            }
+            (Value::Number(n), IRType::Primitive(p)) if p == "number" => {
+                self.encode_number(*n);
+                Ok(())
+            }
+            (Value::Bool(b), IRType::Primitive(p)) if p == "bool" => {
+                self.encode_bool(*b);
+                Ok(())
+            }
+            (Value::List(items), IRType::List(inner_type)) => {
+                self.encode_list(items, inner_type, ir_program)
+            }
+            (Value::Resource(fields), IRType::ResourceRef(idx)) => {
+                self.encode_resource(fields, *idx, ir_program)
+            }
+            // Null and absent are only valid behind a nullable or optional wrapper
+            _ => Err(DiagnosticKind::TypeMismatch {
+                expected: ir_program.type_name(ir_type),
+                got: value.kind_name().to_string(),
+            }
+            .into()),
+        }
+    }
+
+    /// Encode a field with optional/nullable handling
+    pub fn encode_field(&mut self, field_value: &FieldValue, ir_field: &IRField, ir_program: &IRProgram) -> Result<(), CompileError> {
+        // Handle optional fields
+        if ir_field.optional {
+            match &field_value.value {
+                Value::Absent => {
+                    self.buffer.push(0x00); // absent
+                    return Ok(());
+                }
+                _ => {
+                    self.buffer.push(0x01); // present
+                }
+            }
+        }
+
+        // Handle nullable fields
+        if ir_field.nullable {
+            match &field_value.value {
+                Value::Null => {
+                    self.buffer.push(0x00); // null
+                    return Ok(());
+                }
+                _ => {
+                    self.buffer.push(0x01); // not null
+                }
+            }
+        }
+
+        // Encode the actual value
+        self.encode_value(&field_value.value, &ir_field.field_type, ir_program)
+    }
+
+    // Primitive encoders
+
+    fn encode_string(&mut self, s: &str) {
+        let bytes = s.as_bytes();
+        let len = bytes.len() as u32;
+        self.buffer.extend_from_slice(&len.to_le_bytes());
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    fn encode_number(&mut self, n: i64) {
+        self.buffer.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn encode_bool(&mut self, b: bool) {
+        self.buffer.push(if b { 0x01 } else { 0x00 });
+    }
+
+    fn encode_list(&mut self, items: &[Value], inner_type: &IRType, ir_program: &IRProgram) -> Result<(), CompileError> {
+        let count = items.len() as u32;
+        self.buffer.extend_from_slice(&count.to_le_bytes());
+
+        for item in items {
+            self.encode_value(item, inner_type, ir_program)?;
+        }
+
+        Ok(())
+    }
+
+    fn encode_resource(&mut self, fields: &[FieldValue], resource_idx: usize, ir_program: &IRProgram) -> Result<(), CompileError> {
+        let ir_resource = &ir_program.resources.get(resource_idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+            name: format!("<resource {}>", resource_idx),
+        })?;
+
+        // Encode fields in order
+        if fields.len() != ir_resource.fields.len() {
+            return Err(DiagnosticKind::TypeMismatch {
+                expected: format!("{} fields for {}", ir_resource.fields.len(), ir_resource.name),
+                got: fields.len().to_string(),
+            }
+            .into());
         }
 
         for (field_value, ir_field) in fields.iter().zip(ir_resource.fields.iter()) {
             self.encode_field(field_value, ir_field, ir_program)?;
         }
 
-        Ok(())
+        Ok(())
+    }
+}
+
+/// Fewest bytes a value of `ir_type` is encoded in: absent and null fields
+/// take their flag byte, lists their count
+fn min_encoded_size(ir_type: &IRType, ir_program: &IRProgram) -> usize {
+    min_encoded_size_visiting(ir_type, ir_program, &mut Vec::new())
+}
+
+fn min_encoded_size_visiting(ir_type: &IRType, ir_program: &IRProgram, visiting: &mut Vec<usize>) -> usize {
+    match ir_type {
+        IRType::Primitive(p) => match p.as_str() {
+            "string" => 4,
+            "number" => 8,
+            _ => 1,
+        },
+        IRType::List(_) => 4,
+        IRType::ResourceRef(idx) => {
+            // A resource containing itself has no finite encoding, count it as empty
+            let Some(resource) = ir_program.resources.get(*idx).filter(|_| !visiting.contains(idx)) else {
+                return 0;
+            };
+            visiting.push(*idx);
+            let size = resource
+                .fields
+                .iter()
+                .map(|field| {
+                    if field.optional || field.nullable {
+                        1
+                    } else {
+                        min_encoded_size_visiting(&field.field_type, ir_program, visiting)
+                    }
+                })
+                .sum();
+            visiting.pop();
+            size
+        }
+    }
+}
+
+/// Binary decoder for Previous values, the inverse of `BinaryEncoder`
+pub struct BinaryDecoder<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    limits: WireOptions,
+}
+
+impl<'a> BinaryDecoder<'a> {
+    pub fn new(buffer: &'a [u8]) -> Self {
+        BinaryDecoder { buffer, offset: 0, limits: WireOptions::default() }
+    }
+
+    /// Reject strings and lists longer than the limits, before allocating
+    pub fn with_limits(mut self, limits: WireOptions) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Number of bytes consumed so far
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Check the whole buffer was consumed
+    pub fn finish(self) -> Result<(), CompileError> {
+        if self.offset < self.buffer.len() {
+            return Err(self.error(format!("{} trailing bytes", self.buffer.len() - self.offset)));
+        }
+        Ok(())
+    }
+
+    /// Decode a value based on its type
+    pub fn decode_value(&mut self, ir_type: &IRType, ir_program: &IRProgram) -> Result<Value, CompileError> {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => self.decode_string().map(Value::String),
+                "number" => self.decode_number().map(Value::Number),
+                "bool" => self.decode_bool().map(Value::Bool),
+                other => Err(DiagnosticKind::UndefinedType { name: other.to_string() }.into()),
+            },
+            IRType::List(inner_type) => {
+                let count = self.decode_sized_count(min_encoded_size(inner_type, ir_program))?;
+                let mut items = Vec::with_capacity((count as usize).min(self.buffer.len() - self.offset));
+                for _ in 0..count {
+                    items.push(self.decode_value(inner_type, ir_program)?);
+                }
+                Ok(Value::List(items))
+            }
+            IRType::ResourceRef(idx) => self.decode_resource(*idx, ir_program),
+        }
+    }
+
+    /// Decode a field with optional/nullable handling
+    pub fn decode_field(&mut self, ir_field: &IRField, ir_program: &IRProgram) -> Result<FieldValue, CompileError> {
+        let value = if ir_field.optional && !self.decode_flag()? {
+            Value::Absent
+        } else if ir_field.nullable && !self.decode_flag()? {
+            Value::Null
+        } else {
+            self.decode_value(&ir_field.field_type, ir_program)?
+        };
+        Ok(FieldValue {
+            name: ir_field.name.clone(),
+            value,
+            is_optional: ir_field.optional,
+            is_nullable: ir_field.nullable,
+        })
+    }
+
+    fn decode_resource(&mut self, resource_idx: usize, ir_program: &IRProgram) -> Result<Value, CompileError> {
+        let ir_resource = ir_program.resources.get(resource_idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+            name: format!("<resource {}>", resource_idx),
+        })?;
+        let mut fields = Vec::with_capacity(ir_resource.fields.len());
+        for ir_field in &ir_resource.fields {
+            fields.push(self.decode_field(ir_field, ir_program)?);
+        }
+        Ok(Value::Resource(fields))
+    }
+
+    // Primitive decoders
+
+    fn error(&self, reason: String) -> CompileError {
+        DiagnosticKind::Decode { offset: self.offset, reason }.into()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CompileError> {
+        if self.buffer.len() - self.offset < len {
+            return Err(self.error(format!(
+                "expected {} more bytes, {} left",
+                len,
+                self.buffer.len() - self.offset
+            )));
+        }
+        let bytes = &self.buffer[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn decode_u32(&mut self) -> Result<u32, CompileError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// Item count of a list
+    fn decode_count(&mut self) -> Result<u32, CompileError> {
+        let count = self.decode_u32()?;
+        if let Some(max) = self.limits.max_list_length {
+            if count > max {
+                return Err(self.error(format!("list of {} items exceeds the limit of {}", count, max)));
+            }
+        }
+        Ok(count)
+    }
+
+    /// Item count of a list whose items take at least `item_size` bytes,
+    /// checked against the bytes left so a short payload can't claim more
+    /// items than it holds
+    fn decode_sized_count(&mut self, item_size: usize) -> Result<u32, CompileError> {
+        let count = self.decode_count()?;
+        let remaining = self.buffer.len() - self.offset;
+        if item_size == 0 {
+            // Items without bytes can't be checked against the payload size
+            let max = self.max_empty_items();
+            if count > max {
+                return Err(self.error(format!("list of {} empty items exceeds the limit of {}", count, max)));
+            }
+        } else if (count as usize).checked_mul(item_size).is_none_or(|size| size > remaining) {
+            return Err(self.error(format!(
+                "list of {} items needs at least {} bytes per item, {} left",
+                count, item_size, remaining
+            )));
+        }
+        Ok(count)
+    }
+
+    /// Most items a list of zero-byte items may hold, see
+    /// `WireOptions::max_empty_list_length`
+    fn max_empty_items(&self) -> u32 {
+        self.limits.max_empty_list_length.unwrap_or(DEFAULT_MAX_EMPTY_LIST_LENGTH)
+    }
+
+    fn decode_string(&mut self) -> Result<String, CompileError> {
+        self.decode_str().map(str::to_string)
+    }
+
+    /// String borrowed from the buffer
+    fn decode_str(&mut self) -> Result<&'a str, CompileError> {
+        let len = self.decode_u32()?;
+        if let Some(max) = self.limits.max_string_length {
+            if len > max {
+                return Err(self.error(format!("string of {} bytes exceeds the limit of {}", len, max)));
+            }
+        }
+        let start = self.offset;
+        let bytes = self.take(len as usize)?;
+        std::str::from_utf8(bytes).map_err(|_| {
+            DiagnosticKind::Decode { offset: start, reason: "string is not valid UTF-8".to_string() }.into()
+        })
+    }
+
+    fn decode_number(&mut self) -> Result<i64, CompileError> {
+        let bytes = self.take(8)?;
+        let mut le = [0u8; 8];
+        le.copy_from_slice(bytes);
+        Ok(i64::from_le_bytes(le))
+    }
+
+    fn decode_bool(&mut self) -> Result<bool, CompileError> {
+        self.decode_byte("bool")
+    }
+
+    /// Presence flag of an optional or nullable field
+    fn decode_flag(&mut self) -> Result<bool, CompileError> {
+        self.decode_byte("flag")
+    }
+
+    fn decode_byte(&mut self, what: &str) -> Result<bool, CompileError> {
+        match self.take(1)?[0] {
+            0x00 => Ok(false),
+            0x01 => Ok(true),
+            other => {
+                self.offset -= 1;
+                Err(self.error(format!("invalid {} byte 0x{:02x}", what, other)))
+            }
+        }
+    }
+}
+
+/// Rust type with a fixed binary encoding, implemented for the primitives and
+/// by `#[derive(Previous)]` (previous-derive) for structs checked against a
+/// resource
+///
+/// `String`, `i64` and `bool` are the primitives, `Vec<T>` is a list and
+/// `Option<T>` is the presence flag of an optional or nullable field.
+pub trait Wire: Sized {
+    /// Fewest bytes a value is encoded in, bounds the item count of lists;
+    /// 0 holds for any type but limits lists to `max_empty_list_length`
+    const MIN_SIZE: usize = 0;
+
+    fn encode(&self, encoder: &mut BinaryEncoder);
+
+    fn decode(decoder: &mut BinaryDecoder<'_>) -> Result<Self, CompileError>;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut encoder = BinaryEncoder::new();
+        self.encode(&mut encoder);
+        encoder.finish()
+    }
+
+    /// Decode a value, the whole buffer must be consumed
+    fn from_bytes(bytes: &[u8]) -> Result<Self, CompileError> {
+        let mut decoder = BinaryDecoder::new(bytes);
+        let value = Self::decode(&mut decoder)?;
+        decoder.finish()?;
+        Ok(value)
+    }
+}
+
+impl Wire for String {
+    const MIN_SIZE: usize = 4;
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.encode_string(self);
+    }
+
+    fn decode(decoder: &mut BinaryDecoder<'_>) -> Result<Self, CompileError> {
+        decoder.decode_string()
+    }
+}
+
+impl Wire for i64 {
+    const MIN_SIZE: usize = 8;
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.encode_number(*self);
+    }
+
+    fn decode(decoder: &mut BinaryDecoder<'_>) -> Result<Self, CompileError> {
+        decoder.decode_number()
+    }
+}
+
+impl Wire for bool {
+    const MIN_SIZE: usize = 1;
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.encode_bool(*self);
+    }
+
+    fn decode(decoder: &mut BinaryDecoder<'_>) -> Result<Self, CompileError> {
+        decoder.decode_bool()
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    const MIN_SIZE: usize = 4;
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        encoder.buffer.extend_from_slice(&(self.len() as u32).to_le_bytes());
+        for item in self {
+            item.encode(encoder);
+        }
+    }
+
+    fn decode(decoder: &mut BinaryDecoder<'_>) -> Result<Self, CompileError> {
+        let count = decoder.decode_sized_count(T::MIN_SIZE)?;
+        let mut items = Vec::with_capacity((count as usize).min(decoder.buffer.len() - decoder.offset));
+        for _ in 0..count {
+            items.push(T::decode(decoder)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    const MIN_SIZE: usize = 1;
+
+    fn encode(&self, encoder: &mut BinaryEncoder) {
+        match self {
+            Some(value) => {
+                encoder.buffer.push(0x01); // present
+                value.encode(encoder);
+            }
+            None => encoder.buffer.push(0x00), // absent or null
+        }
+    }
+
+    fn decode(decoder: &mut BinaryDecoder<'_>) -> Result<Self, CompileError> {
+        if decoder.decode_flag()? {
+            T::decode(decoder).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Borrowed view of an encoded resource, read without building a `Value`
+///
+/// Nothing is decoded up front: a field is found by skipping over the
+/// fields before it, strings are borrowed from the buffer and lists are
+/// iterated lazily. Malformed bytes are reported by the accessor that
+/// reaches them, with their offset in the buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceView<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+    resource: &'a IRResource,
+    ir_program: &'a IRProgram,
+}
+
+/// Borrowed form of a `Value`
+#[derive(Debug, Clone, Copy)]
+pub enum ValueView<'a> {
+    String(&'a str),
+    Number(i64),
+    Bool(bool),
+    List(ListView<'a>),
+    Resource(ResourceView<'a>),
+    Null,
+    Absent,
+}
+
+/// Borrowed view of an encoded list, see `ListView::iter`
+#[derive(Debug, Clone, Copy)]
+pub struct ListView<'a> {
+    buffer: &'a [u8],
+    /// Offset of the first item, after the count
+    offset: usize,
+    count: u32,
+    item_type: &'a IRType,
+    ir_program: &'a IRProgram,
+}
+
+impl<'a> ResourceView<'a> {
+    /// View of the resource at `resource_idx` encoded at the start of `buffer`
+    pub fn new(buffer: &'a [u8], resource_idx: usize, ir_program: &'a IRProgram) -> Result<Self, CompileError> {
+        let resource = ir_program.resources.get(resource_idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+            name: format!("<resource {}>", resource_idx),
+        })?;
+        Ok(ResourceView { buffer, offset: 0, resource, ir_program })
+    }
+
+    pub fn resource(&self) -> &'a IRResource {
+        self.resource
+    }
+
+    /// Value of the field called `name`, `Absent` or `Null` when it has none
+    pub fn field(&self, name: &str) -> Result<ValueView<'a>, CompileError> {
+        let index = self.field_index(name)?;
+
+        let mut decoder = BinaryDecoder { buffer: self.buffer, offset: self.offset, limits: WireOptions::default() };
+        for field in &self.resource.fields[..index] {
+            skip_field(&mut decoder, field, self.ir_program)?;
+        }
+        let field = &self.resource.fields[index];
+        if field.optional && !decoder.decode_flag()? {
+            return Ok(ValueView::Absent);
+        }
+        if field.nullable && !decoder.decode_flag()? {
+            return Ok(ValueView::Null);
+        }
+        view_value(&mut decoder, &field.field_type, self.ir_program)
+    }
+
+    /// String field, `None` when absent or null
+    pub fn string(&self, name: &str) -> Result<Option<&'a str>, CompileError> {
+        match self.typed_field(name, "string")? {
+            ValueView::String(s) => Ok(Some(s)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Number field, `None` when absent or null
+    pub fn number(&self, name: &str) -> Result<Option<i64>, CompileError> {
+        match self.typed_field(name, "number")? {
+            ValueView::Number(n) => Ok(Some(n)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Bool field, `None` when absent or null
+    pub fn bool(&self, name: &str) -> Result<Option<bool>, CompileError> {
+        match self.typed_field(name, "bool")? {
+            ValueView::Bool(b) => Ok(Some(b)),
+            _ => Ok(None),
+        }
+    }
+
+    /// List field, `None` when absent or null
+    pub fn list(&self, name: &str) -> Result<Option<ListView<'a>>, CompileError> {
+        match self.typed_field(name, "list")? {
+            ValueView::List(list) => Ok(Some(list)),
+            _ => Ok(None),
+        }
+    }
+
+    /// Resource field, `None` when absent or null
+    pub fn nested(&self, name: &str) -> Result<Option<ResourceView<'a>>, CompileError> {
+        match self.typed_field(name, "resource")? {
+            ValueView::Resource(resource) => Ok(Some(resource)),
+            _ => Ok(None),
+        }
+    }
+
+    fn field_index(&self, name: &str) -> Result<usize, CompileError> {
+        self.resource.fields.iter().position(|field| field.name == name).ok_or_else(|| {
+            CompileError::from(DiagnosticKind::UnknownField {
+                resource: self.resource.qualified_name(),
+                field: name.to_string(),
+            })
+        })
+    }
+
+    /// Value of a field whose schema type is of the `expected` kind, so a
+    /// wrong accessor fails whatever the payload holds
+    fn typed_field(&self, name: &str, expected: &str) -> Result<ValueView<'a>, CompileError> {
+        let field = &self.resource.fields[self.field_index(name)?];
+        let kind = match &field.field_type {
+            IRType::Primitive(p) => p.as_str(),
+            IRType::List(_) => "list",
+            IRType::ResourceRef(_) => "resource",
+        };
+        if kind != expected {
+            return Err(DiagnosticKind::TypeMismatch {
+                expected: format!("{} for {}.{}", expected, self.resource.qualified_name(), name),
+                got: self.ir_program.type_name(&field.field_type),
+            }
+            .into());
+        }
+        self.field(name)
+    }
+}
+
+impl<'a> ValueView<'a> {
+    /// Short description of the value's shape, see `Value::kind_name`
+    pub fn kind_name(&self) -> &'static str {
+        match self {
+            ValueView::String(_) => "string",
+            ValueView::Number(_) => "number",
+            ValueView::Bool(_) => "bool",
+            ValueView::List(_) => "list",
+            ValueView::Resource(_) => "resource",
+            ValueView::Null => "null",
+            ValueView::Absent => "absent",
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&'a str> {
+        match self {
+            ValueView::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_number(&self) -> Option<i64> {
+        match self {
+            ValueView::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            ValueView::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<ListView<'a>> {
+        match self {
+            ValueView::List(list) => Some(*list),
+            _ => None,
+        }
+    }
+
+    pub fn as_resource(&self) -> Option<ResourceView<'a>> {
+        match self {
+            ValueView::Resource(resource) => Some(*resource),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> ListView<'a> {
+    /// Item count, read from the list's prefix
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// Items in order, each found by skipping the ones before it; stops after
+    /// the first malformed item
+    pub fn iter(&self) -> ListIter<'a> {
+        ListIter {
+            list: *self,
+            decoder: BinaryDecoder { buffer: self.buffer, offset: self.offset, limits: WireOptions::default() },
+            remaining: self.count,
+        }
+    }
+}
+
+impl<'a> IntoIterator for ListView<'a> {
+    type Item = Result<ValueView<'a>, CompileError>;
+    type IntoIter = ListIter<'a>;
+
+    fn into_iter(self) -> ListIter<'a> {
+        self.iter()
+    }
+}
+
+/// Lazy iterator over the items of a `ListView`
+pub struct ListIter<'a> {
+    list: ListView<'a>,
+    decoder: BinaryDecoder<'a>,
+    remaining: u32,
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = Result<ValueView<'a>, CompileError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let start = self.decoder.offset;
+        let item = view_value(&mut self.decoder, self.list.item_type, self.list.ir_program).and_then(|item| {
+            // Lists and resources are viewed in place, step over their contents
+            if matches!(item, ValueView::List(_) | ValueView::Resource(_)) {
+                self.decoder.offset = start;
+                skip_value(&mut self.decoder, self.list.item_type, self.list.ir_program)?;
+            }
+            Ok(item)
+        });
+        if item.is_err() {
+            self.remaining = 0;
+        }
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining as usize))
+    }
+}
+
+/// View of the value at the decoder's offset; strings and numbers are read,
+/// lists only have their count read and resources are not read at all
+fn view_value<'a>(decoder: &mut BinaryDecoder<'a>, ir_type: &'a IRType, ir_program: &'a IRProgram) -> Result<ValueView<'a>, CompileError> {
+    match ir_type {
+        IRType::Primitive(p) => match p.as_str() {
+            "string" => decoder.decode_str().map(ValueView::String),
+            "number" => decoder.decode_number().map(ValueView::Number),
+            "bool" => decoder.decode_bool().map(ValueView::Bool),
+            other => Err(DiagnosticKind::UndefinedType { name: other.to_string() }.into()),
+        },
+        IRType::List(item_type) => {
+            let count = decoder.decode_sized_count(min_encoded_size(item_type, ir_program))?;
+            Ok(ValueView::List(ListView {
+                buffer: decoder.buffer,
+                offset: decoder.offset,
+                count,
+                item_type,
+                ir_program,
+            }))
+        }
+        IRType::ResourceRef(idx) => {
+            let resource = ir_program.resources.get(*idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+                name: format!("<resource {}>", idx),
+            })?;
+            Ok(ValueView::Resource(ResourceView { buffer: decoder.buffer, offset: decoder.offset, resource, ir_program }))
+        }
+    }
+}
+
+/// Step over a value without reading it, strings are not checked for UTF-8
+fn skip_value(decoder: &mut BinaryDecoder<'_>, ir_type: &IRType, ir_program: &IRProgram) -> Result<(), CompileError> {
+    match ir_type {
+        IRType::Primitive(p) => match p.as_str() {
+            "string" => {
+                let len = decoder.decode_u32()?;
+                decoder.take(len as usize).map(|_| ())
+            }
+            "number" => decoder.take(8).map(|_| ()),
+            "bool" => decoder.decode_bool().map(|_| ()),
+            other => Err(DiagnosticKind::UndefinedType { name: other.to_string() }.into()),
+        },
+        IRType::List(item_type) => {
+            let count = decoder.decode_sized_count(min_encoded_size(item_type, ir_program))?;
+            if **item_type == IRType::Primitive("number".to_string()) {
+                // The count was checked against the bytes left, this can't overflow
+                return decoder.take(count as usize * 8).map(|_| ());
+            }
+            for _ in 0..count {
+                skip_value(decoder, item_type, ir_program)?;
+            }
+            Ok(())
+        }
+        IRType::ResourceRef(idx) => {
+            let resource = ir_program.resources.get(*idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+                name: format!("<resource {}>", idx),
+            })?;
+            for field in &resource.fields {
+                skip_field(decoder, field, ir_program)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn skip_field(decoder: &mut BinaryDecoder<'_>, ir_field: &IRField, ir_program: &IRProgram) -> Result<(), CompileError> {
+    if ir_field.optional && !decoder.decode_flag()? {
+        return Ok(());
+    }
+    if ir_field.nullable && !decoder.decode_flag()? {
+        return Ok(());
+    }
+    skip_value(decoder, &ir_field.field_type, ir_program)
+}
+
+/// One labelled run of bytes in a payload, see `inspect_payload`
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayloadPart {
+    pub offset: usize,
+    pub len: usize,
+    /// Field the bytes belong to, e.g. `tags[0].name`
+    pub path: String,
+    /// What the bytes mean, e.g. `present`, `length 5` or `"Alice"`
+    pub description: String,
+}
+
+/// Labelled bytes of a payload, up to the first malformed byte
+#[derive(Debug, Clone)]
+pub struct PayloadInspection {
+    pub parts: Vec<PayloadPart>,
+    pub error: Option<CompileError>,
+}
+
+/// Label every presence byte, length prefix and value of a payload
+pub fn inspect_payload(bytes: &[u8], ir_type: &IRType, ir_program: &IRProgram) -> PayloadInspection {
+    let mut decoder = BinaryDecoder::new(bytes);
+    let mut parts = Vec::new();
+    let error = inspect_value(&mut decoder, ir_type, ir_program, "", &mut parts)
+        .and_then(|_| {
+            if decoder.offset() < bytes.len() {
+                parts.push(PayloadPart {
+                    offset: decoder.offset(),
+                    len: bytes.len() - decoder.offset(),
+                    path: String::new(),
+                    description: "trailing bytes".to_string(),
+                });
+            }
+            decoder.finish()
+        })
+        .err();
+    PayloadInspection { parts, error }
+}
+
+fn inspect_value(
+    decoder: &mut BinaryDecoder,
+    ir_type: &IRType,
+    ir_program: &IRProgram,
+    path: &str,
+    parts: &mut Vec<PayloadPart>,
+) -> Result<(), CompileError> {
+    let mut part = |offset: usize, decoder: &BinaryDecoder, description: String| {
+        parts.push(PayloadPart { offset, len: decoder.offset() - offset, path: path.to_string(), description });
+    };
+    let start = decoder.offset();
+    match ir_type {
+        IRType::Primitive(p) if p == "string" => {
+            let len = decoder.decode_u32()?;
+            part(start, decoder, format!("length {}", len));
+            let text_start = decoder.offset();
+            let text = String::from_utf8_lossy(decoder.take(len as usize)?).into_owned();
+            if len > 0 {
+                let shown: String = text.chars().take(40).collect();
+                let ellipsis = if shown.len() < text.len() { "…" } else { "" };
+                part(text_start, decoder, format!("{:?}{}", shown, ellipsis));
+            }
+        }
+        IRType::Primitive(p) if p == "number" => {
+            let n = decoder.decode_number()?;
+            part(start, decoder, n.to_string());
+        }
+        IRType::Primitive(_) => {
+            let b = decoder.decode_bool()?;
+            part(start, decoder, b.to_string());
+        }
+        IRType::List(inner) => {
+            let count = decoder.decode_sized_count(min_encoded_size(inner, ir_program))?;
+            part(start, decoder, format!("count {}", count));
+            for i in 0..count {
+                inspect_value(decoder, inner, ir_program, &format!("{}[{}]", path, i), parts)?;
+            }
+        }
+        IRType::ResourceRef(idx) => {
+            let resource = ir_program.resources.get(*idx).ok_or_else(|| DiagnosticKind::UndefinedType {
+                name: format!("<resource {}>", idx),
+            })?;
+            for field in &resource.fields {
+                let field_path = if path.is_empty() { field.name.clone() } else { format!("{}.{}", path, field.name) };
+                let mut flag = |decoder: &mut BinaryDecoder, set: &str, unset: &str| -> Result<bool, CompileError> {
+                    let offset = decoder.offset();
+                    let present = decoder.decode_flag()?;
+                    parts.push(PayloadPart {
+                        offset,
+                        len: 1,
+                        path: field_path.clone(),
+                        description: if present { set } else { unset }.to_string(),
+                    });
+                    Ok(present)
+                };
+                if field.optional && !flag(decoder, "present", "absent")? {
+                    continue;
+                }
+                if field.nullable && !flag(decoder, "not null", "null")? {
+                    continue;
+                }
+                inspect_value(decoder, &field.field_type, ir_program, &field_path, parts)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+impl PayloadInspection {
+    /// Hex dump with the offset, bytes, field and meaning of every part,
+    /// long values wrap at 8 bytes per line
+    pub fn hex_dump(&self, bytes: &[u8]) -> String {
+        let mut out = String::new();
+        let path_width = self.parts.iter().map(|p| p.path.chars().count()).max().unwrap_or(0);
+        for part in &self.parts {
+            let end = (part.offset + part.len).min(bytes.len());
+            for (i, chunk) in bytes[part.offset..end].chunks(8).enumerate() {
+                let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+                let offset = part.offset + i * 8;
+                if i == 0 {
+                    out.push_str(&format!(
+                        "{:08x}  {:<23}  {:<width$}  {}\n",
+                        offset,
+                        hex.join(" "),
+                        part.path,
+                        part.description,
+                        width = path_width
+                    ));
+                } else {
+                    out.push_str(&format!("{:08x}  {}\n", offset, hex.join(" ")));
+                }
+            }
+        }
+        if let Some(error) = &self.error {
+            let offset = self.parts.last().map(|p| p.offset + p.len).unwrap_or(0);
+            if offset < bytes.len() {
+                let rest: Vec<String> = bytes[offset..].iter().take(8).map(|b| format!("{:02x}", b)).collect();
+                out.push_str(&format!("{:08x}  {:<23}  ??\n", offset, rest.join(" ")));
+            }
+            for diagnostic in &error.diagnostics {
+                out.push_str(&format!("error: {}\n", diagnostic.message()));
+            }
+        }
+        out
+    }
+}
+
+// ============================================================================
+// CODE GENERATION (Phase 4)
+// ============================================================================
+
+/// Media type used for payloads in the Previous binary encoding
+pub const PREVIOUS_MEDIA_TYPE: &str = "application/x-previous";
+
+/// Whether an Accept header asks for JSON: it names application/json but
+/// not the Previous media type
+pub fn prefers_json(accept: &str) -> bool {
+    let accepts = |media_type: &str| {
+        accept
+            .split(',')
+            .any(|item| item.split(';').next().unwrap_or("").trim().eq_ignore_ascii_case(media_type))
+    };
+    accepts("application/json") && !accepts(PREVIOUS_MEDIA_TYPE)
+}
+
+/// Generated code output containing client and server code
+#[derive(Debug, Clone)]
+pub struct GeneratedCode {
+    pub typescript_client: String,
+    pub typescript_server: String,
+}
+
+/// How generated TypeScript exposes its declarations
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModuleStyle {
+    /// ES module `export` declarations
+    #[default]
+    Esm,
+    /// Plain script declarations in the global scope, without `export`
+    Global,
+}
+
+/// TypeScript type used for `number` fields, which are i64 on the wire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberMode {
+    /// `number`, values beyond 2^53 lose precision
+    #[default]
+    Number,
+    /// `bigint`, exact for the whole i64 range
+    BigInt,
+}
+
+/// How field names are spelled in generated TypeScript
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum NamingConvention {
+    /// Field names exactly as written in the schema
+    #[default]
+    #[serde(rename = "preserve")]
+    Preserve,
+    /// snake_case field names become camelCase
+    #[serde(rename = "camelCase")]
+    CamelCase,
+}
+
+/// Limits enforced by generated decoders, the layout itself never changes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct WireOptions {
+    /// Largest string length in bytes a decoder accepts
+    pub max_string_length: Option<u32>,
+    /// Largest list item count a decoder accepts
+    pub max_list_length: Option<u32>,
+    /// Largest item count `BinaryDecoder` accepts for a list whose items
+    /// encode in no bytes (resources without fields), which the payload
+    /// size can't bound; `DEFAULT_MAX_EMPTY_LIST_LENGTH` when unset
+    pub max_empty_list_length: Option<u32>,
+}
+
+/// Item count limit for lists of zero-byte items when
+/// `WireOptions::max_empty_list_length` is unset
+pub const DEFAULT_MAX_EMPTY_LIST_LENGTH: u32 = 1 << 16;
+
+/// Options for the TypeScript generator
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CodegenOptions {
+    pub module: ModuleStyle,
+    pub numbers: NumberMode,
+    pub naming: NamingConvention,
+    pub wire: WireOptions,
+}
+
+/// Code generator for TypeScript client and server
+pub struct CodeGenerator {
+    ir: IRProgram,
+    options: CodegenOptions,
+}
+
+impl CodeGenerator {
+    pub fn new(ir: IRProgram) -> Self {
+        CodeGenerator {
+            ir,
+            options: CodegenOptions::default(),
+        }
+    }
+
+    pub fn with_options(mut self, options: CodegenOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Generate both client and server code
+    pub fn generate(&self) -> GeneratedCode {
+        GeneratedCode {
+            typescript_client: self.apply_module_style(self.generate_typescript_client()),
+            typescript_server: self.apply_module_style(self.generate_typescript_server()),
+        }
+    }
+
+    fn apply_module_style(&self, code: String) -> String {
+        match self.options.module {
+            ModuleStyle::Esm => code,
+            ModuleStyle::Global => code
+                .lines()
+                .map(|line| line.strip_prefix("export ").unwrap_or(line))
+                .collect::<Vec<_>>()
+                .join("\n")
+                + "\n",
+        }
+    }
+
+    /// Property name of a field in generated TypeScript
+    fn ts_name(&self, field_name: &str) -> String {
+        match self.options.naming {
+            NamingConvention::Preserve => field_name.to_string(),
+            NamingConvention::CamelCase => {
+                let mut name = String::new();
+                let mut upper = false;
+                for c in field_name.chars() {
+                    if c == '_' && !name.is_empty() {
+                        upper = true;
+                    } else if upper {
+                        name.extend(c.to_uppercase());
+                        upper = false;
+                    } else {
+                        name.push(c);
+                    }
+                }
+                name
+            }
+        }
+    }
+
+    /// TypeScript type of the `number` primitive
+    fn ts_number_type(&self) -> &'static str {
+        match self.options.numbers {
+            NumberMode::Number => "number",
+            NumberMode::BigInt => "bigint",
+        }
+    }
+
+    // ========================================================================
+    // TypeScript Client Generation
+    // ========================================================================
+
+    fn generate_typescript_client(&self) -> String {
+        let mut code = String::new();
+
+        // Header
+        code.push_str("// Generated by Previous Compiler\n");
+        code.push_str("// DO NOT EDIT - This file is auto-generated\n\n");
+
+        // Binary reader utility class
+        code.push_str(&self.generate_binary_reader());
+        code.push('\n');
+
+        // Services send request bodies, which needs the writer too
+        if !self.ir.services.is_empty() {
+            code.push_str(&self.generate_binary_writer());
+            code.push('\n');
+            code.push_str(&self.generate_ts_client_runtime());
+            code.push('\n');
+        }
+
+        // Generate each resource
+        let mut chunks: Vec<(Option<&str>, String)> = self
+            .ir
+            .resources
+            .iter()
+            .map(|resource| (resource.package.as_deref(), self.generate_ts_resource(resource)))
+            .collect();
+        if !self.ir.services.is_empty() {
+            for resource in &self.ir.resources {
+                chunks.push((resource.package.as_deref(), self.generate_ts_plain_writer(resource)));
+            }
+            for service in &self.ir.services {
+                chunks.push((service.package.as_deref(), self.generate_ts_service_client(service)));
+            }
+        }
+        code.push_str(&self.generate_ts_namespaced(chunks));
+
+        code
+    }
+
+    /// Lays out generated chunks, those in a package wrapped in a
+    /// `namespace` named after it
+    fn generate_ts_namespaced(&self, chunks: Vec<(Option<&str>, String)>) -> String {
+        let mut code = String::new();
+        let mut packages: Vec<&str> = Vec::new();
+        for (package, chunk) in &chunks {
+            match package {
+                None => {
+                    code.push_str(chunk);
+                    code.push('\n');
+                }
+                Some(package) if !packages.contains(package) => packages.push(package),
+                Some(_) => {}
+            }
+        }
+
+        for package in packages {
+            let body: Vec<&str> = chunks
+                .iter()
+                .filter(|(chunk_package, _)| *chunk_package == Some(package))
+                .map(|(_, chunk)| chunk.as_str())
+                .collect();
+            code.push_str(&format!("export namespace {} {{\n", package));
+            for line in body.join("\n").lines() {
+                if !line.is_empty() {
+                    code.push_str("  ");
+                }
+                code.push_str(line);
+                code.push('\n');
+            }
+            code.push_str("}\n\n");
+        }
+        code
+    }
+
+    /// Name a generated class (`prefix` "") or interface (`prefix` "I") is
+    /// referred to by, qualified with its namespace, e.g. `billing.IInvoice`
+    fn ts_type_name(&self, idx: usize, prefix: &str) -> String {
+        let resource = &self.ir.resources[idx];
+        qualify(resource.package.as_deref(), &format!("{}{}", prefix, resource.name))
+    }
+
+    fn generate_binary_reader(&self) -> String {
+        let mut reader = r#"class BinaryReader {
+  private buffer: Uint8Array;
+  private offset: number;
+
+  constructor(buffer: Uint8Array) {
+    this.buffer = buffer;
+    this.offset = 0;
+  }
+
+  readString(): string {
+    const length = this.readU32();/*STRING_LIMIT*/
+    const bytes = this.buffer.slice(this.offset, this.offset + length);
+    this.offset += length;
+    return new TextDecoder().decode(bytes);
+  }
+
+  readNumber(): number {
+    const view = new DataView(this.buffer.buffer, this.offset, 8);
+    const value = view.getBigInt64(0, true); // little-endian
+    this.offset += 8;
+    return Number(value);
+  }
+
+  readBool(): boolean {
+    const value = this.buffer[this.offset];
+    this.offset += 1;
+    return value === 1;
+  }
+
+  readU32(): number {
+    const view = new DataView(this.buffer.buffer, this.offset, 4);
+    const value = view.getUint32(0, true); // little-endian
+    this.offset += 4;
+    return value;
+  }
+
+  readByte(): number {
+    const value = this.buffer[this.offset];
+    this.offset += 1;
+    return value;
+  }
+}
+"#
+        .to_string();
+
+        let string_limit = match self.options.wire.max_string_length {
+            Some(max) => format!(
+                "\n    if (length > {}) {{\n      throw new Error(`string of ${{length}} bytes exceeds the limit of {}`);\n    }}",
+                max, max
+            ),
+            None => String::new(),
+        };
+        reader = reader.replace("/*STRING_LIMIT*/", &string_limit);
+        if self.options.numbers == NumberMode::BigInt {
+            reader = reader
+                .replace("readNumber(): number {", "readNumber(): bigint {")
+                .replace("return Number(value);", "return value;");
+        }
+        reader
+    }
+
+    /// Plain object shape of a resource, as decoded by the client
+    fn generate_ts_interface(&self, resource: &IRResource) -> String {
+        let mut code = format!("export interface I{} {{\n", resource.name);
+        for field in &resource.fields {
+            let ts_type = self.ir_type_to_typescript(&field.field_type);
+            let optional = if field.optional || field.nullable { "?" } else { "" };
+            code.push_str(&format!("  {}{}: {};\n", self.ts_name(&field.name), optional, ts_type));
+        }
+        code.push_str("}\n");
+        code
+    }
+
+    fn generate_ts_resource(&self, resource: &IRResource) -> String {
+        let mut code = String::new();
+
+        // Interface for the resource
+        code.push_str(&self.generate_ts_interface(resource));
+        code.push('\n');
+
+        // Decoder class
+        code.push_str(&format!("export class {} {{\n", resource.name));
+        code.push_str("  private reader: BinaryReader;\n");
+        code.push_str(&format!("  private data: I{};\n\n", resource.name));
+
+        // Constructor
+        code.push_str("  constructor(buffer: Uint8Array) {\n");
+        code.push_str("    this.reader = new BinaryReader(buffer);\n");
+        code.push_str(&format!("    this.data = {{}} as I{};\n", resource.name));
+        code.push_str("    this.decode();\n");
+        code.push_str("  }\n\n");
+
+        // Decode method
+        code.push_str("  private decode(): void {\n");
+        for field in &resource.fields {
+            code.push_str(&self.generate_ts_field_decode(field));
+        }
+        code.push_str("  }\n\n");
+
+        // Getter methods
+        for field in &resource.fields {
+            let ts_type = self.ir_type_to_typescript(&field.field_type);
+            let optional = if field.optional || field.nullable { " | null | undefined" } else { "" };
+            code.push_str(&format!(
+                "  get{}(): {}{} {{\n",
+                self.capitalize_first(&self.ts_name(&field.name)),
+                ts_type,
+                optional
+            ));
+            code.push_str(&format!("    return this.data.{};\n", self.ts_name(&field.name)));
+            code.push_str("  }\n\n");
+        }
+
+        // fromJSON, for service clients whose server answered in JSON
+        if !self.ir.services.is_empty() {
+            code.push_str(&self.generate_ts_from_json(resource));
+        }
+
+        // toJSON method
+        code.push_str(&format!("  toJSON(): I{} {{\n", resource.name));
+        code.push_str("    return this.data;\n");
+        code.push_str("  }\n");
+
+        code.push_str("}\n");
+        code
+    }
+
+    /// `fromJSON` building the same data the decoder does: nested resources
+    /// become instances and numbers `bigint`s when `numbers = bigint`
+    fn generate_ts_from_json(&self, resource: &IRResource) -> String {
+        let mut code = format!("  static fromJSON(data: I{}): {} {{\n", resource.name, resource.name);
+        code.push_str(&format!("    const instance = Object.create({}.prototype) as {};\n", resource.name, resource.name));
+        code.push_str("    instance.data = {\n");
+        for field in &resource.fields {
+            let name = self.ts_name(&field.name);
+            let input = format!("data.{}", name);
+            let value = match self.generate_ts_json_convert(&field.field_type, &input) {
+                Some(converted) if field.optional || field.nullable => {
+                    format!("{} == null ? {} : {}", input, input, converted)
+                }
+                Some(converted) => converted,
+                None => input,
+            };
+            code.push_str(&format!("      {}: {},\n", name, value));
+        }
+        code.push_str(&format!("    }} as unknown as I{};\n", resource.name));
+        code.push_str("    return instance;\n");
+        code.push_str("  }\n\n");
+        code
+    }
+
+    /// Expression converting the JSON value `input`, `None` when it is
+    /// already what the decoder would produce
+    fn generate_ts_json_convert(&self, ir_type: &IRType, input: &str) -> Option<String> {
+        match ir_type {
+            IRType::Primitive(p) if p == "number" && self.options.numbers == NumberMode::BigInt => {
+                Some(format!("BigInt({})", input))
+            }
+            IRType::Primitive(_) => None,
+            IRType::List(inner) => self
+                .generate_ts_json_convert(inner, "item")
+                .map(|item| format!("{}.map((item) => {})", input, item)),
+            IRType::ResourceRef(idx) => Some(format!("{}.fromJSON({})", self.ts_type_name(*idx, ""), input)),
+        }
+    }
+
+    fn generate_ts_field_decode(&self, field: &IRField) -> String {
+        let mut code = String::new();
+        let name = self.ts_name(&field.name);
+
+        // Handle optional
+        if field.optional {
+            code.push_str("    const isPresent = this.reader.readByte();\n");
+            code.push_str("    if (isPresent === 0) {\n");
+            code.push_str(&format!("      this.data.{} = undefined;\n", name));
+            code.push_str("    } else {\n");
+            code.push_str(&format!("      this.data.{} = {};\n",
+                name,
+                self.generate_ts_type_read(&field.field_type, false, "      ")));
+            code.push_str("    }\n");
+            return code;
+        }
+
+        // Handle nullable
+        if field.nullable {
+            code.push_str("    const isNull = this.reader.readByte();\n");
+            code.push_str("    if (isNull === 0) {\n");
+            code.push_str(&format!("      this.data.{} = null;\n", name));
+            code.push_str("    } else {\n");
+            code.push_str(&format!("      this.data.{} = {};\n",
+                name,
+                self.generate_ts_type_read(&field.field_type, false, "      ")));
+            code.push_str("    }\n");
+            return code;
+        }
+
+        // Regular field
+        code.push_str(&format!("    this.data.{} = {};\n",
+            name,
+            self.generate_ts_type_read(&field.field_type, false, "    ")));
+        code
+    }
+
+    /// Expression reading one value. `plain` reads through a local `reader`
+    /// into plain objects instead of the decoder classes.
+    fn generate_ts_type_read(&self, ir_type: &IRType, plain: bool, indent: &str) -> String {
+        let reader = if plain { "reader" } else { "this.reader" };
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => format!("{}.readString()", reader),
+                "number" => format!("{}.readNumber()", reader),
+                "bool" => format!("{}.readBool()", reader),
+                _ => "null".to_string(),
+            },
+            IRType::List(inner) => {
+                let inner_read = self.generate_ts_type_read(inner, plain, indent);
+                let limit = match self.options.wire.max_list_length {
+                    Some(max) => format!(
+                        "{}  if (count > {}) {{\n{}    throw new Error(`list of ${{count}} items exceeds the limit of {}`);\n{}  }}\n",
+                        indent, max, indent, max, indent
+                    ),
+                    None => String::new(),
+                };
+                format!(
+                    "(() => {{\n{}  const count = {}.readU32();\n{}{}  const items = [];\n{}  for (let i = 0; i < count; i++) {{\n{}    items.push({});\n{}  }}\n{}  return items;\n{}}})()",
+                    indent, reader, limit, indent, indent, indent, inner_read, indent, indent, indent
+                )
+            }
+            IRType::ResourceRef(idx) if plain => {
+                format!("{}(reader)", self.ts_type_name(*idx, "read"))
+            }
+            IRType::ResourceRef(idx) => {
+                format!("new {}(this.reader.buffer.slice(this.reader.offset))", self.ts_type_name(*idx, ""))
+            }
+        }
+    }
+
+    fn ir_type_to_typescript(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => "string".to_string(),
+                "number" => self.ts_number_type().to_string(),
+                "bool" => "boolean".to_string(),
+                _ => "any".to_string(),
+            },
+            IRType::List(inner) => format!("{}[]", self.ir_type_to_typescript(inner)),
+            IRType::ResourceRef(idx) => self.ts_type_name(*idx, "I"),
+        }
+    }
+
+    fn ir_type_to_typescript_server(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => "string".to_string(),
+                "number" => self.ts_number_type().to_string(),
+                "bool" => "boolean".to_string(),
+                _ => "any".to_string(),
+            },
+            IRType::List(inner) => format!("{}[]", self.ir_type_to_typescript_server(inner)),
+            IRType::ResourceRef(idx) => self.ts_type_name(*idx, ""),
+        }
+    }
+
+    // ========================================================================
+    // TypeScript Server Generation
+    // ========================================================================
+
+    fn generate_typescript_server(&self) -> String {
+        let mut code = String::new();
+
+        // Header
+        code.push_str("// Generated by Previous Compiler\n");
+        code.push_str("// DO NOT EDIT - This file is auto-generated\n\n");
+
+        // Binary writer utility class
+        code.push_str(&self.generate_binary_writer());
+        code.push('\n');
+
+        // Services decode request bodies, which needs the reader too
+        if !self.ir.services.is_empty() {
+            code.push_str(&self.generate_binary_reader());
+            code.push('\n');
+        }
+
+        // Generate each resource
+        let mut chunks: Vec<(Option<&str>, String)> = self
+            .ir
+            .resources
+            .iter()
+            .map(|resource| (resource.package.as_deref(), self.generate_ts_server_resource(resource)))
+            .collect();
+        if !self.ir.services.is_empty() {
+            for resource in &self.ir.resources {
+                let chunk = format!(
+                    "{}\n{}",
+                    self.generate_ts_interface(resource),
+                    self.generate_ts_plain_reader(resource)
+                );
+                chunks.push((resource.package.as_deref(), chunk));
+            }
+            for service in &self.ir.services {
+                chunks.push((service.package.as_deref(), self.generate_ts_service_handlers(service)));
+            }
+        }
+        code.push_str(&self.generate_ts_namespaced(chunks));
+
+        code
+    }
+
+    fn generate_binary_writer(&self) -> String {
+        r#"class BinaryWriter {
+  private buffer: number[];
+
+  constructor() {
+    this.buffer = [];
+  }
+
+  writeString(value: string): void {
+    const encoder = new TextEncoder();
+    const bytes = encoder.encode(value);
+    this.writeU32(bytes.length);
+    this.buffer.push(...bytes);
+  }
+
+  writeNumber(value: number | bigint): void {
+    const buffer = new ArrayBuffer(8);
+    const view = new DataView(buffer);
+    view.setBigInt64(0, BigInt(value), true); // little-endian
+    const bytes = new Uint8Array(buffer);
+    this.buffer.push(...bytes);
+  }
+
+  writeBool(value: boolean): void {
+    this.buffer.push(value ? 1 : 0);
+  }
+
+  writeU32(value: number): void {
+    const buffer = new ArrayBuffer(4);
+    const view = new DataView(buffer);
+    view.setUint32(0, value, true); // little-endian
+    const bytes = new Uint8Array(buffer);
+    this.buffer.push(...bytes);
+  }
+
+  writeByte(value: number): void {
+    this.buffer.push(value & 0xFF);
+  }
+
+  finish(): Uint8Array {
+    return new Uint8Array(this.buffer);
+  }
+}
+"#.to_string()
+    }
+
+    fn generate_ts_server_resource(&self, resource: &IRResource) -> String {
+        let mut code = String::new();
+
+        // Interface definition
+        code.push_str(&format!("export interface I{}Builder {{\n", resource.name));
+        for field in &resource.fields {
+            let ts_type = self.ir_type_to_typescript_server(&field.field_type);
+            let optional = if field.optional || field.nullable { "?" } else { "" };
+            code.push_str(&format!("  {}{}: {};\n", self.ts_name(&field.name), optional, ts_type));
+        }
+        code.push_str("}\n\n");
+
+        // Class definition
+        code.push_str(&format!("export class {} {{\n", resource.name));
+
+        // Private fields
+        for field in &resource.fields {
+            let ts_type = self.ir_type_to_typescript_server(&field.field_type);
+            let optional_type = if field.optional || field.nullable {
+                format!("{} | null | undefined", ts_type)
+            } else {
+                ts_type
+            };
+            code.push_str(&format!("  private _{}: {};\n", self.ts_name(&field.name), optional_type));
+        }
+        code.push('\n');
+
+        // Constructor
+        code.push_str("  constructor() {\n");
+        for field in &resource.fields {
+            let default = if field.optional {
+                "undefined".to_string()
+            } else if field.nullable {
+                "null".to_string()
+            } else {
+                self.ts_default_value(&field.field_type)
+            };
+            code.push_str(&format!("    this._{} = {};\n", self.ts_name(&field.name), default));
+        }
+        code.push_str("  }\n\n");
+
+        // Setter methods (builder pattern)
+        for field in &resource.fields {
+            let ts_type = self.ir_type_to_typescript_server(&field.field_type);
+            let param_type = if field.optional || field.nullable {
+                format!("{} | null | undefined", ts_type)
+            } else {
+                ts_type.clone()
+            };
+
+            code.push_str(&format!("  {}(value: {}): this {{\n", self.ts_name(&field.name), param_type));
+            code.push_str(&format!("    this._{} = value;\n", self.ts_name(&field.name)));
+            code.push_str("    return this;\n");
+            code.push_str("  }\n\n");
+        }
+
+        // Encode method
+        code.push_str("  encode(): Uint8Array {\n");
+        code.push_str("    const writer = new BinaryWriter();\n");
+        code.push_str("    this.encodeFields(writer);\n");
+        code.push_str("    return writer.finish();\n");
+        code.push_str("  }\n\n");
+
+        // encodeFields method
+        code.push_str("  private encodeFields(writer: BinaryWriter): void {\n");
+        for field in &resource.fields {
+            code.push_str(&self.generate_ts_field_encode(field));
+        }
+        code.push_str("  }\n");
+
+        code.push_str("}\n");
+        code
+    }
+
+    fn ts_default_value(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => "\"\"".to_string(),
+                "number" => match self.options.numbers {
+                    NumberMode::Number => "0".to_string(),
+                    NumberMode::BigInt => "0n".to_string(),
+                },
+                "bool" => "false".to_string(),
+                _ => "null".to_string(),
+            },
+            IRType::List(_) => "[]".to_string(),
+            IRType::ResourceRef(idx) => {
+                format!("new {}()", self.ts_type_name(*idx, ""))
+            }
+        }
+    }
+
+    fn generate_ts_field_encode(&self, field: &IRField) -> String {
+        let mut code = String::new();
+        let name = self.ts_name(&field.name);
+        let indent = "    ";
+
+        if field.optional {
+            // Optional field: write 1 byte flag, then value if present
+            code.push_str(&format!("{}if (this._{} === undefined) {{\n", indent, name));
+            code.push_str(&format!("{}  writer.writeByte(0); // absent\n", indent));
+            code.push_str(&format!("{}}} else {{\n", indent));
+            code.push_str(&format!("{}  writer.writeByte(1); // present\n", indent));
+            code.push_str(&self.generate_ts_value_write(&format!("this._{}", name), &field.field_type, false, "      "));
+            code.push_str(&format!("{}}}\n", indent));
+        } else if field.nullable {
+            // Nullable field: write 1 byte flag, then value if not null
+            code.push_str(&format!("{}if (this._{} === null || this._{} === undefined) {{\n", indent, name, name));
+            code.push_str(&format!("{}  writer.writeByte(0); // null\n", indent));
+            code.push_str(&format!("{}}} else {{\n", indent));
+            code.push_str(&format!("{}  writer.writeByte(1); // present\n", indent));
+            code.push_str(&self.generate_ts_value_write(&format!("this._{}", name), &field.field_type, false, "      "));
+            code.push_str(&format!("{}}}\n", indent));
+        } else {
+            // Required field: write value directly
+            code.push_str(&self.generate_ts_value_write(&format!("this._{}", name), &field.field_type, false, indent));
+        }
+
+        code
+    }
+
+    /// Statements writing one value. `plain` writes plain objects through
+    /// the `write{Name}` functions instead of the builder classes.
+    fn generate_ts_value_write(&self, var_name: &str, ir_type: &IRType, plain: bool, indent: &str) -> String {
+        let mut code = String::new();
+
+        match ir_type {
+            IRType::Primitive(p) => {
+                let write_method = match p.as_str() {
+                    "string" => "writeString",
+                    "number" => "writeNumber",
+                    "bool" => "writeBool",
+                    _ => "writeByte",
+                };
+                code.push_str(&format!("{}writer.{}({});\n", indent, write_method, var_name));
+            }
+            IRType::List(inner) => {
+                code.push_str(&format!("{}writer.writeU32({}.length);\n", indent, var_name));
+                code.push_str(&format!("{}for (const item of {}) {{\n", indent, var_name));
+                code.push_str(&self.generate_ts_value_write("item", inner, plain, &format!("{}  ", indent)));
+                code.push_str(&format!("{}}}\n", indent));
+            }
+            IRType::ResourceRef(idx) if plain => {
+                code.push_str(&format!("{}{}(writer, {});\n", indent, self.ts_type_name(*idx, "write"), var_name));
+            }
+            IRType::ResourceRef(_) => {
+                code.push_str(&format!("{}{}.encodeFields(writer);\n", indent, var_name));
+            }
+        }
+
+        code
+    }
+
+    // ========================================================================
+    // TypeScript Service Generation
+    // ========================================================================
+
+    /// `write{Name}` function encoding a plain `I{Name}` object, used for
+    /// request bodies
+    fn generate_ts_plain_writer(&self, resource: &IRResource) -> String {
+        let mut code = format!(
+            "export function write{}(writer: BinaryWriter, value: I{}): void {{\n",
+            resource.name, resource.name
+        );
+        for field in &resource.fields {
+            let var = format!("value.{}", self.ts_name(&field.name));
+            if field.optional || field.nullable {
+                let (check, absent) = if field.optional {
+                    (format!("{} === undefined", var), "absent")
+                } else {
+                    (format!("{} === null || {} === undefined", var, var), "null")
+                };
+                code.push_str(&format!("  if ({}) {{\n", check));
+                code.push_str(&format!("    writer.writeByte(0); // {}\n", absent));
+                code.push_str("  } else {\n");
+                code.push_str("    writer.writeByte(1); // present\n");
+                code.push_str(&self.generate_ts_value_write(&var, &field.field_type, true, "    "));
+                code.push_str("  }\n");
+            } else {
+                code.push_str(&self.generate_ts_value_write(&var, &field.field_type, true, "  "));
+            }
+        }
+        code.push_str("}\n");
+        code
+    }
+
+    /// `read{Name}` function decoding into a plain `I{Name}` object, used
+    /// for request bodies
+    fn generate_ts_plain_reader(&self, resource: &IRResource) -> String {
+        let mut code = format!(
+            "export function read{}(reader: BinaryReader): I{} {{\n",
+            resource.name, resource.name
+        );
+        code.push_str(&format!("  const value = {{}} as I{};\n", resource.name));
+        for field in &resource.fields {
+            let name = self.ts_name(&field.name);
+            if field.optional || field.nullable {
+                let absent = if field.optional { "undefined" } else { "null" };
+                code.push_str("  if (reader.readByte() === 0) {\n");
+                code.push_str(&format!("    value.{} = {};\n", name, absent));
+                code.push_str("  } else {\n");
+                code.push_str(&format!(
+                    "    value.{} = {};\n",
+                    name,
+                    self.generate_ts_type_read(&field.field_type, true, "    ")
+                ));
+                code.push_str("  }\n");
+            } else {
+                code.push_str(&format!(
+                    "  value.{} = {};\n",
+                    name,
+                    self.generate_ts_type_read(&field.field_type, true, "  ")
+                ));
+            }
+        }
+        code.push_str("  return value;\n");
+        code.push_str("}\n");
+        code
+    }
+
+    /// Method name of an endpoint: the HTTP method followed by the static
+    /// path segments and the parameters, e.g. `getUsersById`
+    fn ts_endpoint_name(&self, endpoint: &IREndpoint) -> String {
+        let mut name = endpoint.method.keyword().to_string();
+        for segment in endpoint.path.split('/').filter(|segment| !segment.starts_with(':')) {
+            for word in segment.split(|c: char| !c.is_ascii_alphanumeric()) {
+                name.push_str(&self.capitalize_first(word));
+            }
+        }
+        let params: Vec<String> = endpoint
+            .params()
+            .iter()
+            .map(|param| self.capitalize_first(&self.ts_name(param)))
+            .collect();
+        if !params.is_empty() {
+            name.push_str("By");
+            name.push_str(&params.join("And"));
+        }
+        name
+    }
+
+    /// Method names of every endpoint of a service, numbered on collision
+    fn ts_endpoint_names(&self, service: &IRService) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for endpoint in &service.endpoints {
+            let base = self.ts_endpoint_name(endpoint);
+            let mut name = base.clone();
+            let mut n = 2;
+            while names.contains(&name) {
+                name = format!("{}{}", base, n);
+                n += 1;
+            }
+            names.push(name);
+        }
+        names
+    }
+
+    /// Parameters shared by a client method and its server handler
+    fn ts_endpoint_params(&self, endpoint: &IREndpoint) -> String {
+        let mut params = Vec::new();
+        let path_params = endpoint.params();
+        if !path_params.is_empty() {
+            let fields: Vec<String> = path_params.iter().map(|param| format!("{}: string", param)).collect();
+            params.push(format!("params: {{ {} }}", fields.join("; ")));
+        }
+        if let Some(request) = endpoint.request {
+            params.push(format!("body: {}", self.ts_type_name(request, "I")));
+        }
+        params.join(", ")
+    }
+
+    /// Request helpers shared by every service client: content negotiation,
+    /// error decoding and the JSON fallback
+    fn generate_ts_client_runtime(&self) -> String {
+        r#"export interface PreviousClientOptions {
+  /** Headers sent with every request, e.g. authorization */
+  headers?: Record<string, string>;
+  /** Replaces the global fetch, e.g. in tests */
+  fetch?: (input: string, init: RequestInit) => Promise<Response>;
+}
+
+export interface PreviousRequestOptions {
+  signal?: AbortSignal;
+  headers?: Record<string, string>;
+}
+
+export class PreviousHttpError extends Error {
+  readonly status: number;
+  readonly body: unknown;
+
+  constructor(message: string, status: number, body: unknown) {
+    super(message);
+    this.name = "PreviousHttpError";
+    this.status = status;
+    this.body = body;
+  }
+}
+
+async function previousRequest(client: PreviousClientOptions, endpoint: string, url: string, method: string, options: PreviousRequestOptions, body?: Uint8Array): Promise<Response> {
+  const headers: Record<string, string> = {
+    "Accept": "/*MEDIA_TYPE*/, application/json;q=0.5",
+    ...client.headers,
+    ...options.headers,
+  };
+  if (body !== undefined) {
+    headers["Content-Type"] = "/*MEDIA_TYPE*/";
+  }
+  const fetchImpl = client.fetch ?? ((input: string, init: RequestInit) => fetch(input, init));
+  const response = await fetchImpl(url, { method, headers, body, signal: options.signal });
+  if (!response.ok) {
+    const text = await response.text();
+    let detail: unknown = text;
+    if ((response.headers.get("Content-Type") ?? "").includes("json")) {
+      try {
+        detail = JSON.parse(text);
+      } catch {
+        // keep the raw text
+      }
+    }
+    const message = typeof detail === "object" && detail !== null && typeof (detail as { message?: unknown }).message === "string"
+      ? (detail as { message: string }).message
+      : text || response.statusText;
+    throw new PreviousHttpError(`${endpoint} failed with status ${response.status}: ${message}`, response.status, detail);
+  }
+  return response;
+}
+
+async function previousDecode<T, D>(response: Response, decode: (bytes: Uint8Array) => T, fromJSON: (data: D) => T): Promise<T> {
+  if ((response.headers.get("Content-Type") ?? "").startsWith("application/json")) {
+    return fromJSON(await response.json() as D);
+  }
+  return decode(new Uint8Array(await response.arrayBuffer()));
+}
+"#
+        .replace("/*MEDIA_TYPE*/", PREVIOUS_MEDIA_TYPE)
+    }
+
+    fn generate_ts_service_client(&self, service: &IRService) -> String {
+        let mut code = String::new();
+        code.push_str(&format!("export class {}Client {{\n", service.name));
+        code.push_str("  private baseUrl: string;\n");
+        code.push_str("  private options: PreviousClientOptions;\n\n");
+        code.push_str("  constructor(baseUrl: string, options: PreviousClientOptions = {}) {\n");
+        code.push_str("    this.baseUrl = baseUrl.replace(/\\/+$/, \"\");\n");
+        code.push_str("    this.options = options;\n");
+        code.push_str("  }\n");
+
+        for (endpoint, name) in service.endpoints.iter().zip(self.ts_endpoint_names(service)) {
+            let returns = match endpoint.response {
+                Some(response) => self.ts_type_name(response, ""),
+                None => "void".to_string(),
+            };
+            let url: String = endpoint
+                .path
+                .split('/')
+                .map(|segment| match segment.strip_prefix(':') {
+                    Some(param) => format!("${{encodeURIComponent(params.{})}}", param),
+                    None => segment.replace('\\', "\\\\").replace('`', "\\`").replace('$', "\\$"),
+                })
+                .collect::<Vec<_>>()
+                .join("/");
+            let mut params = self.ts_endpoint_params(endpoint);
+            if !params.is_empty() {
+                params.push_str(", ");
+            }
+            params.push_str("options: PreviousRequestOptions = {}");
+
+            code.push('\n');
+            code.push_str(&format!("  async {}({}): Promise<{}> {{\n", name, params, returns));
+            let mut args = vec![
+                "this.options".to_string(),
+                serde_json::to_string(&format!("{} {}", endpoint.method, endpoint.path)).unwrap_or_default(),
+                format!("`${{this.baseUrl}}{}`", url),
+                format!("\"{}\"", endpoint.method),
+                "options".to_string(),
+            ];
+            if let Some(request) = endpoint.request {
+                code.push_str("    const writer = new BinaryWriter();\n");
+                code.push_str(&format!("    {}(writer, body);\n", self.ts_type_name(request, "write")));
+                args.push("writer.finish()".to_string());
+            }
+            match endpoint.response {
+                Some(response) => {
+                    let class = self.ts_type_name(response, "");
+                    code.push_str(&format!("    const response = await previousRequest({});\n", args.join(", ")));
+                    code.push_str(&format!(
+                        "    return previousDecode(response, (bytes) => new {}(bytes), (data: {}) => {}.fromJSON(data));\n",
+                        class,
+                        self.ts_type_name(response, "I"),
+                        class
+                    ));
+                }
+                None => code.push_str(&format!("    await previousRequest({});\n", args.join(", "))),
+            }
+            code.push_str("  }\n");
+        }
+
+        code.push_str("}\n");
+        code
+    }
+
+    /// Handler interface of a service, and a `handle{Service}` function
+    /// routing a request to it. The path excludes the query string; the
+    /// function resolves to `null` when no endpoint matches.
+    fn generate_ts_service_handlers(&self, service: &IRService) -> String {
+        let names = self.ts_endpoint_names(service);
+        let mut code = String::new();
+
+        code.push_str(&format!("export interface {}Handlers {{\n", service.name));
+        for (endpoint, name) in service.endpoints.iter().zip(&names) {
+            let returns = match endpoint.response {
+                Some(response) => self.ts_type_name(response, ""),
+                None => "void".to_string(),
+            };
+            code.push_str(&format!(
+                "  {}({}): {} | Promise<{}>;\n",
+                name,
+                self.ts_endpoint_params(endpoint),
+                returns,
+                returns
+            ));
+        }
+        code.push_str("}\n\n");
+
+        code.push_str(&format!(
+            "export async function handle{}(handlers: {}Handlers, method: string, path: string, body: Uint8Array): Promise<Uint8Array | null> {{\n",
+            service.name, service.name
+        ));
+        for (endpoint, name) in service.endpoints.iter().zip(&names) {
+            let params = endpoint.params();
+            let method = serde_json::to_string(&endpoint.method.to_string()).unwrap_or_default();
+            let mut args = Vec::new();
+            if params.is_empty() {
+                let path = serde_json::to_string(&endpoint.path).unwrap_or_default();
+                code.push_str(&format!("  if (method === {} && path === {}) {{\n", method, path));
+            } else {
+                let pattern: String = endpoint
+                    .path
+                    .split('/')
+                    .map(|segment| {
+                        if segment.starts_with(':') {
+                            "([^/]+)".to_string()
+                        } else {
+                            segment
+                                .chars()
+                                .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c.to_string() } else { format!("\\{}", c) })
+                                .collect()
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\\/");
+                code.push_str(&format!(
+                    "  const {}Match = method === {} ? path.match(/^{}$/) : null;\n",
+                    name, method, pattern
+                ));
+                code.push_str(&format!("  if ({}Match !== null) {{\n", name));
+                let fields: Vec<String> = params
+                    .iter()
+                    .enumerate()
+                    .map(|(i, param)| format!("{}: decodeURIComponent({}Match[{}])", param, name, i + 1))
+                    .collect();
+                args.push(format!("{{ {} }}", fields.join(", ")));
+            }
+            if let Some(request) = endpoint.request {
+                args.push(format!("{}(new BinaryReader(body))", self.ts_type_name(request, "read")));
+            }
+            let call = format!("await handlers.{}({})", name, args.join(", "));
+            if endpoint.response.is_some() {
+                code.push_str(&format!("    const result = {};\n", call));
+                code.push_str("    return result.encode();\n");
+            } else {
+                code.push_str(&format!("    {};\n", call));
+                code.push_str("    return new Uint8Array(0);\n");
+            }
+            code.push_str("  }\n");
+        }
+        code.push_str("  return null;\n");
+        code.push_str("}\n");
+        code
+    }
+
+    // ========================================================================
+    // OpenAPI Generation
+    // ========================================================================
+
+    /// Generate an OpenAPI 3.1 document describing every resource
+    ///
+    /// Each resource becomes an entry in `components.schemas`. Reusable
+    /// `components.requestBodies` and `components.responses` entries carry
+    /// the resources under the `application/x-previous` media type so paths
+    /// can reference them directly. Service endpoints become `paths`.
+    pub fn generate_openapi(&self) -> String {
+        use serde_json::{json, Map};
+
+        let mut schemas = Map::new();
+        let mut request_bodies = Map::new();
+        let mut responses = Map::new();
+
+        for resource in &self.ir.resources {
+            let name = resource.qualified_name();
+            schemas.insert(name.clone(), self.openapi_resource_schema(resource));
+
+            let content = json!({
+                PREVIOUS_MEDIA_TYPE: {
+                    "schema": { "$ref": format!("#/components/schemas/{}", name) }
+                }
+            });
+            request_bodies.insert(
+                name.clone(),
+                json!({ "required": true, "content": content.clone() }),
+            );
+            responses.insert(
+                name.clone(),
+                json!({ "description": format!("{} resource", name), "content": content }),
+            );
+        }
+
+        let mut paths = Map::new();
+        for service in &self.ir.services {
+            for (endpoint, name) in service.endpoints.iter().zip(self.ts_endpoint_names(service)) {
+                let path: Vec<String> = endpoint
+                    .path
+                    .split('/')
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(param) => format!("{{{}}}", param),
+                        None => segment.to_string(),
+                    })
+                    .collect();
+                let item = paths
+                    .entry(path.join("/"))
+                    .or_insert_with(|| json!({}));
+                if let Some(item) = item.as_object_mut() {
+                    item.insert(
+                        endpoint.method.keyword().to_string(),
+                        self.openapi_operation(service, endpoint, &name),
+                    );
+                }
+            }
+        }
+
+        let document = json!({
+            "openapi": "3.1.0",
+            "info": {
+                "title": "Previous API",
+                "version": "0.1.0"
+            },
+            "paths": paths,
+            "components": {
+                "schemas": schemas,
+                "requestBodies": request_bodies,
+                "responses": responses
+            },
+            "x-previous": {
+                "mediaType": PREVIOUS_MEDIA_TYPE,
+                "description": "Previous binary encoding: fields are written in declaration order, see the x-previous-index of each property"
+            }
+        });
+
+        let mut code = serde_json::to_string_pretty(&document).unwrap_or_default();
+        code.push('\n');
+        code
+    }
+
+    /// Operation of an endpoint, its id built from the method name the
+    /// TypeScript client uses
+    fn openapi_operation(&self, service: &IRService, endpoint: &IREndpoint, name: &str) -> serde_json::Value {
+        use serde_json::json;
+
+        let mut operation = json!({
+            "operationId": format!("{}.{}", service.qualified_name(), name),
+            "tags": [service.qualified_name()],
+        });
+        let params: Vec<serde_json::Value> = endpoint
+            .params()
+            .iter()
+            .map(|param| {
+                json!({ "name": param, "in": "path", "required": true, "schema": { "type": "string" } })
+            })
+            .collect();
+        if !params.is_empty() {
+            operation["parameters"] = json!(params);
+        }
+        if let Some(request) = endpoint.request {
+            let name = self.ir.resources[request].qualified_name();
+            operation["requestBody"] = json!({ "$ref": format!("#/components/requestBodies/{}", name) });
+        }
+        operation["responses"] = match endpoint.response {
+            Some(response) => {
+                let name = self.ir.resources[response].qualified_name();
+                json!({ "200": { "$ref": format!("#/components/responses/{}", name) } })
+            }
+            None => json!({ "204": { "description": "No content" } }),
+        };
+        operation
+    }
+
+    fn openapi_resource_schema(&self, resource: &IRResource) -> serde_json::Value {
+        use serde_json::{json, Map, Value as Json};
+
+        let mut properties = Map::new();
+        let mut required = Vec::new();
+
+        for field in &resource.fields {
+            let mut schema = self.openapi_type_schema(&field.field_type);
+
+            if field.nullable {
+                schema = match schema {
+                    Json::Object(mut obj) if obj.contains_key("type") => {
+                        let ty = obj.remove("type").unwrap_or(Json::Null);
+                        obj.insert("type".to_string(), json!([ty, "null"]));
+                        Json::Object(obj)
+                    }
+                    other => json!({ "oneOf": [other, { "type": "null" }] }),
+                };
+            }
+
+            if let (Json::Object(obj), Some(default)) = (&mut schema, &field.default) {
+                let default = match &default.value {
+                    Literal::String(s) => json!(s),
+                    Literal::Number(n) => json!(n),
+                    Literal::Bool(b) => json!(b),
+                };
+                obj.insert("default".to_string(), default);
+            }
+
+            if let Json::Object(obj) = &mut schema {
+                obj.insert("x-previous-index".to_string(), json!(field.index));
+            }
+
+            if !field.optional {
+                required.push(Json::String(field.name.clone()));
+            }
+            properties.insert(field.name.clone(), schema);
+        }
+
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+            "additionalProperties": false
+        })
+    }
+
+    fn openapi_type_schema(&self, ir_type: &IRType) -> serde_json::Value {
+        use serde_json::json;
+
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => json!({ "type": "string" }),
+                "number" => json!({ "type": "integer", "format": "int64" }),
+                "bool" => json!({ "type": "boolean" }),
+                _ => json!({}),
+            },
+            IRType::List(inner) => json!({
+                "type": "array",
+                "items": self.openapi_type_schema(inner)
+            }),
+            IRType::ResourceRef(idx) => json!({
+                "$ref": format!("#/components/schemas/{}", self.ir.resources[*idx].qualified_name())
+            }),
+        }
+    }
+
+    /// Name of a resource in targets without namespaces (proto, GraphQL),
+    /// e.g. `billing_Invoice`
+    fn flat_name(resource: &IRResource) -> String {
+        resource.qualified_name().replace('.', "_")
+    }
+
+    // ========================================================================
+    // Protocol Buffers Generation
+    // ========================================================================
+
+    /// Generate a proto3 file with one message per resource
+    ///
+    /// Field numbers follow the field index (index + 1). Attributes proto3
+    /// cannot express are kept as comments: `nullable` fields become
+    /// `optional` and `default` values are documented only. A list of lists
+    /// is wrapped in a nested message since `repeated repeated` is invalid.
+    ///
+    /// Fails when two resources flatten to the same message name, or when a
+    /// nested wrapper message would shadow a resource its message refers to.
+    pub fn generate_proto(&self) -> Result<String, CompileError> {
+        self.check_proto_names()?;
+
+        let mut code = String::new();
+
+        code.push_str("// Generated by Previous Compiler\n");
+        code.push_str("// DO NOT EDIT - This file is auto-generated\n");
+        code.push_str("//\n");
+        code.push_str("// proto3 has a single presence marker: `optional` and `nullable` fields\n");
+        code.push_str("// are both emitted as `optional`, nullable ones carry a `// nullable` comment.\n\n");
+        code.push_str("syntax = \"proto3\";\n");
+
+        for resource in &self.ir.resources {
+            code.push('\n');
+            code.push_str(&self.generate_proto_message(resource));
+        }
+
+        Ok(code)
+    }
+
+    /// Reject message names that would clash once flattened
+    ///
+    /// `a.B_C` and `a.B.C` both become `a_B_C`, and a wrapper such as
+    /// `Post.TagsItem` hides a top-level `TagsItem` inside `Post`.
+    fn check_proto_names(&self) -> Result<(), CompileError> {
+        let collision = |message: String| -> CompileError {
+            DiagnosticKind::Codegen {
+                target: "proto".to_string(),
+                message,
+            }
+            .into()
+        };
+
+        let mut seen: std::collections::HashMap<String, String> = std::collections::HashMap::new();
+        for resource in &self.ir.resources {
+            let flat = Self::flat_name(resource);
+            if let Some(other) = seen.insert(flat.clone(), resource.qualified_name()) {
+                return Err(collision(format!(
+                    "'{}' and '{}' both become message '{}'",
+                    other,
+                    resource.qualified_name(),
+                    flat
+                )));
+            }
+        }
+
+        let graph = CycleDetector::build(&self.ir)?;
+        for (idx, resource) in self.ir.resources.iter().enumerate() {
+            let mut wrappers: Vec<String> = Vec::new();
+            for field in &resource.fields {
+                for wrapper in self.proto_wrapper_names(&field.field_type, &field.name) {
+                    if wrappers.contains(&wrapper) {
+                        return Err(collision(format!(
+                            "two list fields of '{}' both need the nested message '{}'",
+                            resource.qualified_name(),
+                            wrapper
+                        )));
+                    }
+                    wrappers.push(wrapper);
+                }
+            }
+
+            for to in graph.references(idx) {
+                let target = &self.ir.resources[to];
+                if wrappers.contains(&Self::flat_name(target)) {
+                    return Err(collision(format!(
+                        "nested message '{}.{}' would shadow the referenced message '{}'",
+                        Self::flat_name(resource),
+                        Self::flat_name(target),
+                        target.qualified_name()
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Names of the wrapper messages `generate_proto_list_wrapper` nests
+    /// into a message for a field, outermost first
+    fn proto_wrapper_names(&self, field_type: &IRType, field_name: &str) -> Vec<String> {
+        let mut names = Vec::new();
+        let mut name = format!("{}Item", self.capitalize_first(field_name));
+        let mut current = field_type;
+        while let IRType::List(inner) = current {
+            if !matches!(inner.as_ref(), IRType::List(_)) {
+                break;
+            }
+            names.push(name.clone());
+            name.push_str("Item");
+            current = inner;
+        }
+        names
+    }
+
+    fn generate_proto_message(&self, resource: &IRResource) -> String {
+        let mut code = String::new();
+        let mut wrappers = String::new();
+        let mut fields = String::new();
+
+        for field in &resource.fields {
+            let number = field.index + 1;
+            let mut comments = Vec::new();
+            if field.nullable {
+                comments.push("nullable".to_string());
+            }
+            if let Some(default) = &field.default {
+                comments.push(format!("default({})", literal_to_source(&default.value)));
+            }
+            let comment = if comments.is_empty() {
+                String::new()
+            } else {
+                format!(" // {}", comments.join(", "))
+            };
+
+            let label = if field.optional || field.nullable { "optional " } else { "" };
+            let field_line = match &field.field_type {
+                IRType::List(inner) => {
+                    let item_type = match inner.as_ref() {
+                        IRType::List(_) => {
+                            let wrapper = format!("{}Item", self.capitalize_first(&field.name));
+                            wrappers.push_str(&self.generate_proto_list_wrapper(&wrapper, inner, "  "));
+                            wrapper
+                        }
+                        other => self.ir_type_to_proto(other),
+                    };
+                    // proto3 does not allow `optional repeated`, the presence is kept as a comment
+                    let comment = if label.is_empty() {
+                        comment
+                    } else if comment.is_empty() {
+                        " // optional".to_string()
+                    } else {
+                        comment.replacen(" // ", " // optional, ", 1)
+                    };
+                    format!("  repeated {} {} = {};{}\n", item_type, field.name, number, comment)
+                }
+                other => format!(
+                    "  {}{} {} = {};{}\n",
+                    label,
+                    self.ir_type_to_proto(other),
+                    field.name,
+                    number,
+                    comment
+                ),
+            };
+            fields.push_str(&field_line);
+        }
+
+        code.push_str(&format!("message {} {{\n", Self::flat_name(resource)));
+        code.push_str(&wrappers);
+        code.push_str(&fields);
+        code.push_str("}\n");
+        code
+    }
+
+    /// Wrap a nested list type into a message holding a single repeated field
+    fn generate_proto_list_wrapper(&self, name: &str, list_type: &IRType, indent: &str) -> String {
+        let mut code = String::new();
+        let mut nested = String::new();
+
+        let item_type = match list_type {
+            IRType::List(inner) => match inner.as_ref() {
+                IRType::List(_) => {
+                    let wrapper = format!("{}Item", name);
+                    nested = self.generate_proto_list_wrapper(&wrapper, inner, &format!("{}  ", indent));
+                    wrapper
+                }
+                other => self.ir_type_to_proto(other),
+            },
+            other => self.ir_type_to_proto(other),
+        };
+
+        code.push_str(&format!("{}message {} {{\n", indent, name));
+        code.push_str(&nested);
+        code.push_str(&format!("{}  repeated {} items = 1;\n", indent, item_type));
+        code.push_str(&format!("{}}}\n", indent));
+        code
+    }
+
+    fn ir_type_to_proto(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => "string".to_string(),
+                "number" => "int64".to_string(),
+                "bool" => "bool".to_string(),
+                _ => "bytes".to_string(),
+            },
+            IRType::List(inner) => self.ir_type_to_proto(inner),
+            IRType::ResourceRef(idx) => Self::flat_name(&self.ir.resources[*idx]),
+        }
+    }
+
+    // ========================================================================
+    // GraphQL SDL Generation
+    // ========================================================================
+
+    /// Generate GraphQL SDL `type` definitions, one per resource
+    ///
+    /// Required fields are non-null, `optional` and `nullable` fields are
+    /// nullable and list items are always non-null. `number` is a 64-bit
+    /// integer, which does not fit GraphQL's 32-bit `Int`, so it maps to the
+    /// custom `Int64` scalar declared at the top of the document.
+    pub fn generate_graphql(&self) -> String {
+        let mut code = String::new();
+
+        code.push_str("# Generated by Previous Compiler\n");
+        code.push_str("# DO NOT EDIT - This file is auto-generated\n\n");
+
+        let uses_number = self
+            .ir
+            .resources
+            .iter()
+            .flat_map(|r| r.fields.iter())
+            .any(|f| Self::type_uses_primitive(&f.field_type, "number"));
+        if uses_number {
+            code.push_str("\"\"\"64-bit signed integer\"\"\"\n");
+            code.push_str("scalar Int64\n\n");
+        }
+
+        for (i, resource) in self.ir.resources.iter().enumerate() {
+            if i > 0 {
+                code.push('\n');
+            }
+            code.push_str(&format!("type {} {{\n", Self::flat_name(resource)));
+            for field in &resource.fields {
+                if let Some(default) = &field.default {
+                    // A plain string description, its escapes are the same as JSON's
+                    let description = format!("Default: {}", literal_to_source(&default.value));
+                    code.push_str(&format!("  {}\n", json!(description)));
+                }
+                let non_null = if field.optional || field.nullable { "" } else { "!" };
+                code.push_str(&format!(
+                    "  {}: {}{}\n",
+                    field.name,
+                    self.ir_type_to_graphql(&field.field_type),
+                    non_null
+                ));
+            }
+            code.push_str("}\n");
+        }
+
+        code
+    }
+
+    fn ir_type_to_graphql(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => "String".to_string(),
+                "number" => "Int64".to_string(),
+                "bool" => "Boolean".to_string(),
+                _ => "String".to_string(),
+            },
+            IRType::List(inner) => format!("[{}!]", self.ir_type_to_graphql(inner)),
+            IRType::ResourceRef(idx) => Self::flat_name(&self.ir.resources[*idx]),
+        }
+    }
+
+    fn type_uses_primitive(ir_type: &IRType, primitive: &str) -> bool {
+        match ir_type {
+            IRType::Primitive(p) => p == primitive,
+            IRType::List(inner) => Self::type_uses_primitive(inner, primitive),
+            IRType::ResourceRef(_) => false,
+        }
+    }
+
+    // ========================================================================
+    // Rust Generation
+    // ========================================================================
+
+    /// Generate Rust structs implementing `previous::Wire`, one per resource
+    ///
+    /// Meant to be `include!`d from `OUT_DIR` (see previous-build). Each
+    /// package becomes a `pub mod`, fields are snake_case, `optional` and
+    /// `nullable` each add an `Option` layer and `number` is `i64`. Services
+    /// are not generated.
+    pub fn generate_rust(&self) -> Result<String, CompileError> {
+        let mut code = String::new();
+
+        code.push_str("// Generated by Previous Compiler\n");
+        code.push_str("// DO NOT EDIT - This file is auto-generated\n\n");
+        code.push_str(&self.generate_rust_module(&[])?);
+
+        Ok(code)
+    }
+
+    /// Resources declared in the module at `path`, then its child modules
+    fn generate_rust_module(&self, path: &[&str]) -> Result<String, CompileError> {
+        let mut items = Vec::new();
+        let mut children: Vec<&str> = Vec::new();
+
+        for (idx, resource) in self.ir.resources.iter().enumerate() {
+            let module = Self::rust_module_path(resource);
+            if module == path {
+                items.push(self.generate_rust_struct(idx)?);
+            } else if module.len() > path.len() && module.starts_with(path) && !children.contains(&module[path.len()]) {
+                children.push(module[path.len()]);
+            }
+        }
+
+        for child in children {
+            let mut child_path = path.to_vec();
+            child_path.push(child);
+            let mut module = format!("pub mod {} {{\n", rust_ident(child));
+            for line in self.generate_rust_module(&child_path)?.lines() {
+                if !line.is_empty() {
+                    module.push_str("    ");
+                }
+                module.push_str(line);
+                module.push('\n');
+            }
+            module.push_str("}\n");
+            items.push(module);
+        }
+
+        Ok(items.join("\n"))
+    }
+
+    fn generate_rust_struct(&self, idx: usize) -> Result<String, CompileError> {
+        let resource = &self.ir.resources[idx];
+        let module = Self::rust_module_path(resource);
+        let error = |message: String| -> CompileError {
+            DiagnosticKind::Codegen {
+                target: "Rust".to_string(),
+                message,
+            }
+            .into()
+        };
+
+        if resource.name == "Self" {
+            return Err(error(format!("'{}' is not a valid struct name", resource.qualified_name())));
+        }
+
+        let mut names: Vec<String> = Vec::new();
+        for field in &resource.fields {
+            let name = rust_ident(&snake_case(&field.name));
+            if names.contains(&name) {
+                return Err(error(format!(
+                    "two fields of '{}' become the Rust field '{}'",
+                    resource.qualified_name(),
+                    name
+                )));
+            }
+            names.push(name);
+        }
+
+        let mut code = String::new();
+        code.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+        code.push_str(&format!("pub struct {} {{\n", resource.name));
+        for (field, name) in resource.fields.iter().zip(&names) {
+            if let Some(default) = &field.default {
+                code.push_str(&format!("    /// Default: `{}`\n", literal_to_source(&default.value)));
+            }
+            let mut rust_type = self.ir_type_to_rust(&field.field_type, &module);
+            for _ in 0..(field.optional as usize + field.nullable as usize) {
+                rust_type = format!("Option<{}>", rust_type);
+            }
+            code.push_str(&format!("    pub {}: {},\n", name, rust_type));
+        }
+        code.push_str("}\n\n");
+
+        // Unused parameters would warn in the including crate
+        let (encoder, decoder) = if names.is_empty() { ("_encoder", "_decoder") } else { ("encoder", "decoder") };
+
+        code.push_str(&format!("impl ::previous::Wire for {} {{\n", resource.name));
+        code.push_str(&format!(
+            "    const MIN_SIZE: usize = {};\n\n",
+            min_encoded_size(&IRType::ResourceRef(idx), &self.ir)
+        ));
+        code.push_str(&format!("    fn encode(&self, {}: &mut ::previous::BinaryEncoder) {{\n", encoder));
+        for name in &names {
+            code.push_str(&format!("        ::previous::Wire::encode(&self.{}, encoder);\n", name));
+        }
+        code.push_str("    }\n\n");
+        code.push_str(&format!(
+            "    fn decode({}: &mut ::previous::BinaryDecoder<'_>) -> Result<Self, ::previous::CompileError> {{\n",
+            decoder
+        ));
+        code.push_str(&format!("        Ok({} {{\n", resource.name));
+        for name in &names {
+            code.push_str(&format!("            {}: ::previous::Wire::decode(decoder)?,\n", name));
+        }
+        code.push_str("        })\n");
+        code.push_str("    }\n");
+        code.push_str("}\n");
+
+        Ok(code)
+    }
+
+    /// Module a resource is generated in, one segment per part of its package
+    fn rust_module_path(resource: &IRResource) -> Vec<&str> {
+        resource.package.as_deref().map(|p| p.split('.').collect()).unwrap_or_default()
+    }
+
+    /// Rust type of a value, resources in other modules are reached through
+    /// `super`, e.g. `Vec<super::billing::Invoice>`
+    fn ir_type_to_rust(&self, ir_type: &IRType, from: &[&str]) -> String {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => "String".to_string(),
+                "number" => "i64".to_string(),
+                _ => "bool".to_string(),
+            },
+            IRType::List(inner) => format!("Vec<{}>", self.ir_type_to_rust(inner, from)),
+            IRType::ResourceRef(idx) => {
+                let target = &self.ir.resources[*idx];
+                let to = Self::rust_module_path(target);
+                let common = from.iter().zip(&to).take_while(|(a, b)| a == b).count();
+                let mut segments: Vec<String> = vec!["super".to_string(); from.len() - common];
+                segments.extend(to[common..].iter().map(|segment| rust_ident(segment)));
+                segments.push(target.name.clone());
+                segments.join("::")
+            }
+        }
+    }
+
+    fn capitalize_first(&self, s: &str) -> String {
+        let mut chars = s.chars();
+        match chars.next() {
+            None => String::new(),
+            Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        }
+    }
+}
+
+// ============================================================================
+// TOKEN TYPES
+// ============================================================================
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    // Keywords
+    Package,
+    Resource,
+    Service,
+    String,
+    Number,
+    Bool,
+    Nullable,
+    Optional,
+    Default,
+    List,
+    True,
+    False,
+
+    // Identifiers and literals
+    Identifier(String),
+    Comment(String),
+    StringLiteral(String),
+    NumberLiteral(i64),
+
+    // Symbols
+    LeftBrace,
+    RightBrace,
+    LeftParen,
+    RightParen,
+    Arrow,
+    Semicolon,
+
+    // Special
+    Eof,
+}
+
+// ============================================================================
+// LEXER
+// ============================================================================
+
+#[derive(Debug)]
+pub struct Lexer {
+    input: Vec<char>,
+    position: usize,
+    line: usize,
+    column: usize,
+    token_start: usize,
+    token_line: usize,
+    token_column: usize,
+}
+
+impl Lexer {
+    pub fn new(input: &str) -> Self {
+        Lexer {
+            input: input.chars().collect(),
+            position: 0,
+            line: 1,
+            column: 1,
+            token_start: 0,
+            token_line: 1,
+            token_column: 1,
+        }
+    }
+
+    fn current_char(&self) -> Option<char> {
+        if self.position < self.input.len() {
+            Some(self.input[self.position])
+        } else {
+            None
+        }
+    }
+
+    fn peek_char(&self, offset: usize) -> Option<char> {
+        let pos = self.position + offset;
+        if pos < self.input.len() {
+            Some(self.input[pos])
+        } else {
+            None
+        }
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let ch = self.current_char();
+        if ch.is_some() {
+            self.position += 1;
+        }
+        if ch == Some('\n') {
+            self.line += 1;
+            self.column = 1;
+        } else if ch.is_some() {
+            self.column += 1;
+        }
+        ch
+    }
+
+    /// Line (1-based) where the last returned token starts
+    pub fn token_line(&self) -> usize {
+        self.token_line
+    }
+
+    /// Span of the last returned token
+    pub fn token_span(&self) -> Span {
+        Span {
+            line: self.token_line,
+            column: self.token_column,
+            length: self.position - self.token_start,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(ch) = self.current_char() {
+            if ch.is_whitespace() {
+                self.advance();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Read an identifier, qualified names such as `billing.Invoice` are
+    /// read as one
+    fn read_identifier(&mut self) -> String {
+        let mut ident = String::new();
+        while let Some(ch) = self.current_char() {
+            let qualifies = ch == '.' && self.peek_char(1).is_some_and(|next| next.is_alphabetic() || next == '_');
+            if ch.is_alphanumeric() || ch == '_' || qualifies {
+                ident.push(ch);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        ident
+    }
+
+    fn read_string(&mut self) -> String {
+        let mut string = String::new();
+        self.advance(); // skip opening quote
+        while let Some(ch) = self.current_char() {
+            if ch == '"' {
+                self.advance();
+                break;
+            }
+            string.push(ch);
+            self.advance();
+        }
+        string
+    }
+
+    /// Read a `//` comment up to the end of the line, without the slashes
+    fn read_comment(&mut self) -> String {
+        self.advance();
+        self.advance();
+        let mut comment = String::new();
+        while let Some(ch) = self.current_char() {
+            if ch == '\n' {
+                break;
+            }
+            comment.push(ch);
+            self.advance();
+        }
+        comment.trim_end().to_string()
+    }
+
+    fn read_number(&mut self) -> i64 {
+        let mut num_str = String::new();
+        while let Some(ch) = self.current_char() {
+            if ch.is_ascii_digit() {
+                num_str.push(ch);
+                self.advance();
+            } else {
+                break;
+            }
+        }
+        num_str.parse().unwrap_or(0)
+    }
+
+    pub fn next_token(&mut self) -> Token {
+        self.skip_whitespace();
+        self.token_start = self.position;
+        self.token_line = self.line;
+        self.token_column = self.column;
+
+        match self.current_char() {
+            None => Token::Eof,
+            Some('/') if self.peek_char(1) == Some('/') => {
+                let comment = self.read_comment();
+                Token::Comment(comment)
+            }
+            Some('{') => {
+                self.advance();
+                Token::LeftBrace
+            }
+            Some('}') => {
+                self.advance();
+                Token::RightBrace
+            }
+            Some('(') => {
+                self.advance();
+                Token::LeftParen
+            }
+            Some(')') => {
+                self.advance();
+                Token::RightParen
+            }
+            Some('-') if self.peek_char(1) == Some('>') => {
+                self.advance();
+                self.advance();
+                Token::Arrow
+            }
+            Some(';') => {
+                self.advance();
+                Token::Semicolon
+            }
+            Some('"') => {
+                let string = self.read_string();
+                Token::StringLiteral(string)
+            }
+            Some(ch) if ch.is_ascii_digit() => {
+                let num = self.read_number();
+                Token::NumberLiteral(num)
+            }
+            Some(ch) if ch.is_alphabetic() || ch == '_' => {
+                let ident = self.read_identifier();
+                match ident.as_str() {
+                    "package" => Token::Package,
+                    "resource" => Token::Resource,
+                    "service" => Token::Service,
+                    "string" => Token::String,
+                    "number" => Token::Number,
+                    "bool" => Token::Bool,
+                    "nullable" => Token::Nullable,
+                    "optional" => Token::Optional,
+                    "default" => Token::Default,
+                    "list" => Token::List,
+                    "true" => Token::True,
+                    "false" => Token::False,
+                    _ => Token::Identifier(ident),
+                }
+            }
+            Some(_) => {
+                self.advance();
+                self.next_token()
+            }
+        }
+    }
+}
+
+// ============================================================================
+// PARSER
+// ============================================================================
+
+/// A comment removed from the token stream, remembered for the AST trivia
+struct CommentToken {
+    text: String,
+    line: usize,
+    /// Index of the token following the comment
+    before: usize,
+}
+
+pub struct Parser {
+    tokens: Vec<Token>,
+    spans: Vec<Span>,
+    comments: Vec<CommentToken>,
+    next_comment: usize,
+    position: usize,
+    errors: Vec<Diagnostic>,
+}
+
+// Diagnostics are only built on the error path, boxing them buys nothing
+#[allow(clippy::result_large_err)]
+impl Parser {
+    pub fn new(input: &str) -> Self {
+        let mut lexer = Lexer::new(input);
+        let mut tokens = Vec::new();
+        let mut spans = Vec::new();
+        let mut comments = Vec::new();
+
+        loop {
+            let token = lexer.next_token();
+            if let Token::Comment(text) = token {
+                comments.push(CommentToken {
+                    text,
+                    line: lexer.token_line(),
+                    before: tokens.len(),
+                });
+                continue;
+            }
+            spans.push(lexer.token_span());
+            if token == Token::Eof {
+                tokens.push(token);
+                break;
+            }
+            tokens.push(token);
+        }
+
+        Parser {
+            tokens,
+            spans,
+            comments,
+            next_comment: 0,
+            position: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Error for the current token, which is not what the grammar expects
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        Diagnostic::error(DiagnosticKind::UnexpectedToken {
+            expected: expected.to_string(),
+            got: format!("{:?}", self.current_token()),
+        })
+        .with_span(self.current_span())
+    }
+
+    /// Span of the token at `index`, or of the last token past the end
+    fn span_at(&self, index: usize) -> Span {
+        self.spans
+            .get(index)
+            .or(self.spans.last())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Line of the token at `index`, or of the last token past the end
+    fn line_at(&self, index: usize) -> usize {
+        self.span_at(index).line
+    }
+
+    /// Span of the token the parser is looking at, e.g. where an error occurred
+    pub fn current_span(&self) -> Span {
+        self.span_at(self.position)
+    }
+
+    /// Take every comment that precedes the token at `index`
+    fn take_comments_before(&mut self, index: usize) -> Vec<String> {
+        let mut taken = Vec::new();
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.before > index {
+                break;
+            }
+            taken.push(comment.text.clone());
+            self.next_comment += 1;
+        }
+        taken
+    }
+
+    /// Take the comment on the same line as the previous token, if any
+    fn take_trailing_comment(&mut self) -> Option<String> {
+        if self.position == 0 {
+            return None;
+        }
+        let line = self.line_at(self.position - 1);
+        let comment = self.comments.get(self.next_comment)?;
+        if comment.before == self.position && comment.line == line {
+            self.next_comment += 1;
+            return Some(comment.text.clone());
+        }
+        None
+    }
+
+    /// Whether a blank line separates the previous token from the next
+    /// node, whose leading comments (if any) start the node
+    fn blank_line_before_node(&self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+        let previous_line = self.line_at(self.position - 1);
+        let first_line = match self.comments.get(self.next_comment) {
+            Some(comment) if comment.before <= self.position => comment.line,
+            _ => self.line_at(self.position),
+        };
+        first_line > previous_line + 1
+    }
+
+    fn current_token(&self) -> &Token {
+        self.tokens.get(self.position).unwrap_or(&Token::Eof)
+    }
+
+    #[allow(dead_code)]
+    fn peek_token(&self, offset: usize) -> &Token {
+        self.tokens
+            .get(self.position + offset)
+            .unwrap_or(&Token::Eof)
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.current_token().clone();
+        if self.position < self.tokens.len() {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), Diagnostic> {
+        let current = self.current_token();
+        let matches = match (&expected, current) {
+            (Token::Resource, Token::Resource) => true,
+            (Token::LeftBrace, Token::LeftBrace) => true,
+            (Token::RightBrace, Token::RightBrace) => true,
+            (Token::LeftParen, Token::LeftParen) => true,
+            (Token::RightParen, Token::RightParen) => true,
+            _ => std::mem::discriminant(&expected) == std::mem::discriminant(current),
+        };
+        if matches {
+            self.advance();
+            Ok(())
+        } else {
+            Err(self.unexpected(&format!("{:?}", expected)))
+        }
+    }
+
+    /// Parse the whole input, failing with every syntax error found
+    pub fn parse(&mut self) -> Result<Program, CompileError> {
+        let (program, errors) = self.parse_with_errors();
+        if errors.is_empty() {
+            Ok(program)
+        } else {
+            Err(CompileError::new(errors))
+        }
+    }
+
+    /// Parse the whole input, recovering from syntax errors
+    ///
+    /// After an error inside a resource or service the parser skips to the
+    /// next field (endpoint) line or the closing `}`; after an error in a
+    /// header it skips to the next `resource` or `service`. The returned
+    /// program holds everything that could be parsed.
+    pub fn parse_with_errors(&mut self) -> (Program, Vec<Diagnostic>) {
+        let package = if self.current_token() == &Token::Package {
+            match self.parse_package() {
+                Ok(package) => Some(package),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.recover_to_resource();
+                    None
+                }
+            }
+        } else {
+            None
+        };
+        let package_name = package.as_ref().map(|p: &PackageDecl| p.name.clone());
+
+        let mut resources = Vec::new();
+        let mut services = Vec::new();
+
+        while self.current_token() != &Token::Eof {
+            let start = self.position;
+            let result = match self.current_token() {
+                Token::Resource => self.parse_resource().map(|mut resource| {
+                    resource.package = package_name.clone();
+                    resources.push(resource);
+                }),
+                Token::Service => self.parse_service().map(|mut service| {
+                    service.package = package_name.clone();
+                    services.push(service);
+                }),
+                _ => {
+                    let error = self.unexpected("Resource or Service");
+                    self.errors.push(error);
+                    self.recover_to_resource();
+                    continue;
+                }
+            };
+            if let Err(e) = result {
+                self.errors.push(e);
+                if self.position == start || !self.at_declaration() {
+                    self.recover_to_resource();
+                }
+            }
+        }
+
+        let trailing_comments = self.take_comments_before(self.position);
+        let program = Program {
+            package,
+            resources,
+            services,
+            trailing_comments,
+        };
+        (program, std::mem::take(&mut self.errors))
+    }
+
+    /// `package name`, only allowed before the first resource
+    fn parse_package(&mut self) -> Result<PackageDecl, Diagnostic> {
+        let blank_line_before = self.blank_line_before_node();
+        let leading = self.take_comments_before(self.position);
+
+        self.expect(Token::Package)?;
+
+        let span = self.current_span();
+        let name = match self.current_token().clone() {
+            Token::Identifier(id) => {
+                self.advance();
+                id
+            }
+            _ => return Err(self.unexpected("package name")),
+        };
+        let trailing = self.take_trailing_comment();
+
+        Ok(PackageDecl {
+            name,
+            span,
+            trivia: Trivia {
+                leading,
+                trailing,
+                blank_line_before,
+            },
+        })
+    }
+
+    /// Whether the current token starts a top-level declaration
+    fn at_declaration(&self) -> bool {
+        matches!(self.current_token(), Token::Resource | Token::Service)
+    }
+
+    /// Skip tokens up to the next `resource` or `service` keyword
+    fn recover_to_resource(&mut self) {
+        self.advance();
+        while !self.at_declaration() && self.current_token() != &Token::Eof {
+            self.advance();
+        }
+    }
+
+    /// Skip the rest of a broken field or endpoint: up to the first token on
+    /// a later line than `start`, a closing `}` or a declaration keyword
+    fn recover_to_next_field(&mut self, start: usize) {
+        let start_line = self.line_at(start);
+        if self.position == start {
+            self.advance();
+        }
+        while !matches!(self.current_token(), Token::RightBrace | Token::Resource | Token::Service | Token::Eof)
+            && self.line_at(self.position) <= start_line
+        {
+            self.advance();
+        }
+    }
+
+    fn parse_resource(&mut self) -> Result<Resource, Diagnostic> {
+        let blank_line_before = self.blank_line_before_node();
+        let leading = self.take_comments_before(self.position);
+
+        self.expect(Token::Resource)?;
+
+        let name_span = self.current_span();
+        let name = match self.current_token().clone() {
+            Token::Identifier(id) => {
+                self.advance();
+                id
+            }
+            _ => return Err(self.unexpected("resource name")),
+        };
+
+        // Validate PascalCase, the resource is still parsed to find more errors
+        if !name.chars().next().unwrap().is_uppercase() || name.contains('.') {
+            self.errors
+                .push(Diagnostic::error(DiagnosticKind::InvalidResourceName { name: name.clone() }).with_span(name_span));
+        }
+
+        self.expect(Token::LeftBrace)?;
+        let trailing = self.take_trailing_comment();
+
+        let mut fields = Vec::new();
+        let mut index = 0;
+
+        while !matches!(self.current_token(), Token::RightBrace | Token::Resource | Token::Service | Token::Eof) {
+            let start = self.position;
+            match self.parse_field(index) {
+                Ok(field) => {
+                    fields.push(field);
+                    index += 1;
+                }
+                Err(e) => {
+                    self.errors.push(e);
+                    self.recover_to_next_field(start);
+                }
+            }
+        }
+
+        let closing_comments = self.take_comments_before(self.position);
+        // A missing `}` is reported but keeps the fields parsed so far
+        if let Err(e) = self.expect(Token::RightBrace) {
+            self.errors.push(e);
+        }
+
+        Ok(Resource {
+            name,
+            package: None,
+            fields,
+            name_span,
+            trivia: Trivia {
+                leading,
+                trailing,
+                blank_line_before,
+            },
+            closing_comments,
+            file: None,
+        })
+    }
+
+    fn parse_service(&mut self) -> Result<Service, Diagnostic> {
+        let blank_line_before = self.blank_line_before_node();
+        let leading = self.take_comments_before(self.position);
+
+        self.expect(Token::Service)?;
+
+        let name_span = self.current_span();
+        let name = match self.current_token().clone() {
+            Token::Identifier(id) if !id.contains('.') => {
+                self.advance();
+                id
+            }
+            _ => return Err(self.unexpected("service name")),
+        };
+
+        self.expect(Token::LeftBrace)?;
+        let trailing = self.take_trailing_comment();
+
+        let mut endpoints = Vec::new();
+        while !matches!(self.current_token(), Token::RightBrace | Token::Resource | Token::Service | Token::Eof) {
+            let start = self.position;
+            match self.parse_endpoint(!endpoints.is_empty()) {
+                Ok(endpoint) => endpoints.push(endpoint),
+                Err(e) => {
+                    self.errors.push(e);
+                    self.recover_to_next_field(start);
+                }
+            }
+        }
+
+        let closing_comments = self.take_comments_before(self.position);
+        if let Err(e) = self.expect(Token::RightBrace) {
+            self.errors.push(e);
+        }
+
+        Ok(Service {
+            name,
+            package: None,
+            endpoints,
+            name_span,
+            trivia: Trivia {
+                leading,
+                trailing,
+                blank_line_before,
+            },
+            closing_comments,
+            file: None,
+        })
+    }
+
+    /// `method "path" [Request] [-> Response]` on one line, a `;` may end it
+    fn parse_endpoint(&mut self, after_endpoint: bool) -> Result<Endpoint, Diagnostic> {
+        let blank_line_before = after_endpoint && self.blank_line_before_node();
+        let leading = self.take_comments_before(self.position);
+
+        let span = self.current_span();
+        let method = match self.current_token() {
+            Token::Identifier(id) => HttpMethod::from_keyword(id),
+            _ => None,
+        }
+        .ok_or_else(|| self.unexpected("HTTP method (get, post, put, patch, delete)"))?;
+        self.advance();
+
+        let path = match self.current_token().clone() {
+            Token::StringLiteral(path) => {
+                self.advance();
+                path
+            }
+            _ => return Err(self.unexpected("endpoint path")),
+        };
+
+        // The request type has to be on the endpoint's line, the next line
+        // starts with the next endpoint's method
+        let line = span.line;
+        let mut request = None;
+        let mut request_span = Span::default();
+        if self.line_at(self.position) == line && self.at_type() {
+            request = Some(self.parse_type()?);
+            request_span = self.span_at(self.position - 1);
+        }
+
+        let mut response = None;
+        let mut response_span = Span::default();
+        if self.current_token() == &Token::Arrow {
+            self.advance();
+            response = Some(self.parse_type()?);
+            response_span = self.span_at(self.position - 1);
+        }
+        if self.current_token() == &Token::Semicolon {
+            self.advance();
+        }
+
+        let trailing = self.take_trailing_comment();
+
+        Ok(Endpoint {
+            method,
+            path,
+            request,
+            response,
+            trivia: Trivia {
+                leading,
+                trailing,
+                blank_line_before,
+            },
+            span,
+            request_span,
+            response_span,
+        })
+    }
+
+    /// Whether the current token can start a type
+    fn at_type(&self) -> bool {
+        matches!(
+            self.current_token(),
+            Token::String | Token::Number | Token::Bool | Token::List | Token::Identifier(_)
+        )
+    }
+
+    fn parse_field(&mut self, index: usize) -> Result<Field, Diagnostic> {
+        let blank_line_before = index > 0 && self.blank_line_before_node();
+        let mut leading = self.take_comments_before(self.position);
+
+        let mut nullable = false;
+        let mut optional = false;
+        let mut default = None;
+
+        // Parse attributes
+        self.parse_attributes(&mut nullable, &mut optional, &mut default)?;
+
+        // Parse type
+        let field_type = self.parse_type()?;
+        let type_span = self.span_at(self.position - 1);
+
+        // Attributes are also accepted after the type, e.g. `number default(10) interval`
+        self.parse_attributes(&mut nullable, &mut optional, &mut default)?;
+
+        // Parse identifier
+        let name_span = self.current_span();
+        let name = match self.current_token().clone() {
+            Token::Identifier(id) if !id.contains('.') => {
+                self.advance();
+                id
+            }
+            _ => return Err(self.unexpected("field name")),
+        };
+
+        // Comments in the middle of the field are kept with the leading ones
+        leading.extend(self.take_comments_before(self.position - 1));
+        let trailing = self.take_trailing_comment();
+
+        Ok(Field {
+            name,
+            field_type,
+            nullable,
+            optional,
+            default,
+            index,
+            trivia: Trivia {
+                leading,
+                trailing,
+                blank_line_before,
+            },
+            name_span,
+            type_span,
+        })
+    }
+
+    fn parse_attributes(
+        &mut self,
+        nullable: &mut bool,
+        optional: &mut bool,
+        default: &mut Option<DefaultValue>,
+    ) -> Result<(), Diagnostic> {
+        loop {
+            match self.current_token() {
+                Token::Nullable => {
+                    *nullable = true;
+                    self.advance();
+                }
+                Token::Optional => {
+                    *optional = true;
+                    self.advance();
+                }
+                Token::Default => {
+                    self.advance();
+                    self.expect(Token::LeftParen)?;
+                    let literal = self.parse_literal()?;
+                    self.expect(Token::RightParen)?;
+                    *default = Some(DefaultValue { value: literal });
+                }
+                _ => return Ok(()),
+            }
+        }
+    }
+
+    fn parse_type(&mut self) -> Result<ASTType, Diagnostic> {
+        match self.current_token() {
+            Token::String => {
+                self.advance();
+                Ok(ASTType::Primitive("string".to_string()))
+            }
+            Token::Number => {
+                self.advance();
+                Ok(ASTType::Primitive("number".to_string()))
+            }
+            Token::Bool => {
+                self.advance();
+                Ok(ASTType::Primitive("bool".to_string()))
+            }
+            Token::List => {
+                self.advance();
+                let inner_type = self.parse_type()?;
+                Ok(ASTType::List(Box::new(inner_type)))
+            }
+            Token::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(ASTType::Named(name))
+            }
+            _ => Err(self.unexpected("type")),
+        }
+    }
+
+    fn parse_literal(&mut self) -> Result<Literal, Diagnostic> {
+        match self.current_token() {
+            Token::StringLiteral(s) => {
+                let s = s.clone();
+                self.advance();
+                Ok(Literal::String(s))
+            }
+            Token::NumberLiteral(n) => {
+                let n = *n;
+                self.advance();
+                Ok(Literal::Number(n))
+            }
+            Token::True => {
+                self.advance();
+                Ok(Literal::Bool(true))
+            }
+            Token::False => {
+                self.advance();
+                Ok(Literal::Bool(false))
+            }
+            _ => Err(self.unexpected("literal")),
+        }
+    }
+}
+
+// ============================================================================
+// COMPILER
+// ============================================================================
+
+#[allow(dead_code)]
+pub struct Compiler {
+    program: Program,
+}
+
+impl Compiler {
+    pub fn new(program: Program) -> Result<Self, CompileError> {
+        let errors = Self::validate(&program);
+        if !errors.is_empty() {
+            return Err(CompileError::new(errors));
+        }
+
+        Ok(Compiler { program })
+    }
+
+    /// Run the AST validations, returning every problem found
+    pub fn validate(program: &Program) -> Vec<Diagnostic> {
+        let mut errors = Vec::new();
+
+        // Validate uniqueness of resource names within each package, across
+        // files when several were merged
+        let mut first_definitions: std::collections::HashMap<String, &Resource> = std::collections::HashMap::new();
+        for resource in &program.resources {
+            match first_definitions.get(&resource.qualified_name()) {
+                Some(first) => errors.push(
+                    Diagnostic::error(DiagnosticKind::DuplicateResource {
+                        name: resource.qualified_name(),
+                    })
+                    .with_span(resource.name_span)
+                    .in_file(resource.file.as_ref())
+                    .with_note(format!("`{}` first defined at {}", first.name, Self::location(first))),
+                ),
+                None => {
+                    first_definitions.insert(resource.qualified_name(), resource);
+                }
+            }
+        }
+
+        // Validate uniqueness of field names within each resource
+        for resource in &program.resources {
+            let mut field_names = std::collections::HashSet::new();
+            for field in &resource.fields {
+                if !field_names.insert(field.name.clone()) {
+                    errors.push(
+                        Diagnostic::error(DiagnosticKind::DuplicateField {
+                            resource: resource.name.clone(),
+                            field: field.name.clone(),
+                        })
+                        .with_span(field.name_span)
+                        .in_file(resource.file.as_ref()),
+                    );
+                }
+            }
+        }
+
+        // Validate services: names unique per package, endpoints well-formed
+        // and unique across services, as they are all served together
+        let mut service_names = std::collections::HashSet::new();
+        let mut routes = HashMap::new();
+        for service in &program.services {
+            if !service_names.insert(service.qualified_name()) {
+                errors.push(
+                    Diagnostic::error(DiagnosticKind::DuplicateService {
+                        name: service.qualified_name(),
+                    })
+                    .with_span(service.name_span)
+                    .in_file(service.file.as_ref()),
+                );
+            }
+
+            for endpoint in &service.endpoints {
+                let problem = Self::path_problem(&endpoint.path).or_else(|| {
+                    (endpoint.request.is_some() && !endpoint.method.has_body())
+                        .then(|| format!("{} requests cannot have a body", endpoint.method))
+                });
+                if let Some(reason) = problem {
+                    errors.push(
+                        Diagnostic::error(DiagnosticKind::InvalidEndpoint {
+                            service: service.name.clone(),
+                            endpoint: endpoint.describe(),
+                            reason,
+                        })
+                        .with_span(endpoint.span)
+                        .in_file(service.file.as_ref()),
+                    );
+                }
+
+                // `/users/:id` and `/users/:key` match the same requests
+                let route: Vec<&str> = endpoint
+                    .path
+                    .split('/')
+                    .map(|segment| if segment.starts_with(':') { ":" } else { segment })
+                    .collect();
+                if let Some(first) = routes.insert((endpoint.method, route), service.qualified_name()) {
+                    let mut diagnostic = Diagnostic::error(DiagnosticKind::DuplicateEndpoint {
+                        service: service.name.clone(),
+                        endpoint: endpoint.describe(),
+                    })
+                    .with_span(endpoint.span)
+                    .in_file(service.file.as_ref());
+                    if first != service.qualified_name() {
+                        diagnostic = diagnostic.with_note(format!("also declared by service {}", first));
+                    }
+                    errors.push(diagnostic);
+                }
+            }
+        }
+
+        errors
+    }
+
+    /// Why an endpoint path is malformed, if it is
+    fn path_problem(path: &str) -> Option<String> {
+        let Some(rest) = path.strip_prefix('/') else {
+            return Some("paths must start with '/'".to_string());
+        };
+        let mut params = Vec::new();
+        for segment in rest.split('/') {
+            if let Some(param) = segment.strip_prefix(':') {
+                let valid = param.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+                    && param.chars().all(|c| c.is_alphanumeric() || c == '_');
+                if !valid {
+                    return Some(format!("invalid path parameter ':{}'", param));
+                }
+                if params.contains(&param) {
+                    return Some(format!("path parameter ':{}' appears twice", param));
+                }
+                params.push(param);
+            } else if segment.contains(|c: char| c.is_whitespace() || c == '?' || c == '#' || c == ':') {
+                return Some(format!("invalid path segment '{}'", segment));
+            }
+        }
+        None
+    }
+
+    /// `file:line:column` of a resource name, or just `line:column` when
+    /// the schema came from a single source
+    fn location(resource: &Resource) -> String {
+        let span = resource.name_span;
+        match &resource.file {
+            Some(file) => format!("{}:{}:{}", file.display(), span.line, span.column),
+            None => format!("{}:{}", span.line, span.column),
+        }
+    }
+
+    pub fn compile(&self) -> Result<CompiledOutput, CompileError> {
+        // 1. Validate AST (already done in new())
+
+        // 2. Type resolution
+        let resolver = TypeResolver::new(&self.program)?;
+        let ir = resolver.resolve(self.program.clone())?;
+
+        // 3. Cycle detection
+        Compiler::detect_cycles(&self.program, &ir)?;
+
+        // 4. Code generation
+        let code_generator = CodeGenerator::new(ir.clone());
+        let generated_code = code_generator.generate();
+
+        // 5. Return compiled output with IR and generated code
+        Ok(CompiledOutput {
+            ir,
+            generated_code,
+        })
+    }
+
+    /// Report resource reference loops in `ir`, pointing at the field of
+    /// `program` that starts each one; also run on programs that failed
+    /// validation, so it doesn't need a `Compiler`
+    fn detect_cycles(program: &Program, ir: &IRProgram) -> Result<(), CompileError> {
+        let cycle_detector = CycleDetector::build(ir)?;
+        if let Err(mut error) = cycle_detector.detect() {
+            for diagnostic in &mut error.diagnostics {
+                if let DiagnosticKind::Cycle { path } = &diagnostic.kind {
+                    diagnostic.span = Compiler::cycle_span(program, path);
+                    diagnostic.file = program
+                        .resources
+                        .iter()
+                        .find(|r| r.qualified_name() == path[0])
+                        .and_then(|r| r.file.clone());
+                }
+            }
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Span of the type of the field that starts a cycle, e.g. `B` in
+    /// `resource A { B b }` for the cycle A → B → A
+    fn cycle_span(program: &Program, path: &[String]) -> Option<Span> {
+        let resource = program.resources.iter().find(|r| r.qualified_name() == path[0])?;
+        let next_name = path.get(1)?;
+        let next = program.resources.iter().position(|r| &r.qualified_name() == next_name);
+        let field = resource.fields.iter().find(|f| match innermost_ast_type(&f.field_type) {
+            ASTType::Named(n) => program.resolve_name(resource.package.as_deref(), n) == next,
+            _ => false,
+        });
+        Some(field.map(|f| f.type_span).unwrap_or(resource.name_span))
+    }
+}
+
+// ============================================================================
+// TYPE RESOLVER
+// ============================================================================
+
+pub struct TypeResolver {
+    resource_map: std::collections::HashMap<String, usize>,
+}
+
+impl TypeResolver {
+    /// Build a type resolver from an AST program
+    ///
+    /// Creates a mapping of qualified resource names to their indices for
+    /// fast lookup during type resolution.
+    pub fn new(program: &Program) -> Result<Self, CompileError> {
+        let resolver = TypeResolver::first_declared(program);
+        if let Some(resource) = program.resources.iter().enumerate().find_map(|(index, resource)| {
+            (resolver.resource_map[&resource.qualified_name()] != index).then_some(resource)
+        }) {
+            // This shouldn't happen because Compiler::new validates uniqueness
+            return Err(Diagnostic::error(DiagnosticKind::DuplicateResource { name: resource.qualified_name() })
+                .with_span(resource.name_span)
+                .in_file(resource.file.as_ref())
+                .into());
+        }
+        Ok(resolver)
+    }
+
+    /// Resolver for a program that may declare a resource twice, names
+    /// refer to the first declaration
+    fn first_declared(program: &Program) -> Self {
+        let mut resource_map = std::collections::HashMap::new();
+        for (index, resource) in program.resources.iter().enumerate() {
+            resource_map.entry(resource.qualified_name()).or_insert(index);
+        }
+        TypeResolver { resource_map }
+    }
+
+    /// Resolve a single AST type to an IR type
+    ///
+    /// Converts:
+    /// - ASTType::Primitive(s) → IRType::Primitive(s)
+    /// - ASTType::Named(s) → IRType::ResourceRef(index) or error, plain
+    ///   names are looked up in `package` first, see `Program::resolve_name`
+    /// - ASTType::List(inner) → IRType::List(resolved_inner)
+    fn resolve_type(&self, ast_type: &ASTType, package: Option<&str>) -> Result<IRType, DiagnosticKind> {
+        match ast_type {
+            ASTType::Primitive(name) => {
+                // Validate it's one of the three primitives
+                match name.as_str() {
+                    "string" | "number" | "bool" => Ok(IRType::Primitive(name.clone())),
+                    _ => Err(DiagnosticKind::UndefinedType { name: name.clone() }),
+                }
+            }
+            ASTType::Named(name) => {
+                // Look up the resource name, in the same package first
+                let in_package = match package {
+                    Some(package) if !name.contains('.') => self.resource_map.get(&qualify(Some(package), name)),
+                    _ => None,
+                };
+                match in_package.or_else(|| self.resource_map.get(name)) {
+                    Some(&index) => Ok(IRType::ResourceRef(index)),
+                    None => Err(DiagnosticKind::UndefinedType { name: name.clone() }),
+                }
+            }
+            ASTType::List(inner) => {
+                // Recursively resolve the inner type
+                let resolved_inner = self.resolve_type(inner, package)?;
+                Ok(IRType::List(Box::new(resolved_inner)))
+            }
+        }
+    }
+
+    /// Transform an entire AST program to an IR program
+    ///
+    /// Converts all field types from AST to IR, preserving all field attributes.
+    /// Every unresolved type is reported at the field's type.
+    pub fn resolve(&self, program: Program) -> Result<IRProgram, CompileError> {
+        let (ir, errors) = self.resolve_partial(program);
+        if !errors.is_empty() {
+            return Err(CompileError::new(errors));
+        }
+        Ok(ir)
+    }
+
+    /// Resolve what can be, leaving out fields and bodies whose type is
+    /// undefined, and the errors for them
+    fn resolve_partial(&self, program: Program) -> (IRProgram, Vec<Diagnostic>) {
+        let mut ir_resources = Vec::new();
+        let mut errors = Vec::new();
+
+        for ast_resource in program.resources {
+            let mut ir_fields = Vec::new();
+
+            for ast_field in ast_resource.fields {
+                let resolved_type = match self.resolve_type(&ast_field.field_type, ast_resource.package.as_deref()) {
+                    Ok(resolved_type) => resolved_type,
+                    Err(kind) => {
+                        errors.push(
+                            Diagnostic::error(kind)
+                                .with_span(ast_field.type_span)
+                                .in_file(ast_resource.file.as_ref()),
+                        );
+                        continue;
+                    }
+                };
+                ir_fields.push(IRField {
+                    name: ast_field.name,
+                    field_type: resolved_type,
+                    nullable: ast_field.nullable,
+                    optional: ast_field.optional,
+                    default: ast_field.default,
+                    index: ast_field.index,
+                });
+            }
+
+            ir_resources.push(IRResource {
+                name: ast_resource.name,
+                package: ast_resource.package,
+                fields: ir_fields,
+                file: ast_resource.file,
+            });
+        }
+
+        let mut ir_services = Vec::new();
+        for service in program.services {
+            // Bodies are whole resources, see `resolve_body`
+            let mut resolve_body = |endpoint: &Endpoint, body: &Option<ASTType>, span: Span| {
+                let body = body.as_ref()?;
+                match self.resolve_body(body, &service, endpoint) {
+                    Ok(idx) => Some(idx),
+                    Err(kind) => {
+                        errors.push(Diagnostic::error(kind).with_span(span).in_file(service.file.as_ref()));
+                        None
+                    }
+                }
+            };
+            let endpoints = service
+                .endpoints
+                .iter()
+                .map(|endpoint| IREndpoint {
+                    method: endpoint.method,
+                    path: endpoint.path.clone(),
+                    request: resolve_body(endpoint, &endpoint.request, endpoint.request_span),
+                    response: resolve_body(endpoint, &endpoint.response, endpoint.response_span),
+                })
+                .collect();
+            ir_services.push(IRService {
+                name: service.name,
+                package: service.package,
+                endpoints,
+                file: service.file,
+            });
+        }
+
+        let ir = IRProgram {
+            resources: ir_resources,
+            services: ir_services,
+        };
+        (ir, errors)
+    }
+
+    /// Resolve a request or response body, which must be a resource
+    fn resolve_body(&self, ast_type: &ASTType, service: &Service, endpoint: &Endpoint) -> Result<usize, DiagnosticKind> {
+        match self.resolve_type(ast_type, service.package.as_deref())? {
+            IRType::ResourceRef(idx) => Ok(idx),
+            _ => Err(DiagnosticKind::InvalidEndpoint {
+                service: service.name.clone(),
+                endpoint: endpoint.describe(),
+                reason: format!("body `{}` is not a resource", ast_type_to_source(ast_type)),
+            }),
+        }
+    }
+}
+
+// ============================================================================
+// CYCLE DETECTOR
+// ============================================================================
+
+/// A reference from one resource to another through one of its fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct DependencyEdge {
+    /// Index of the referenced resource
+    pub to: usize,
+    /// Name of the field holding the reference
+    pub field: String,
+    /// Whether the reference is wrapped in one or more lists
+    pub through_list: bool,
+}
+
+pub struct CycleDetector {
+    graph: Vec<Vec<DependencyEdge>>,
+    resource_names: Vec<String>,
+}
+
+impl CycleDetector {
+    /// Build a dependency graph from the IR program
+    ///
+    /// Creates an adjacency list where each node represents a resource
+    /// and edges represent references to other resources.
+    pub fn build(ir: &IRProgram) -> Result<Self, CompileError> {
+        Ok(Self::new(ir))
+    }
+
+    /// Infallible part of `build`, for callers that only walk the graph
+    fn new(ir: &IRProgram) -> Self {
+        let mut graph = vec![Vec::new(); ir.resources.len()];
+
+        // For each resource and its fields, collect all resource references
+        for (res_idx, resource) in ir.resources.iter().enumerate() {
+            for field in &resource.fields {
+                Self::collect_refs(res_idx, &field.name, &field.field_type, false, &mut graph);
+            }
+        }
+
+        // Extract resource names for error reporting
+        let resource_names: Vec<String> = ir.resources.iter().map(|r| r.qualified_name()).collect();
+
+        CycleDetector {
+            graph,
+            resource_names,
+        }
+    }
+
+    /// Names of the resources, indexed like the graph nodes
+    pub fn resource_names(&self) -> &[String] {
+        &self.resource_names
+    }
+
+    /// Outgoing edges of the resource at `idx`, in field order
+    pub fn edges(&self, idx: usize) -> &[DependencyEdge] {
+        &self.graph[idx]
+    }
+
+    /// Resources referenced by the resource at `idx`, without duplicates
+    pub fn references(&self, idx: usize) -> Vec<usize> {
+        let mut refs = Vec::new();
+        for edge in &self.graph[idx] {
+            if !refs.contains(&edge.to) {
+                refs.push(edge.to);
+            }
+        }
+        refs
+    }
+
+    /// Resources that reference the resource at `idx`, in declaration order
+    pub fn referenced_by(&self, idx: usize) -> Vec<usize> {
+        (0..self.graph.len())
+            .filter(|&from| self.graph[from].iter().any(|edge| edge.to == idx))
+            .collect()
+    }
+
+    /// Helper: extract all resource references from a type recursively
+    ///
+    /// - Primitive types: no references
+    /// - ResourceRef: add edge from current resource to referenced resource
+    /// - List: recursively process inner type
+    fn collect_refs(
+        from_idx: usize,
+        field: &str,
+        ir_type: &IRType,
+        through_list: bool,
+        graph: &mut [Vec<DependencyEdge>],
+    ) {
+        match ir_type {
+            IRType::Primitive(_) => {
+                // No resource references in primitive types
+            }
+            IRType::ResourceRef(to_idx) => {
+                // Add edge: from_idx → to_idx
+                graph[from_idx].push(DependencyEdge {
+                    to: *to_idx,
+                    field: field.to_string(),
+                    through_list,
+                });
+            }
+            IRType::List(inner) => {
+                // Recursively process list inner type
+                Self::collect_refs(from_idx, field, inner, true, graph);
+            }
+        }
+    }
+
+    /// Render the graph in Graphviz DOT format
+    ///
+    /// Edges are labelled with the field name, references through a list
+    /// are dashed and suffixed with `[]`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        out.push_str("digraph resources {\n");
+        out.push_str("    node [shape=box];\n");
+        for name in &self.resource_names {
+            out.push_str(&format!("    \"{}\";\n", name));
+        }
+        for (from, edges) in self.graph.iter().enumerate() {
+            for edge in edges {
+                let style = if edge.through_list { ", style=dashed" } else { "" };
+                out.push_str(&format!(
+                    "    \"{}\" -> \"{}\" [label=\"{}\"{}];\n",
+                    self.resource_names[from],
+                    self.resource_names[edge.to],
+                    Self::edge_label(edge),
+                    style
+                ));
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    /// Render the graph as a Mermaid flowchart
+    ///
+    /// Nodes get ids like `n0` and the resource name as a quoted label, since
+    /// Mermaid ids can't contain the dots of package-qualified names.
+    /// References through a list use a dotted arrow.
+    pub fn to_mermaid(&self) -> String {
+        let mut out = String::new();
+        out.push_str("flowchart LR\n");
+        for (i, name) in self.resource_names.iter().enumerate() {
+            out.push_str(&format!("    n{}[\"{}\"]\n", i, name));
+        }
+        for (from, edges) in self.graph.iter().enumerate() {
+            for edge in edges {
+                let arrow = if edge.through_list { "-.->" } else { "-->" };
+                out.push_str(&format!("    n{} {}|\"{}\"| n{}\n", from, arrow, Self::edge_label(edge), edge.to));
+            }
+        }
+        out
+    }
+
+    fn edge_label(edge: &DependencyEdge) -> String {
+        if edge.through_list {
+            format!("{}[]", edge.field)
+        } else {
+            edge.field.clone()
+        }
+    }
+
+    /// Detect cycles in the resource dependency graph
+    ///
+    /// Uses depth-first search with recursion stack tracking.
+    /// If a node is encountered that's already in the current recursion stack,
+    /// a cycle has been found.
+    ///
+    /// Every cycle found is reported.
+    pub fn detect(&self) -> Result<(), CompileError> {
+        let n = self.graph.len();
+        let mut visited = vec![false; n];
+        let mut rec_stack = vec![false; n];
+        let mut path = Vec::new();
+        let mut errors = Vec::new();
+
+        for i in 0..n {
+            if !visited[i] {
+                self.dfs(i, &mut visited, &mut rec_stack, &mut path, &mut errors);
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(CompileError::new(errors))
+        }
+    }
+
+    /// Depth-first search for cycle detection
+    ///
+    /// Maintains:
+    /// - visited: tracks nodes we've processed
+    /// - rec_stack: tracks nodes in the current path (to detect back edges)
+    /// - path: tracks the current traversal path for error messages
+    /// - errors: cycles found so far, without duplicates
+    fn dfs(
+        &self,
+        node: usize,
+        visited: &mut Vec<bool>,
+        rec_stack: &mut Vec<bool>,
+        path: &mut Vec<usize>,
+        errors: &mut Vec<Diagnostic>,
+    ) {
+        // Mark as visited and in current recursion path
+        visited[node] = true;
+        rec_stack[node] = true;
+        path.push(node);
+
+        // Visit all neighbors
+        for neighbor in self.graph[node].iter().map(|edge| edge.to) {
+            if !visited[neighbor] {
+                // Unvisited neighbor: recurse
+                self.dfs(neighbor, visited, rec_stack, path, errors);
+            } else if rec_stack[neighbor] {
+                // Neighbor is in current path: found a cycle!
+                // Extract the cycle from the path
+                let cycle_start = path.iter().position(|&n| n == neighbor).unwrap();
+                let cycle_path = &path[cycle_start..];
+
+                // Convert node indices to names, closing the loop: A → B → C → A
+                let mut cycle_names: Vec<String> = cycle_path
+                    .iter()
+                    .map(|&idx| self.resource_names[idx].clone())
+                    .collect();
+                cycle_names.push(self.resource_names[neighbor].clone());
+
+                let error = Diagnostic::error(DiagnosticKind::Cycle { path: cycle_names });
+                if !errors.contains(&error) {
+                    errors.push(error);
+                }
+            }
+        }
+
+        // Backtrack: remove from current path
+        path.pop();
+        rec_stack[node] = false;
+    }
+}
+
+#[derive(Debug)]
+pub struct CompiledOutput {
+    pub ir: IRProgram,
+    pub generated_code: GeneratedCode,
+}
+
+impl Default for CompiledOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CompiledOutput {
+    pub fn new() -> Self {
+        CompiledOutput {
+            ir: IRProgram {
+                resources: Vec::new(),
+                services: Vec::new(),
+            },
+            generated_code: GeneratedCode {
+                typescript_client: String::new(),
+                typescript_server: String::new(),
+            },
+        }
+    }
+}
+
+// ============================================================================
+// LINTER
+// ============================================================================
+
+/// Style and risk checks run by `previouscc lint`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LintRule {
+    /// Field names should be camelCase
+    FieldCamelCase,
+    /// Resources that no other resource references
+    UnusedResource,
+    /// `default` on an `optional` field, the default is never applied on the wire
+    DefaultOnOptional,
+    /// Resources with more fields than `LintConfig::max_fields`
+    WideResource,
+    /// Lists of lists
+    NestedList,
+    /// Field names that are reserved words or generated members in a target language
+    ReservedWord,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 6] = [
+        LintRule::FieldCamelCase,
+        LintRule::UnusedResource,
+        LintRule::DefaultOnOptional,
+        LintRule::WideResource,
+        LintRule::NestedList,
+        LintRule::ReservedWord,
+    ];
+
+    /// Name used on the command line and in reports
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::FieldCamelCase => "field-camel-case",
+            LintRule::UnusedResource => "unused-resource",
+            LintRule::DefaultOnOptional => "default-on-optional",
+            LintRule::WideResource => "wide-resource",
+            LintRule::NestedList => "nested-list",
+            LintRule::ReservedWord => "reserved-word",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<LintRule> {
+        LintRule::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+
+    /// Level used when the configuration does not mention the rule
+    ///
+    /// Root resources are never referenced by design, so `unused-resource`
+    /// is opt-in.
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            LintRule::UnusedResource => LintLevel::Allow,
+            LintRule::ReservedWord => LintLevel::Deny,
+            _ => LintLevel::Warn,
+        }
+    }
+}
+
+/// Whether a rule is disabled, reported as a warning or reported as an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// Per-rule lint configuration
+#[derive(Debug, Clone)]
+pub struct LintConfig {
+    /// Rules not listed use `LintRule::default_level`
+    pub levels: std::collections::HashMap<LintRule, LintLevel>,
+    /// Field count above which `wide-resource` reports a resource
+    pub max_fields: usize,
+}
+
+impl Default for LintConfig {
+    fn default() -> Self {
+        LintConfig {
+            levels: std::collections::HashMap::new(),
+            max_fields: 32,
+        }
+    }
+}
+
+impl LintConfig {
+    pub fn set_level(&mut self, rule: LintRule, level: LintLevel) {
+        self.levels.insert(rule, level);
+    }
+
+    pub fn level(&self, rule: LintRule) -> LintLevel {
+        self.levels.get(&rule).copied().unwrap_or_else(|| rule.default_level())
+    }
+}
+
+/// A problem reported by the linter
+#[derive(Debug, Clone, PartialEq)]
+pub struct LintWarning {
+    pub rule: LintRule,
+    pub severity: Severity,
+    pub resource: String,
+    pub field: Option<String>,
+    pub message: String,
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = match &self.field {
+            Some(field) => format!("{}.{}", self.resource, field),
+            None => self.resource.clone(),
+        };
+        write!(f, "{}[{}]: {}: {}", self.severity, self.rule.name(), location, self.message)
+    }
+}
+
+const TYPESCRIPT_RESERVED_WORDS: &[&str] = &[
+    "break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do", "else",
+    "enum", "export", "extends", "false", "finally", "for", "function", "if", "implements", "import", "in",
+    "instanceof", "interface", "let", "new", "null", "package", "private", "protected", "public", "return",
+    "static", "super", "switch", "this", "throw", "true", "try", "typeof", "var", "void", "while", "with",
+    "yield", "await",
+];
+
+/// Members of the generated TypeScript classes a field would shadow
+const TYPESCRIPT_GENERATED_MEMBERS: &[&str] = &[
+    "constructor", "decode", "encode", "encodeFields", "toJSON", "reader", "data",
+];
+
+const RUST_RESERVED_WORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+];
+
+/// `userId` as a Rust field name, `user_id`
+pub fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// A name usable as a Rust identifier: keywords are raw (`r#type`), except
+/// those that cannot be, which get a trailing underscore (`self_`)
+fn rust_ident(name: &str) -> String {
+    if matches!(name, "self" | "super" | "crate") {
+        format!("{}_", name)
+    } else if RUST_RESERVED_WORDS.contains(&name) {
+        format!("r#{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Run the enabled lint rules over a resolved program
+pub fn lint(ir: &IRProgram, config: &LintConfig) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    let mut report = |rule: LintRule, resource: &str, field: Option<&str>, message: String| {
+        let severity = match config.level(rule) {
+            LintLevel::Allow => return,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+        warnings.push(LintWarning {
+            rule,
+            severity,
+            resource: resource.to_string(),
+            field: field.map(|f| f.to_string()),
+            message,
+        });
+    };
+
+    let graph = CycleDetector::new(ir);
+    let referenced: Vec<bool> = (0..ir.resources.len())
+        .map(|idx| {
+            !graph.referenced_by(idx).is_empty()
+                || ir
+                    .services
+                    .iter()
+                    .flat_map(|s| s.endpoints.iter())
+                    .any(|e| e.request == Some(idx) || e.response == Some(idx))
+        })
+        .collect();
+
+    for (idx, resource) in ir.resources.iter().enumerate() {
+        if !referenced[idx] {
+            report(
+                LintRule::UnusedResource,
+                &resource.name,
+                None,
+                "resource is never referenced by another resource or a service".to_string(),
+            );
+        }
+
+        if resource.fields.len() > config.max_fields {
+            report(
+                LintRule::WideResource,
+                &resource.name,
+                None,
+                format!("resource has {} fields, more than {}", resource.fields.len(), config.max_fields),
+            );
+        }
+
+        for field in &resource.fields {
+            if !is_camel_case(&field.name) {
+                report(
+                    LintRule::FieldCamelCase,
+                    &resource.name,
+                    Some(&field.name),
+                    format!("field name should be camelCase, e.g. `{}`", to_camel_case(&field.name)),
+                );
+            }
+
+            if field.optional && field.default.is_some() {
+                report(
+                    LintRule::DefaultOnOptional,
+                    &resource.name,
+                    Some(&field.name),
+                    "`default` on an optional field is never applied, absent values stay absent".to_string(),
+                );
+            }
+
+            if let IRType::List(inner) = &field.field_type {
+                if matches!(inner.as_ref(), IRType::List(_)) {
+                    report(
+                        LintRule::NestedList,
+                        &resource.name,
+                        Some(&field.name),
+                        "list of lists, consider a resource for the inner list".to_string(),
+                    );
+                }
+            }
+
+            let name = field.name.as_str();
+            let reserved = if TYPESCRIPT_RESERVED_WORDS.contains(&name) {
+                Some("is a reserved word in TypeScript")
+            } else if TYPESCRIPT_GENERATED_MEMBERS.contains(&name) {
+                Some("shadows a member of the generated TypeScript classes")
+            } else if RUST_RESERVED_WORDS.contains(&name) {
+                Some("is a reserved word in Rust")
+            } else {
+                None
+            };
+            if let Some(reason) = reserved {
+                report(
+                    LintRule::ReservedWord,
+                    &resource.name,
+                    Some(&field.name),
+                    format!("`{}` {}", field.name, reason),
+                );
+            }
+        }
+    }
+
+    warnings
+}
+
+fn is_camel_case(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_lowercase()) && chars.all(|c| c.is_ascii_alphanumeric())
+}
+
+fn to_camel_case(name: &str) -> String {
+    let mut result = String::new();
+    let mut upper_next = false;
+    for ch in name.chars() {
+        if ch == '_' {
+            upper_next = !result.is_empty();
+        } else if result.is_empty() {
+            result.extend(ch.to_lowercase());
+        } else if upper_next {
+            result.extend(ch.to_uppercase());
+            upper_next = false;
+        } else {
+            result.push(ch);
+        }
+    }
+    result
+}
+
+// ============================================================================
+// DOCUMENTATION GENERATOR
+// ============================================================================
+
+/// Output format for the generated reference documentation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocsFormat {
+    /// One Markdown page per resource plus an `index.md`
+    Markdown,
+    /// A single `index.html` with one section per resource
+    Html,
+}
+
+/// A generated documentation file, relative to the output directory
+#[derive(Debug, Clone)]
+pub struct DocFile {
+    pub path: String,
+    pub contents: String,
+}
+
+/// Generator for browsable schema reference documentation
+///
+/// Back-links ("Used by") come from the resource graph built by
+/// `CycleDetector::build`.
+pub struct DocsGenerator<'a> {
+    ir: &'a IRProgram,
+    graph: CycleDetector,
+    format: DocsFormat,
+}
+
+impl<'a> DocsGenerator<'a> {
+    pub fn new(ir: &'a IRProgram, format: DocsFormat) -> Result<Self, CompileError> {
+        let graph = CycleDetector::build(ir)?;
+        Ok(DocsGenerator { ir, graph, format })
+    }
+
+    /// Generate all documentation files
+    pub fn generate(&self) -> Vec<DocFile> {
+        match self.format {
+            DocsFormat::Markdown => {
+                let mut files = vec![DocFile {
+                    path: "index.md".to_string(),
+                    contents: self.markdown_index(),
+                }];
+                for (idx, resource) in self.ir.resources.iter().enumerate() {
+                    files.push(DocFile {
+                        path: format!("{}.md", resource.qualified_name()),
+                        contents: self.markdown_resource(idx),
+                    });
+                }
+                files
+            }
+            DocsFormat::Html => vec![DocFile {
+                path: "index.html".to_string(),
+                contents: self.html_document(),
+            }],
+        }
+    }
+
+    /// Fields of `from` that reference the resource at `to`
+    fn referencing_fields(&self, from: usize, to: usize) -> Vec<&str> {
+        let mut fields = Vec::new();
+        for edge in self.graph.edges(from).iter().filter(|edge| edge.to == to) {
+            if !fields.contains(&edge.field.as_str()) {
+                fields.push(edge.field.as_str());
+            }
+        }
+        fields
+    }
+
+    fn field_attributes(field: &IRField) -> (&'static str, &'static str, String) {
+        let optional = if field.optional { "yes" } else { "" };
+        let nullable = if field.nullable { "yes" } else { "" };
+        let default = field
+            .default
+            .as_ref()
+            .map(|d| literal_to_source(&d.value))
+            .unwrap_or_default();
+        (optional, nullable, default)
+    }
+
+    // ========================================================================
+    // Markdown
+    // ========================================================================
+
+    fn markdown_index(&self) -> String {
+        let mut doc = String::new();
+        doc.push_str("# Schema Reference\n\n");
+        doc.push_str("| Resource | Fields | Used by |\n");
+        doc.push_str("|----------|--------|---------|\n");
+        for (idx, resource) in self.ir.resources.iter().enumerate() {
+            doc.push_str(&format!(
+                "| [{}]({}.md) | {} | {} |\n",
+                resource.qualified_name(),
+                resource.qualified_name(),
+                resource.fields.len(),
+                self.graph.referenced_by(idx).len()
+            ));
+        }
+        doc
+    }
+
+    fn markdown_resource(&self, idx: usize) -> String {
+        let resource = &self.ir.resources[idx];
+        let mut doc = String::new();
+
+        doc.push_str(&format!("# {}\n\n", resource.qualified_name()));
+        doc.push_str("[Back to index](index.md)\n\n");
+
+        doc.push_str("## Fields\n\n");
+        doc.push_str("| Index | Name | Type | Optional | Nullable | Default |\n");
+        doc.push_str("|-------|------|------|----------|----------|---------|\n");
+        for field in &resource.fields {
+            let (optional, nullable, default) = Self::field_attributes(field);
+            doc.push_str(&format!(
+                "| {} | `{}` | {} | {} | {} | {} |\n",
+                field.index,
+                field.name,
+                self.markdown_type(&field.field_type),
+                optional,
+                nullable,
+                if default.is_empty() { String::new() } else { format!("`{}`", default.replace('|', "\\|")) }
+            ));
+        }
+
+        let references = self.graph.references(idx);
+        if !references.is_empty() {
+            doc.push_str("\n## References\n\n");
+            for to in references {
+                let name = self.ir.resources[to].qualified_name();
+                doc.push_str(&format!("- [{}]({}.md)\n", name, name));
+            }
+        }
+
+        doc.push_str("\n## Used by\n\n");
+        let used_by = self.graph.referenced_by(idx);
+        if used_by.is_empty() {
+            doc.push_str("Not referenced by any resource.\n");
+        }
+        for from in used_by {
+            let name = self.ir.resources[from].qualified_name();
+            let fields: Vec<String> = self
+                .referencing_fields(from, idx)
+                .iter()
+                .map(|f| format!("`{}`", f))
+                .collect();
+            doc.push_str(&format!("- [{}]({}.md) via {}\n", name, name, fields.join(", ")));
+        }
+
+        doc
+    }
+
+    fn markdown_type(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => format!("`{}`", p),
+            IRType::List(inner) => format!("`list` {}", self.markdown_type(inner)),
+            IRType::ResourceRef(idx) => {
+                let name = self.ir.resources[*idx].qualified_name();
+                format!("[{}]({}.md)", name, name)
+            }
+        }
+    }
+
+    // ========================================================================
+    // HTML
+    // ========================================================================
+
+    fn html_document(&self) -> String {
+        let mut doc = String::new();
+
+        doc.push_str("<!DOCTYPE html>\n<html>\n<head>\n");
+        doc.push_str("<meta charset=\"utf-8\">\n");
+        doc.push_str("<title>Schema Reference</title>\n");
+        doc.push_str("<style>\n");
+        doc.push_str("body { font-family: sans-serif; max-width: 960px; margin: 2em auto; }\n");
+        doc.push_str("table { border-collapse: collapse; width: 100%; }\n");
+        doc.push_str("th, td { border: 1px solid #ccc; padding: 4px 8px; text-align: left; }\n");
+        doc.push_str("</style>\n");
+        doc.push_str("</head>\n<body>\n");
+        doc.push_str("<h1>Schema Reference</h1>\n");
+
+        doc.push_str("<ul>\n");
+        for resource in &self.ir.resources {
+            doc.push_str(&format!("<li><a href=\"#{}\">{}</a></li>\n", resource.qualified_name(), resource.qualified_name()));
+        }
+        doc.push_str("</ul>\n");
+
+        for (idx, resource) in self.ir.resources.iter().enumerate() {
+            doc.push_str(&format!("<section id=\"{}\">\n", resource.qualified_name()));
+            doc.push_str(&format!("<h2>{}</h2>\n", resource.qualified_name()));
+            doc.push_str("<table>\n");
+            doc.push_str("<tr><th>Index</th><th>Name</th><th>Type</th><th>Optional</th><th>Nullable</th><th>Default</th></tr>\n");
+            for field in &resource.fields {
+                let (optional, nullable, default) = Self::field_attributes(field);
+                doc.push_str(&format!(
+                    "<tr><td>{}</td><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    field.index,
+                    field.name,
+                    self.html_type(&field.field_type),
+                    optional,
+                    nullable,
+                    if default.is_empty() { String::new() } else { format!("<code>{}</code>", html_escape(&default)) }
+                ));
+            }
+            doc.push_str("</table>\n");
+
+            let used_by = self.graph.referenced_by(idx);
+            doc.push_str("<p>Used by: ");
+            if used_by.is_empty() {
+                doc.push_str("none");
+            }
+            let links: Vec<String> = used_by
+                .iter()
+                .map(|&from| {
+                    let name = self.ir.resources[from].qualified_name();
+                    format!(
+                        "<a href=\"#{}\">{}</a> ({})",
+                        name,
+                        name,
+                        self.referencing_fields(from, idx).join(", ")
+                    )
+                })
+                .collect();
+            doc.push_str(&links.join(", "));
+            doc.push_str("</p>\n");
+            doc.push_str("</section>\n");
+        }
+
+        doc.push_str("</body>\n</html>\n");
+        doc
+    }
+
+    fn html_type(&self, ir_type: &IRType) -> String {
+        match ir_type {
+            IRType::Primitive(p) => format!("<code>{}</code>", p),
+            IRType::List(inner) => format!("<code>list</code> {}", self.html_type(inner)),
+            IRType::ResourceRef(idx) => {
+                let name = self.ir.resources[*idx].qualified_name();
+                format!("<a href=\"#{}\">{}</a>", name, name)
+            }
+        }
+    }
+}
+
+fn html_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Write generated documentation files into `output_dir`
+pub fn write_docs(files: &[DocFile], output_dir: &Path) -> Result<(), CompileError> {
+    fs::create_dir_all(output_dir).map_err(|e| io_error("create output directory", output_dir, e))?;
+
+    for file in files {
+        let path = output_dir.join(&file.path);
+        fs::write(&path, &file.contents).map_err(|e| io_error("write documentation file", &path, e))?;
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// FORMATTER
+// ============================================================================
+//
+// Canonical layout:
+// - one blank line between resources, 4-space indentation
+// - resources and services in declaration order, one endpoint per line
+// - attributes before the type, in the order `optional nullable default(..)`
+// - field names aligned in a column, trailing comments aligned after them
+// - single blank lines between fields are kept, longer runs are collapsed
+//
+
+/// Format schema source into the canonical layout
+pub fn format_schema(input: &str) -> Result<String, CompileError> {
+    let program = parse_schema(input)?;
+    Ok(format_program(&program))
+}
+
+/// Render an AST program back to source in the canonical layout
+pub fn format_program(program: &Program) -> String {
+    let mut code = String::new();
+
+    if let Some(package) = &program.package {
+        for comment in &package.trivia.leading {
+            code.push_str(&format!("//{}\n", comment));
+        }
+        code.push_str(&format!("package {}", package.name));
+        if let Some(comment) = &package.trivia.trailing {
+            code.push_str(&format!(" //{}", comment));
+        }
+        code.push('\n');
+    }
+
+    // Resources and services interleave as declared; programs built without
+    // source spans keep their resources first
+    let mut declarations: Vec<(Span, String)> = program
+        .resources
+        .iter()
+        .map(|resource| (resource.name_span, format_resource(resource)))
+        .chain(program.services.iter().map(|service| (service.name_span, format_service(service))))
+        .collect();
+    declarations.sort_by_key(|(span, _)| (span.line, span.column));
+
+    for (i, (_, declaration)) in declarations.iter().enumerate() {
+        if i > 0 || program.package.is_some() {
+            code.push('\n');
+        }
+        code.push_str(declaration);
+    }
+
+    if !program.trailing_comments.is_empty() {
+        if !program.resources.is_empty() || !program.services.is_empty() || program.package.is_some() {
+            code.push('\n');
+        }
+        for comment in &program.trailing_comments {
+            code.push_str(&format!("//{}\n", comment));
+        }
+    }
+
+    code
+}
+
+fn format_resource(resource: &Resource) -> String {
+    let mut code = String::new();
+
+    for comment in &resource.trivia.leading {
+        code.push_str(&format!("//{}\n", comment));
+    }
+
+    code.push_str(&format!("resource {} {{", resource.name));
+    if let Some(comment) = &resource.trivia.trailing {
+        code.push_str(&format!(" //{}", comment));
+    } else if resource.fields.is_empty() && resource.closing_comments.is_empty() {
+        code.push_str("}\n");
+        return code;
+    }
+    code.push('\n');
+
+    let prefixes: Vec<String> = resource.fields.iter().map(format_field_prefix).collect();
+    let prefix_width = prefixes.iter().map(|p| p.chars().count()).max().unwrap_or(0);
+    let lines: Vec<String> = resource
+        .fields
+        .iter()
+        .zip(&prefixes)
+        .map(|(field, prefix)| format!("{:<width$} {}", prefix, field.name, width = prefix_width))
+        .collect();
+    let line_width = resource
+        .fields
+        .iter()
+        .zip(&lines)
+        .filter(|(field, _)| field.trivia.trailing.is_some())
+        .map(|(_, line)| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    for (field, line) in resource.fields.iter().zip(&lines) {
+        if field.trivia.blank_line_before {
+            code.push('\n');
+        }
+        for comment in &field.trivia.leading {
+            code.push_str(&format!("    //{}\n", comment));
+        }
+        match &field.trivia.trailing {
+            Some(comment) => code.push_str(&format!("    {:<width$} //{}\n", line, comment, width = line_width)),
+            None => code.push_str(&format!("    {}\n", line)),
+        }
+    }
+
+    for comment in &resource.closing_comments {
+        code.push_str(&format!("    //{}\n", comment));
+    }
+    code.push_str("}\n");
+    code
+}
+
+fn format_service(service: &Service) -> String {
+    let mut code = String::new();
+
+    for comment in &service.trivia.leading {
+        code.push_str(&format!("//{}\n", comment));
+    }
+
+    code.push_str(&format!("service {} {{", service.name));
+    if let Some(comment) = &service.trivia.trailing {
+        code.push_str(&format!(" //{}", comment));
+    } else if service.endpoints.is_empty() && service.closing_comments.is_empty() {
+        code.push_str("}\n");
+        return code;
+    }
+    code.push('\n');
+
+    for endpoint in &service.endpoints {
+        if endpoint.trivia.blank_line_before {
+            code.push('\n');
+        }
+        for comment in &endpoint.trivia.leading {
+            code.push_str(&format!("    //{}\n", comment));
+        }
+        let mut line = format!("{} \"{}\"", endpoint.method.keyword(), endpoint.path);
+        if let Some(request) = &endpoint.request {
+            line.push_str(&format!(" {}", ast_type_to_source(request)));
+        }
+        if let Some(response) = &endpoint.response {
+            line.push_str(&format!(" -> {}", ast_type_to_source(response)));
+        }
+        match &endpoint.trivia.trailing {
+            Some(comment) => code.push_str(&format!("    {} //{}\n", line, comment)),
+            None => code.push_str(&format!("    {}\n", line)),
+        }
+    }
+
+    for comment in &service.closing_comments {
+        code.push_str(&format!("    //{}\n", comment));
+    }
+    code.push_str("}\n");
+    code
+}
+
+/// Attributes and type of a field, e.g. `optional default(3) number`
+fn format_field_prefix(field: &Field) -> String {
+    let mut parts = Vec::new();
+    if field.optional {
+        parts.push("optional".to_string());
+    }
+    if field.nullable {
+        parts.push("nullable".to_string());
+    }
+    if let Some(default) = &field.default {
+        parts.push(format!("default({})", literal_to_source(&default.value)));
+    }
+    parts.push(ast_type_to_source(&field.field_type));
+    parts.join(" ")
+}
+
+fn literal_to_source(literal: &Literal) -> String {
+    match literal {
+        Literal::String(s) => format!("\"{}\"", s),
+        Literal::Number(n) => n.to_string(),
+        Literal::Bool(b) => b.to_string(),
+    }
+}
+
+fn ast_type_to_source(ast_type: &ASTType) -> String {
+    match ast_type {
+        ASTType::Primitive(name) | ASTType::Named(name) => name.clone(),
+        ASTType::List(inner) => format!("list {}", ast_type_to_source(inner)),
+    }
+}
+
+// ============================================================================
+// PROTOCOL BUFFERS IMPORT
+// ============================================================================
+//
+// Translates proto2/proto3 messages into resources:
+// - scalar types map to string/number/bool (float and double are lossy)
+// - `optional` stays optional, `repeated` becomes `list`
+// - nested messages are flattened into `OuterInner` resources, a clash with
+//   another message of that name fails the import
+// - enums, maps, oneofs, bytes and services are reported as warnings
+//
+
+/// Result of translating a `.proto` file into a Previous program
+#[derive(Debug, Clone)]
+pub struct ProtoImport {
+    pub program: Program,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ProtoToken {
+    Ident(String),
+    Int(String),
+    Str(String),
+    Symbol(char),
+}
+
+fn tokenize_proto(input: &str) -> Result<Vec<(ProtoToken, usize)>, String> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut line = 1;
+
+    while pos < chars.len() {
+        let ch = chars[pos];
+        if ch == '\n' {
+            line += 1;
+            pos += 1;
+        } else if ch.is_whitespace() {
+            pos += 1;
+        } else if ch == '/' && chars.get(pos + 1) == Some(&'/') {
+            while pos < chars.len() && chars[pos] != '\n' {
+                pos += 1;
+            }
+        } else if ch == '/' && chars.get(pos + 1) == Some(&'*') {
+            pos += 2;
+            while pos < chars.len() && !(chars[pos] == '*' && chars.get(pos + 1) == Some(&'/')) {
+                if chars[pos] == '\n' {
+                    line += 1;
+                }
+                pos += 1;
+            }
+            pos += 2;
+        } else if ch == '"' || ch == '\'' {
+            let quote = ch;
+            let mut value = String::new();
+            pos += 1;
+            while pos < chars.len() && chars[pos] != quote {
+                if chars[pos] == '\\' && pos + 1 < chars.len() {
+                    pos += 1;
+                }
+                value.push(chars[pos]);
+                pos += 1;
+            }
+            pos += 1;
+            tokens.push((ProtoToken::Str(value), line));
+        } else if ch.is_ascii_digit() || (ch == '-' && chars.get(pos + 1).is_some_and(|c| c.is_ascii_digit())) {
+            let mut value = String::new();
+            value.push(ch);
+            pos += 1;
+            while pos < chars.len() && (chars[pos].is_ascii_alphanumeric() || chars[pos] == '.') {
+                value.push(chars[pos]);
+                pos += 1;
+            }
+            tokens.push((ProtoToken::Int(value), line));
+        } else if ch.is_alphabetic() || ch == '_' || ch == '.' {
+            let mut value = String::new();
+            while pos < chars.len() && (chars[pos].is_alphanumeric() || chars[pos] == '_' || chars[pos] == '.') {
+                value.push(chars[pos]);
+                pos += 1;
+            }
+            tokens.push((ProtoToken::Ident(value), line));
+        } else if "{}[]()<>;=,:".contains(ch) {
+            tokens.push((ProtoToken::Symbol(ch), line));
+            pos += 1;
+        } else {
+            return Err(format!("Unexpected character '{}' at line {}", ch, line));
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// A proto field before its type is resolved against the declared messages
+struct ProtoField {
+    name: String,
+    type_name: String,
+    repeated: bool,
+    optional: bool,
+    line: usize,
+}
+
+struct ProtoMessage {
+    /// Fully qualified name inside the file, e.g. `Outer.Inner`
+    full_name: String,
+    fields: Vec<ProtoField>,
+}
+
+struct ProtoParser {
+    tokens: Vec<(ProtoToken, usize)>,
+    position: usize,
+    messages: Vec<ProtoMessage>,
+    enums: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl ProtoParser {
+    fn current(&self) -> Option<&ProtoToken> {
+        self.tokens.get(self.position).map(|(t, _)| t)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map(|(_, l)| *l)
+            .unwrap_or(1)
+    }
+
+    fn advance(&mut self) -> Option<ProtoToken> {
+        let token = self.tokens.get(self.position).map(|(t, _)| t.clone());
+        self.position += 1;
+        token
+    }
+
+    fn is_symbol(&self, symbol: char) -> bool {
+        self.current() == Some(&ProtoToken::Symbol(symbol))
+    }
+
+    fn expect_symbol(&mut self, symbol: char) -> Result<(), String> {
+        if self.is_symbol(symbol) {
+            self.advance();
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' at line {}, got {:?}", symbol, self.line(), self.current()))
+        }
+    }
+
+    fn expect_ident(&mut self) -> Result<String, String> {
+        match self.advance() {
+            Some(ProtoToken::Ident(id)) => Ok(id),
+            other => Err(format!("Expected identifier at line {}, got {:?}", self.line(), other)),
+        }
+    }
+
+    /// Skip tokens up to and including the next `;`
+    fn skip_statement(&mut self) {
+        while let Some(token) = self.advance() {
+            if token == ProtoToken::Symbol(';') {
+                break;
+            }
+        }
+    }
+
+    /// Skip a `{ ... }` block including nested blocks
+    fn skip_block(&mut self) -> Result<(), String> {
+        while !self.is_symbol('{') {
+            if self.advance().is_none() {
+                return Err("Unexpected end of file, expected '{'".to_string());
+            }
+        }
+        let mut depth = 0;
+        while let Some(token) = self.advance() {
+            match token {
+                ProtoToken::Symbol('{') => depth += 1,
+                ProtoToken::Symbol('}') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err("Unexpected end of file, unclosed block".to_string())
+    }
+
+    fn parse_file(&mut self) -> Result<(), String> {
+        while let Some(token) = self.current().cloned() {
+            match token {
+                ProtoToken::Ident(kw) if kw == "message" => {
+                    self.advance();
+                    self.parse_message("")?;
+                }
+                ProtoToken::Ident(kw) if kw == "enum" => {
+                    self.advance();
+                    let name = self.expect_ident()?;
+                    self.warnings.push(format!(
+                        "line {}: enum '{}' is not supported, fields using it are imported as number",
+                        self.line(),
+                        name
+                    ));
+                    self.enums.push(name);
+                    self.skip_block()?;
+                }
+                ProtoToken::Ident(kw) if kw == "service" => {
+                    self.advance();
+                    let name = self.expect_ident()?;
+                    self.warnings.push(format!("line {}: service '{}' is not supported, skipped", self.line(), name));
+                    self.skip_block()?;
+                }
+                ProtoToken::Ident(kw) if kw == "extend" => {
+                    self.warnings.push(format!("line {}: extend is not supported, skipped", self.line()));
+                    self.skip_block()?;
+                }
+                ProtoToken::Ident(kw) if kw == "import" => {
+                    self.warnings.push(format!(
+                        "line {}: imports are not followed, referenced messages must be defined in this file",
+                        self.line()
+                    ));
+                    self.skip_statement();
+                }
+                ProtoToken::Ident(kw) if kw == "syntax" || kw == "edition" || kw == "package" || kw == "option" => {
+                    self.skip_statement();
+                }
+                ProtoToken::Symbol(';') => {
+                    self.advance();
+                }
+                other => return Err(format!("Unexpected {:?} at line {}", other, self.line())),
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_message(&mut self, scope: &str) -> Result<(), String> {
+        let name = self.expect_ident()?;
+        let full_name = if scope.is_empty() { name } else { format!("{}.{}", scope, name) };
+        self.expect_symbol('{')?;
+
+        let message_idx = self.messages.len();
+        self.messages.push(ProtoMessage {
+            full_name: full_name.clone(),
+            fields: Vec::new(),
+        });
+
+        while !self.is_symbol('}') {
+            let token = match self.current().cloned() {
+                Some(token) => token,
+                None => return Err(format!("Unexpected end of file in message '{}'", full_name)),
+            };
+            match token {
+                ProtoToken::Ident(kw) if kw == "message" => {
+                    self.advance();
+                    self.parse_message(&full_name)?;
+                }
+                ProtoToken::Ident(kw) if kw == "enum" => {
+                    self.advance();
+                    let name = self.expect_ident()?;
+                    self.warnings.push(format!(
+                        "line {}: enum '{}.{}' is not supported, fields using it are imported as number",
+                        self.line(),
+                        full_name,
+                        name
+                    ));
+                    self.enums.push(format!("{}.{}", full_name, name));
+                    self.skip_block()?;
+                }
+                ProtoToken::Ident(kw) if kw == "oneof" => {
+                    self.advance();
+                    let name = self.expect_ident()?;
+                    self.warnings.push(format!(
+                        "line {}: oneof '{}' in '{}' is not supported, its fields are imported as optional",
+                        self.line(),
+                        name,
+                        full_name
+                    ));
+                    self.expect_symbol('{')?;
+                    while !self.is_symbol('}') {
+                        if self.current().is_none() {
+                            return Err(format!("Unexpected end of file in oneof '{}'", name));
+                        }
+                        if self.current() == Some(&ProtoToken::Ident("option".to_string())) {
+                            self.skip_statement();
+                            continue;
+                        }
+                        let mut field = self.parse_field()?;
+                        field.optional = true;
+                        self.messages[message_idx].fields.push(field);
+                    }
+                    self.expect_symbol('}')?;
+                }
+                ProtoToken::Ident(kw) if kw == "map" => {
+                    let line = self.line();
+                    self.skip_statement();
+                    self.warnings.push(format!("line {}: map field in '{}' is not supported, skipped", line, full_name));
+                }
+                ProtoToken::Ident(kw) if kw == "option" || kw == "reserved" || kw == "extensions" => {
+                    self.skip_statement();
+                }
+                ProtoToken::Ident(kw) if kw == "extend" => {
+                    self.warnings.push(format!("line {}: extend is not supported, skipped", self.line()));
+                    self.skip_block()?;
+                }
+                ProtoToken::Symbol(';') => {
+                    self.advance();
+                }
+                _ => {
+                    let field = self.parse_field()?;
+                    self.messages[message_idx].fields.push(field);
+                }
+            }
+        }
+        self.expect_symbol('}')?;
+
+        Ok(())
+    }
+
+    fn parse_field(&mut self) -> Result<ProtoField, String> {
+        let line = self.line();
+        let mut repeated = false;
+        let mut optional = false;
+
+        let mut type_name = self.expect_ident()?;
+        match type_name.as_str() {
+            "repeated" => {
+                repeated = true;
+                type_name = self.expect_ident()?;
+            }
+            "optional" => {
+                optional = true;
+                type_name = self.expect_ident()?;
+            }
+            "required" => {
+                type_name = self.expect_ident()?;
+            }
+            _ => {}
+        }
+
+        let name = self.expect_ident()?;
+        self.expect_symbol('=')?;
+        match self.advance() {
+            Some(ProtoToken::Int(_)) => {}
+            other => return Err(format!("Expected field number at line {}, got {:?}", line, other)),
+        }
+
+        // Field options such as [deprecated = true]
+        if self.is_symbol('[') {
+            while !self.is_symbol(']') {
+                if self.advance().is_none() {
+                    return Err(format!("Unclosed field options at line {}", line));
+                }
+            }
+            self.advance();
+        }
+        self.expect_symbol(';')?;
+
+        Ok(ProtoField {
+            name,
+            type_name,
+            repeated,
+            optional,
+            line,
+        })
+    }
+}
+
+/// Convert a dotted proto name into a PascalCase resource name
+fn proto_resource_name(full_name: &str) -> String {
+    full_name
+        .split('.')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+/// Resolve a type reference from inside `scope` following proto scoping rules
+///
+/// The innermost scope is searched first, then each enclosing scope.
+fn resolve_proto_name<'a>(type_name: &str, scope: &str, candidates: impl Iterator<Item = &'a str> + Clone) -> Option<String> {
+    let type_name = type_name.trim_start_matches('.');
+    let mut scope_parts: Vec<&str> = scope.split('.').filter(|s| !s.is_empty()).collect();
+
+    loop {
+        let qualified = if scope_parts.is_empty() {
+            type_name.to_string()
+        } else {
+            format!("{}.{}", scope_parts.join("."), type_name)
+        };
+        if let Some(found) = candidates.clone().find(|c| *c == qualified) {
+            return Some(found.to_string());
+        }
+        if scope_parts.pop().is_none() {
+            break;
+        }
+    }
+
+    // Fully qualified names carry the package prefix, match on the suffix
+    candidates
+        .clone()
+        .find(|c| type_name.ends_with(&format!(".{}", c)))
+        .map(|c| c.to_string())
+}
+
+/// Translate a `.proto` file into a Previous program
+///
+/// Unsupported constructs do not fail the import, they are skipped or
+/// approximated and reported in `ProtoImport::warnings`.
+pub fn import_proto(input: &str) -> Result<ProtoImport, CompileError> {
+    let invalid = |message: String| CompileError::from(DiagnosticKind::InvalidProto { message });
+    let mut parser = ProtoParser {
+        tokens: tokenize_proto(input).map_err(invalid)?,
+        position: 0,
+        messages: Vec::new(),
+        enums: Vec::new(),
+        warnings: Vec::new(),
+    };
+    parser.parse_file().map_err(invalid)?;
+
+    let message_names: Vec<String> = parser.messages.iter().map(|m| m.full_name.clone()).collect();
+    for (i, name) in message_names.iter().enumerate() {
+        let resource = proto_resource_name(name);
+        if let Some(other) = message_names[..i].iter().find(|other| proto_resource_name(other) == resource) {
+            return Err(invalid(format!(
+                "Messages '{}' and '{}' would both be imported as resource '{}'",
+                other, name, resource
+            )));
+        }
+    }
+    let mut warnings = parser.warnings;
+    let mut resources = Vec::new();
+
+    for message in &parser.messages {
+        let mut fields = Vec::new();
+
+        for proto_field in &message.fields {
+            let scalar = match proto_field.type_name.as_str() {
+                "string" => Some("string"),
+                "bool" => Some("bool"),
+                "int32" | "int64" | "uint32" | "uint64" | "sint32" | "sint64" | "fixed32" | "fixed64"
+                | "sfixed32" | "sfixed64" => Some("number"),
+                "float" | "double" => {
+                    warnings.push(format!(
+                        "line {}: '{}.{}' is {}, imported as number (integer)",
+                        proto_field.line, message.full_name, proto_field.name, proto_field.type_name
+                    ));
+                    Some("number")
+                }
+                _ => None,
+            };
+
+            let field_type = if let Some(primitive) = scalar {
+                ASTType::Primitive(primitive.to_string())
+            } else if proto_field.type_name == "bytes" {
+                warnings.push(format!(
+                    "line {}: bytes field '{}.{}' is not supported, skipped",
+                    proto_field.line, message.full_name, proto_field.name
+                ));
+                continue;
+            } else if let Some(found) =
+                resolve_proto_name(&proto_field.type_name, &message.full_name, message_names.iter().map(|s| s.as_str()))
+            {
+                ASTType::Named(proto_resource_name(&found))
+            } else if resolve_proto_name(&proto_field.type_name, &message.full_name, parser.enums.iter().map(|s| s.as_str()))
+                .is_some()
+            {
+                ASTType::Primitive("number".to_string())
+            } else {
+                warnings.push(format!(
+                    "line {}: unknown type '{}' for '{}.{}', skipped",
+                    proto_field.line, proto_field.type_name, message.full_name, proto_field.name
+                ));
+                continue;
+            };
+
+            let field_type = if proto_field.repeated {
+                ASTType::List(Box::new(field_type))
+            } else {
+                field_type
+            };
+
+            let mut name = proto_field.name.clone();
+            if Lexer::new(&name).next_token() != Token::Identifier(name.clone()) {
+                warnings.push(format!(
+                    "line {}: field '{}.{}' is a reserved word, imported as '{}_'",
+                    proto_field.line, message.full_name, name, name
+                ));
+                name.push('_');
+            }
+
+            fields.push(Field {
+                name,
+                field_type,
+                nullable: false,
+                optional: proto_field.optional,
+                default: None,
+                index: fields.len(),
+                trivia: Trivia::default(),
+                name_span: Span::default(),
+                type_span: Span::default(),
+            });
+        }
+
+        resources.push(Resource {
+            name: proto_resource_name(&message.full_name),
+            package: None,
+            fields,
+            name_span: Span::default(),
+            trivia: Trivia::default(),
+            closing_comments: Vec::new(),
+            file: None,
+        });
+    }
+
+    Ok(ProtoImport {
+        program: Program {
+            package: None,
+            resources,
+            services: Vec::new(),
+            trailing_comments: Vec::new(),
+        },
+        warnings,
+    })
+}
+
+// ============================================================================
+// LANGUAGE SERVER
+// ============================================================================
+//
+// Minimal Language Server Protocol implementation over stdio (JSON-RPC with
+// `Content-Length` framing). Documents are fully re-parsed on every change.
+//
+// Supported requests: initialize, shutdown, textDocument/definition,
+// textDocument/references, textDocument/completion, textDocument/hover,
+// textDocument/rename. Diagnostics are published on open and change.
+//
+
+use serde_json::{json, Value as Json};
+
+const LSP_KEYWORDS: &[&str] = &["package", "resource", "service", "string", "number", "bool", "list", "optional", "nullable", "default"];
+
+/// What the cursor points at in a parsed document
+enum SymbolAt {
+    /// The name in `resource Name {`
+    ResourceName(usize),
+    /// A field's type; `target` is the referenced resource, if any
+    FieldType { resource: usize, field: usize, target: Option<usize> },
+    /// A field's name
+    FieldName { resource: usize, field: usize },
+}
+
+/// Language server state: the open documents, keyed by URI
+#[derive(Default)]
+pub struct LanguageServer {
+    documents: std::collections::HashMap<String, String>,
+    shutdown_requested: bool,
+}
+
+impl LanguageServer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle one incoming JSON-RPC message
+    ///
+    /// Returns the messages to send back and whether the server should exit.
+    pub fn handle(&mut self, message: &Json) -> (Vec<Json>, bool) {
+        let method = message["method"].as_str().unwrap_or_default();
+        let id = message.get("id").cloned();
+        let params = &message["params"];
+
+        let result = match method {
+            "initialize" => Some(Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                    "renameProvider": true
+                },
+                "serverInfo": { "name": "previouscc", "version": env!("CARGO_PKG_VERSION") }
+            }))),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Some(Ok(Json::Null))
+            }
+            "exit" => return (Vec::new(), true),
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default().to_string();
+                self.documents.insert(uri.clone(), text);
+                return (vec![self.publish_diagnostics(&uri)], false);
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+                // Full document sync: the last change holds the whole text
+                if let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                {
+                    self.documents.insert(uri.clone(), text.to_string());
+                }
+                return (vec![self.publish_diagnostics(&uri)], false);
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+                self.documents.remove(&uri);
+                let clear = json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] }
+                });
+                return (vec![clear], false);
+            }
+            "textDocument/definition" => Some(Ok(self.definition(params))),
+            "textDocument/references" => Some(Ok(self.references(params))),
+            "textDocument/completion" => Some(Ok(self.completion(params))),
+            "textDocument/hover" => Some(Ok(self.hover(params))),
+            "textDocument/rename" => Some(self.rename(params)),
+            _ if id.is_some() => Some(Err((-32601, format!("Method not found: {}", method)))),
+            _ => None,
+        };
+
+        let response = match (id, result) {
+            (Some(id), Some(Ok(result))) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            (Some(id), Some(Err((code, message)))) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message }
+            }),
+            _ => return (Vec::new(), false),
+        };
+        (vec![response], false)
+    }
+
+    fn document<'a>(&'a self, params: &'a Json) -> Option<(&'a str, &'a str)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let text = self.documents.get(uri)?;
+        Some((uri, text))
+    }
+
+    /// Parse the document and find the symbol under the request position
+    fn symbol_at_position<'a>(&'a self, params: &'a Json) -> Option<(String, &'a str, Program, SymbolAt)> {
+        let (uri, text) = self.document(params)?;
+        let program = parse_schema(text).ok()?;
+        let line = params["position"]["line"].as_u64()? as usize + 1;
+        let column = lsp_column(text, line, params["position"]["character"].as_u64()? as usize);
+        let symbol = lsp_symbol_at(&program, line, column)?;
+        Some((uri.to_string(), text, program, symbol))
+    }
+
+    fn publish_diagnostics(&self, uri: &str) -> Json {
+        let text = self.documents.get(uri).map(|s| s.as_str()).unwrap_or_default();
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": lsp_diagnostics(text) }
+        })
+    }
+
+    fn definition(&self, params: &Json) -> Json {
+        let Some((uri, text, program, symbol)) = self.symbol_at_position(params) else {
+            return Json::Null;
+        };
+        let target = match symbol {
+            SymbolAt::ResourceName(idx) => idx,
+            SymbolAt::FieldType { target: Some(idx), .. } => idx,
+            _ => return Json::Null,
+        };
+        lsp_location(&uri, text, program.resources[target].name_span)
+    }
+
+    fn references(&self, params: &Json) -> Json {
+        let Some((uri, text, program, symbol)) = self.symbol_at_position(params) else {
+            return Json::Null;
+        };
+        let target = match symbol {
+            SymbolAt::ResourceName(idx) => idx,
+            SymbolAt::FieldType { target: Some(idx), .. } => idx,
+            _ => return Json::Null,
+        };
+
+        let mut locations = Vec::new();
+        if params["context"]["includeDeclaration"].as_bool().unwrap_or(false) {
+            locations.push(lsp_location(&uri, text, program.resources[target].name_span));
+        }
+        for (span, _) in lsp_references(&program, target) {
+            locations.push(lsp_location(&uri, text, span));
+        }
+        Json::Array(locations)
+    }
+
+    fn completion(&self, params: &Json) -> Json {
+        let mut items: Vec<Json> = LSP_KEYWORDS
+            .iter()
+            .map(|keyword| json!({ "label": keyword, "kind": 14 }))
+            .collect();
+
+        // Resource names come from the last document state that parses
+        if let Some(program) = self.document(params).and_then(|(_, text)| parse_schema(text).ok()) {
+            for resource in &program.resources {
+                items.push(json!({
+                    "label": resource.name,
+                    "kind": 22,
+                    "detail": format!("resource ({} fields)", resource.fields.len())
+                }));
+            }
+        }
+        Json::Array(items)
+    }
+
+    fn hover(&self, params: &Json) -> Json {
+        let Some((_, text, program, symbol)) = self.symbol_at_position(params) else {
+            return Json::Null;
+        };
+        let (contents, span) = match symbol {
+            SymbolAt::ResourceName(idx) => {
+                let resource = &program.resources[idx];
+                (lsp_resource_summary(resource), resource.name_span)
+            }
+            SymbolAt::FieldType { target: Some(idx), resource, field } => {
+                let span = program.resources[resource].fields[field].type_span;
+                (lsp_resource_summary(&program.resources[idx]), span)
+            }
+            SymbolAt::FieldType { resource, field, target: None } | SymbolAt::FieldName { resource, field } => {
+                let owner = &program.resources[resource];
+                let field = &owner.fields[field];
+                let contents = format!(
+                    "**{}.{}**: index {}\n\n```\n{} {}\n```\n\nWire layout: {}",
+                    owner.name,
+                    field.name,
+                    field.index,
+                    format_field_prefix(field),
+                    field.name,
+                    field_wire_layout(field)
+                );
+                (contents, field.name_span)
+            }
+        };
+        json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": lsp_range(text, span)
+        })
     }
-}
 
-// ============================================================================
-// CODE GENERATION (Phase 4)
-// ============================================================================
+    fn rename(&self, params: &Json) -> Result<Json, (i64, String)> {
+        let new_name = params["newName"].as_str().unwrap_or_default();
+        let valid = new_name.chars().next().is_some_and(|c| c.is_uppercase())
+            && new_name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && Lexer::new(new_name).next_token() == Token::Identifier(new_name.to_string());
+        if !valid {
+            return Err((-32602, format!("Resource name must be a PascalCase identifier: {}", new_name)));
+        }
 
-/// Generated code output containing client and server code
-#[derive(Debug, Clone)]
-pub struct GeneratedCode {
-    pub typescript_client: String,
-    pub typescript_server: String,
+        let Some((uri, text, program, symbol)) = self.symbol_at_position(params) else {
+            return Ok(Json::Null);
+        };
+        let target = match symbol {
+            SymbolAt::ResourceName(idx) => idx,
+            SymbolAt::FieldType { target: Some(idx), .. } => idx,
+            _ => return Err((-32602, "Only resources can be renamed".to_string())),
+        };
+        let package = &program.resources[target].package;
+        if program.resources.iter().any(|r| r.name == new_name && &r.package == package) {
+            return Err((-32602, format!("A resource named {} already exists", new_name)));
+        }
+
+        let mut edits = vec![json!({ "range": lsp_range(text, program.resources[target].name_span), "newText": new_name })];
+        for (span, written) in lsp_references(&program, target) {
+            // Qualified references keep their package
+            let new_text = match written.rsplit_once('.') {
+                Some((package, _)) => format!("{}.{}", package, new_name),
+                None => new_name.to_string(),
+            };
+            edits.push(json!({ "range": lsp_range(text, span), "newText": new_text }));
+        }
+
+        let mut changes = serde_json::Map::new();
+        changes.insert(uri, Json::Array(edits));
+        Ok(json!({ "changes": changes }))
+    }
 }
 
-/// Code generator for TypeScript client and server
-pub struct CodeGenerator {
-    ir: IRProgram,
+fn innermost_ast_type(ast_type: &ASTType) -> &ASTType {
+    match ast_type {
+        ASTType::List(inner) => innermost_ast_type(inner),
+        other => other,
+    }
 }
 
-impl CodeGenerator {
-    pub fn new(ir: IRProgram) -> Self {
-        CodeGenerator { ir }
+fn lsp_symbol_at(program: &Program, line: usize, column: usize) -> Option<SymbolAt> {
+    for (r, resource) in program.resources.iter().enumerate() {
+        if resource.name_span.contains(line, column) {
+            return Some(SymbolAt::ResourceName(r));
+        }
+        for (f, field) in resource.fields.iter().enumerate() {
+            if field.type_span.contains(line, column) {
+                let target = match innermost_ast_type(&field.field_type) {
+                    ASTType::Named(name) => program.resolve_name(resource.package.as_deref(), name),
+                    _ => None,
+                };
+                return Some(SymbolAt::FieldType { resource: r, field: f, target });
+            }
+            if field.name_span.contains(line, column) {
+                return Some(SymbolAt::FieldName { resource: r, field: f });
+            }
+        }
     }
+    None
+}
 
-    /// Generate both client and server code
-    pub fn generate(&self) -> GeneratedCode {
-        GeneratedCode {
-            typescript_client: self.generate_typescript_client(),
-            typescript_server: self.generate_typescript_server(),
+/// Every field type that refers to the resource at `target`, with the
+/// name as written there
+fn lsp_references(program: &Program, target: usize) -> Vec<(Span, &str)> {
+    let fields = program
+        .resources
+        .iter()
+        .flat_map(|r| r.fields.iter().map(move |f| (r.package.as_deref(), &f.field_type, f.type_span)));
+    let endpoints = program.services.iter().flat_map(|s| {
+        s.endpoints.iter().flat_map(move |e| {
+            let request = e.request.as_ref().map(|t| (s.package.as_deref(), t, e.request_span));
+            let response = e.response.as_ref().map(|t| (s.package.as_deref(), t, e.response_span));
+            request.into_iter().chain(response)
+        })
+    });
+    fields
+        .chain(endpoints)
+        .filter_map(|(package, ast_type, span)| match innermost_ast_type(ast_type) {
+            ASTType::Named(n) if program.resolve_name(package, n) == Some(target) => Some((span, n.as_str())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// LSP `character` of a 1-based `column` on a 1-based `line`: spans count
+/// chars, LSP positions count UTF-16 code units
+fn lsp_character(text: &str, line: usize, column: usize) -> usize {
+    let mut chars = text.lines().nth(line.saturating_sub(1)).unwrap_or_default().chars();
+    (1..column).map(|_| chars.next().map_or(1, char::len_utf16)).sum()
+}
+
+/// 1-based column of an LSP `character` on a 1-based `line`, the inverse of
+/// `lsp_character`
+fn lsp_column(text: &str, line: usize, character: usize) -> usize {
+    let mut units = 0;
+    let mut column = 1;
+    for ch in text.lines().nth(line.saturating_sub(1)).unwrap_or_default().chars() {
+        if units >= character {
+            return column;
         }
+        units += ch.len_utf16();
+        column += 1;
     }
+    column + character.saturating_sub(units)
+}
 
-    // ========================================================================
-    // TypeScript Client Generation
-    // ========================================================================
+fn lsp_range(text: &str, span: Span) -> Json {
+    let line = span.line.saturating_sub(1);
+    let column = span.column.max(1);
+    json!({
+        "start": { "line": line, "character": lsp_character(text, span.line, column) },
+        "end": { "line": line, "character": lsp_character(text, span.line, column + span.length.max(1)) }
+    })
+}
 
-    fn generate_typescript_client(&self) -> String {
-        let mut code = String::new();
+fn lsp_location(uri: &str, text: &str, span: Span) -> Json {
+    json!({ "uri": uri, "range": lsp_range(text, span) })
+}
 
-        // Header
-        code.push_str("// Generated by Previous Compiler\n");
-        code.push_str("// DO NOT EDIT - This file is auto-generated\n\n");
+fn lsp_resource_summary(resource: &Resource) -> String {
+    let mut summary = format!("**resource {}**\n\n", resource.name);
+    summary.push_str("| Index | Field | Wire layout |\n|---|---|---|\n");
+    for field in &resource.fields {
+        summary.push_str(&format!(
+            "| {} | `{} {}` | {} |\n",
+            field.index,
+            format_field_prefix(field),
+            field.name,
+            field_wire_layout(field)
+        ));
+    }
+    summary
+}
 
-        // Binary reader utility class
-        code.push_str(&self.generate_binary_reader());
-        code.push_str("\n");
+/// Human readable description of how a field is laid out on the wire
+pub fn field_wire_layout(field: &Field) -> String {
+    let mut parts = Vec::new();
+    if field.optional {
+        parts.push("1 byte presence flag (0x00 absent, 0x01 present)".to_string());
+    }
+    if field.nullable {
+        parts.push("1 byte null flag (0x00 null, 0x01 present)".to_string());
+    }
+    parts.push(type_wire_layout(&field.field_type));
+    parts.join(", then ")
+}
 
-        // Generate each resource
-        for resource in &self.ir.resources {
-            code.push_str(&self.generate_ts_resource(resource));
-            code.push_str("\n");
+fn type_wire_layout(ast_type: &ASTType) -> String {
+    match ast_type {
+        ASTType::Primitive(p) => match p.as_str() {
+            "string" => "u32 length (little-endian) + UTF-8 bytes".to_string(),
+            "number" => "i64 (8 bytes, little-endian)".to_string(),
+            "bool" => "1 byte (0x00 false, 0x01 true)".to_string(),
+            _ => "unknown".to_string(),
+        },
+        ASTType::List(inner) => format!("u32 count (little-endian), then each item as {}", type_wire_layout(inner)),
+        ASTType::Named(name) => format!("the fields of {} in order", name),
+    }
+}
+
+/// Diagnostics for a document: the compile errors or lint warnings
+fn lsp_diagnostics(text: &str) -> Vec<Json> {
+    let output = match compile_schema(text) {
+        Ok(output) => output,
+        Err(e) => {
+            return e
+                .diagnostics
+                .iter()
+                .map(|d| {
+                    json!({
+                        "range": lsp_range(text, d.span.unwrap_or_default()),
+                        "severity": if d.severity == Severity::Error { 1 } else { 2 },
+                        "source": "previouscc",
+                        "code": d.code(),
+                        "message": d.message()
+                    })
+                })
+                .collect();
         }
+    };
+
+    let mut parser = Parser::new(text);
+    let (program, _) = parser.parse_with_errors();
+    let mut diagnostics = Vec::new();
+    for warning in lint(&output.ir, &LintConfig::default()) {
+        let resource = program.resources.iter().find(|r| r.name == warning.resource);
+        let span = match (resource, &warning.field) {
+            (Some(r), Some(field)) => r
+                .fields
+                .iter()
+                .find(|f| &f.name == field)
+                .map(|f| f.name_span)
+                .unwrap_or(r.name_span),
+            (Some(r), None) => r.name_span,
+            _ => Span::default(),
+        };
+        diagnostics.push(json!({
+            "range": lsp_range(text, span),
+            "severity": if warning.severity == Severity::Error { 1 } else { 2 },
+            "source": "previouscc",
+            "code": warning.rule.name(),
+            "message": warning.message
+        }));
+    }
+    diagnostics
+}
 
-        code
+/// Read one `Content-Length` framed JSON-RPC message, `None` at end of input
+/// Error for a failed read or write of the LSP stream
+fn lsp_error(operation: &str, stream: &str, error: String) -> CompileError {
+    CompileError::from(DiagnosticKind::Io {
+        operation: operation.to_string(),
+        path: PathBuf::from(stream),
+        error,
+    })
+}
+
+fn read_lsp_message(reader: &mut impl std::io::BufRead) -> Result<Option<Json>, CompileError> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        let read = reader
+            .read_line(&mut header)
+            .map_err(|e| lsp_error("read message header from", "<stdin>", e.to_string()))?;
+        if read == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            content_length = value.trim().parse::<usize>().ok();
+        }
     }
 
-    fn generate_binary_reader(&self) -> String {
-        r#"class BinaryReader {
-  private buffer: Uint8Array;
-  private offset: number;
+    let length = content_length
+        .ok_or_else(|| lsp_error("read message from", "<stdin>", "missing Content-Length header".to_string()))?;
+    let mut body = vec![0u8; length];
+    reader
+        .read_exact(&mut body)
+        .map_err(|e| lsp_error("read message body from", "<stdin>", e.to_string()))?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| lsp_error("parse JSON-RPC message from", "<stdin>", e.to_string()))
+}
 
-  constructor(buffer: Uint8Array) {
-    this.buffer = buffer;
-    this.offset = 0;
-  }
+fn write_lsp_message(writer: &mut impl std::io::Write, message: &Json) -> Result<(), CompileError> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| writer.flush())
+        .map_err(|e| lsp_error("write message to", "<stdout>", e.to_string()))
+}
 
-  readString(): string {
-    const length = this.readU32();
-    const bytes = this.buffer.slice(this.offset, this.offset + length);
-    this.offset += length;
-    return new TextDecoder().decode(bytes);
-  }
+/// Serve the language server protocol until `exit` or end of input
+pub fn run_language_server(
+    mut input: impl std::io::BufRead,
+    mut output: impl std::io::Write,
+) -> Result<(), CompileError> {
+    let mut server = LanguageServer::new();
+    while let Some(message) = read_lsp_message(&mut input)? {
+        let (responses, exit) = server.handle(&message);
+        for response in &responses {
+            write_lsp_message(&mut output, response)?;
+        }
+        if exit {
+            break;
+        }
+    }
+    Ok(())
+}
 
-  readNumber(): number {
-    const view = new DataView(this.buffer.buffer, this.offset, 8);
-    const value = view.getBigInt64(0, true); // little-endian
-    this.offset += 8;
-    return Number(value);
-  }
+// ============================================================================
+// PUBLIC API
+// ============================================================================
 
-  readBool(): boolean {
-    const value = this.buffer[this.offset];
-    this.offset += 1;
-    return value === 1;
-  }
+pub fn run() {
+    println!("Previous Compiler v0.1.0");
+}
 
-  readU32(): number {
-    const view = new DataView(this.buffer.buffer, this.offset, 4);
-    const value = view.getUint32(0, true); // little-endian
-    this.offset += 4;
-    return value;
-  }
+pub fn parse_schema(input: &str) -> Result<Program, CompileError> {
+    let mut parser = Parser::new(input);
+    parser.parse().map_err(|e| e.with_source(input))
+}
 
-  readByte(): number {
-    const value = this.buffer[this.offset];
-    this.offset += 1;
-    return value;
-  }
+/// Parse, validate and resolve a schema without rejecting cycles
+///
+/// Useful for tools that inspect the resource graph itself, such as
+/// `previouscc graph`.
+pub fn resolve_schema(input: &str) -> Result<IRProgram, CompileError> {
+    let program = parse_schema(input)?;
+    let resolved = Compiler::new(program.clone())
+        .and_then(|_| TypeResolver::new(&program))
+        .and_then(|resolver| resolver.resolve(program));
+    resolved.map_err(|e| e.with_source(input))
 }
-"#.to_string()
+
+/// Compile a schema, reporting every error found
+///
+/// Syntax errors do not stop the AST validations, and undefined types are
+/// still looked up in whatever could be parsed.
+pub fn compile_schema(input: &str) -> Result<CompiledOutput, CompileError> {
+    let mut parser = Parser::new(input);
+    let (program, errors) = parser.parse_with_errors();
+    compile_program(program, errors).map_err(|e| e.with_source(input))
+}
+
+/// Compile several schema files as one program
+///
+/// Files declaring the same package (or none) share one namespace, so a
+/// name declared in two of them is reported at the second with a note
+/// pointing at the first. Each resource and service remembers its file.
+pub fn compile_sources(sources: &[(PathBuf, String)]) -> Result<CompiledOutput, CompileError> {
+    let mut program = Program {
+        package: None,
+        resources: Vec::new(),
+        services: Vec::new(),
+        trailing_comments: Vec::new(),
+    };
+    let mut errors = Vec::new();
+
+    for (path, source) in sources {
+        let (parsed, parse_errors) = Parser::new(source).parse_with_errors();
+        errors.extend(parse_errors.into_iter().map(|e| e.in_file(Some(path))));
+        program.resources.extend(parsed.resources.into_iter().map(|mut resource| {
+            resource.file = Some(path.clone());
+            resource
+        }));
+        program.services.extend(parsed.services.into_iter().map(|mut service| {
+            service.file = Some(path.clone());
+            service
+        }));
+    }
+
+    compile_program(program, errors).map_err(|e| e.with_sources(sources))
+}
+
+/// Validate, resolve and generate code for a parsed program, `errors` being
+/// the syntax errors found while parsing it
+fn compile_program(program: Program, mut errors: Vec<Diagnostic>) -> Result<CompiledOutput, CompileError> {
+    errors.extend(Compiler::validate(&program));
+
+    if errors.is_empty() {
+        return Compiler::new(program).and_then(|compiler| compiler.compile());
     }
 
-    fn generate_ts_resource(&self, resource: &IRResource) -> String {
-        let mut code = String::new();
+    // Keep resolving to report undefined types and cycles too, a duplicated
+    // resource name refers to its first declaration
+    let (ir, resolve_errors) = TypeResolver::first_declared(&program).resolve_partial(program.clone());
+    errors.extend(resolve_errors);
+    if let Err(e) = Compiler::detect_cycles(&program, &ir) {
+        errors.extend(e.diagnostics);
+    }
+    Err(CompileError::new(errors))
+}
 
-        // Interface for the resource
-        code.push_str(&format!("export interface I{} {{\n", resource.name));
-        for field in &resource.fields {
-            let ts_type = self.ir_type_to_typescript(&field.field_type);
-            let optional = if field.optional || field.nullable { "?" } else { "" };
-            code.push_str(&format!("  {}{}: {};\n", field.name, optional, ts_type));
+// ============================================================================
+// CLI & FILE I/O (Phase 5)
+// ============================================================================
+
+use serde::Deserialize;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Output targets the compiler can write
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Target {
+    /// TypeScript client and server code
+    #[serde(rename = "ts")]
+    TypeScript,
+    /// OpenAPI 3.1 document with the resources as component schemas
+    #[serde(rename = "openapi")]
+    OpenApi,
+    /// proto3 definitions with one message per resource
+    #[serde(rename = "proto")]
+    Proto,
+    /// GraphQL SDL type definitions
+    #[serde(rename = "graphql")]
+    GraphQl,
+    /// Rust structs implementing `Wire`, one module per package
+    #[serde(rename = "rust")]
+    Rust,
+}
+
+impl Target {
+    /// Files written to the output directory for this target
+    pub fn file_names(&self) -> &'static [&'static str] {
+        match self {
+            Target::TypeScript => &["client.ts", "server.ts"],
+            Target::OpenApi => &["openapi.json"],
+            Target::Proto => &["schema.proto"],
+            Target::GraphQl => &["schema.graphql"],
+            Target::Rust => &["previous.rs"],
         }
-        code.push_str("}\n\n");
+    }
+}
 
-        // Decoder class
-        code.push_str(&format!("export class {} {{\n", resource.name));
-        code.push_str("  private reader: BinaryReader;\n");
-        code.push_str(&format!("  private data: I{};\n\n", resource.name));
+/// How generated files are laid out when several schema files are compiled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLayout {
+    /// Every resource in one set of files in the output directory
+    #[default]
+    Merged,
+    /// One subdirectory per input file, named after its path relative to the
+    /// directory the inputs share without the extension, holding its
+    /// resources and the resources they reference from other files
+    PerFile,
+}
 
-        // Constructor
-        code.push_str("  constructor(buffer: Uint8Array) {\n");
-        code.push_str("    this.reader = new BinaryReader(buffer);\n");
-        code.push_str(&format!("    this.data = {{}} as I{};\n", resource.name));
-        code.push_str("    this.decode();\n");
-        code.push_str("  }\n\n");
+/// CLI options for the Previous compiler
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    /// Schema files or glob patterns such as `schemas/*.pr`, compiled as
+    /// one program
+    pub inputs: Vec<PathBuf>,
+    pub output_dir: PathBuf,
+    pub verbose: bool,
+    pub targets: Vec<Target>,
+    pub codegen: CodegenOptions,
+    pub layout: OutputLayout,
+}
 
-        // Decode method
-        code.push_str("  private decode(): void {\n");
-        for field in &resource.fields {
-            code.push_str(&self.generate_ts_field_decode(field));
+impl Default for CliOptions {
+    fn default() -> Self {
+        CliOptions {
+            inputs: vec![PathBuf::from("schema.pr")],
+            output_dir: PathBuf::from("./generated"),
+            verbose: false,
+            targets: vec![Target::TypeScript],
+            codegen: CodegenOptions::default(),
+            layout: OutputLayout::default(),
         }
-        code.push_str("  }\n\n");
+    }
+}
 
-        // Getter methods
-        for field in &resource.fields {
-            let ts_type = self.ir_type_to_typescript(&field.field_type);
-            let optional = if field.optional || field.nullable { " | null | undefined" } else { "" };
-            code.push_str(&format!(
-                "  get{}(): {}{} {{\n",
-                self.capitalize_first(&field.name),
-                ts_type,
-                optional
-            ));
-            code.push_str(&format!("    return this.data.{};\n", field.name));
-            code.push_str("  }\n\n");
-        }
+/// Error for a failed file operation, e.g. `io_error("read input file", path, e)`
+fn io_error(operation: &str, path: &Path, error: std::io::Error) -> CompileError {
+    CompileError::from(DiagnosticKind::Io {
+        operation: operation.to_string(),
+        path: path.to_path_buf(),
+        error: error.to_string(),
+    })
+}
 
-        // toJSON method
-        code.push_str(&format!("  toJSON(): I{} {{\n", resource.name));
-        code.push_str("    return this.data;\n");
-        code.push_str("  }\n");
+/// Expand glob patterns into the schema files they match
+///
+/// Plain paths are kept as given, so a missing file is reported when it is
+/// read. Matches are sorted and a file reached twice, e.g. as `a.pr` and
+/// `./a.pr` or through a glob and an explicit path, is compiled once under
+/// the first spelling. A pattern matching nothing is an error.
+pub fn expand_inputs(patterns: &[PathBuf]) -> Result<Vec<PathBuf>, CompileError> {
+    let mut files: Vec<PathBuf> = Vec::new();
+    let mut seen = std::collections::HashSet::new();
+    let mut push = |path: PathBuf| {
+        let key = fs::canonicalize(&path).unwrap_or_else(|_| path.clone());
+        if seen.insert(key) {
+            files.push(path);
+        }
+    };
+    for pattern in patterns {
+        let text = pattern.to_string_lossy();
+        if !text.contains(['*', '?', '[']) {
+            push(pattern.clone());
+            continue;
+        }
 
-        code.push_str("}\n");
-        code
+        let glob_error = |error: String| {
+            CompileError::from(DiagnosticKind::Io {
+                operation: "expand pattern".to_string(),
+                path: pattern.clone(),
+                error,
+            })
+        };
+        let mut matches: Vec<PathBuf> = glob::glob(&text)
+            .map_err(|e| glob_error(e.to_string()))?
+            .filter_map(Result::ok)
+            .filter(|path| path.is_file())
+            .collect();
+        if matches.is_empty() {
+            return Err(glob_error("no schema files match".to_string()));
+        }
+        matches.sort();
+        for path in matches {
+            push(path);
+        }
     }
+    Ok(files)
+}
 
-    fn generate_ts_field_decode(&self, field: &IRField) -> String {
-        let mut code = String::new();
+/// Read every input, in order
+fn read_inputs(files: &[PathBuf]) -> Result<Vec<(PathBuf, String)>, CompileError> {
+    files
+        .iter()
+        .map(|file| {
+            fs::read_to_string(file)
+                .map(|source| (file.clone(), source))
+                .map_err(|e| io_error("read input file", file, e).with_file(file.clone()))
+        })
+        .collect()
+}
 
-        // Handle optional
-        if field.optional {
-            code.push_str("    const isPresent = this.reader.readByte();\n");
-            code.push_str("    if (isPresent === 0) {\n");
-            code.push_str(&format!("      this.data.{} = undefined;\n", field.name));
-            code.push_str("    } else {\n");
-            code.push_str(&format!("      this.data.{} = {};\n",
-                field.name,
-                self.generate_ts_type_read(&field.field_type, "      ")));
-            code.push_str("    }\n");
-            return code;
-        }
+/// Expand, read and compile schema files as one program, without writing
+/// anything
+pub fn compile_inputs(patterns: &[PathBuf]) -> Result<CompiledOutput, CompileError> {
+    let files = expand_inputs(patterns)?;
+    let sources = read_inputs(&files)?;
+    compile_sources(&sources)
+}
 
-        // Handle nullable
-        if field.nullable {
-            code.push_str("    const isNull = this.reader.readByte();\n");
-            code.push_str("    if (isNull === 0) {\n");
-            code.push_str(&format!("      this.data.{} = null;\n", field.name));
-            code.push_str("    } else {\n");
-            code.push_str(&format!("      this.data.{} = {};\n",
-                field.name,
-                self.generate_ts_type_read(&field.field_type, "      ")));
-            code.push_str("    }\n");
-            return code;
+/// Compile the schema files and write generated code to files
+pub fn compile_file(options: &CliOptions) -> Result<RebuildReport, CompileError> {
+    // Read the input files
+    let files = expand_inputs(&options.inputs)?;
+    let sources = read_inputs(&files)?;
+
+    if options.verbose {
+        for file in &files {
+            eprintln!("Reading schema from: {}", file.display());
         }
+    }
+
+    // Compile the schemas as one program
+    let output = compile_sources(&sources)?;
+
+    if options.verbose {
+        eprintln!("Compilation successful!");
+        eprintln!("  Resources: {}", output.ir.resources.len());
+        eprintln!("  TypeScript Client lines: {}", output.generated_code.typescript_client.lines().count());
+        eprintln!("  TypeScript Server lines: {}", output.generated_code.typescript_server.lines().count());
+    }
 
-        // Regular field
-        code.push_str(&format!("    this.data.{} = {};\n",
-            field.name,
-            self.generate_ts_type_read(&field.field_type, "    ")));
-        code
+    let report = write_outputs(options, &files, &output.ir)?;
+
+    if options.verbose {
+        for path in &report.written {
+            eprintln!("  Generated: {}", path.display());
+        }
+        for path in &report.unchanged {
+            eprintln!("  Unchanged: {}", path.display());
+        }
     }
 
-    fn generate_ts_type_read(&self, ir_type: &IRType, indent: &str) -> String {
-        match ir_type {
-            IRType::Primitive(p) => match p.as_str() {
-                "string" => "this.reader.readString()".to_string(),
-                "number" => "this.reader.readNumber()".to_string(),
-                "bool" => "this.reader.readBool()".to_string(),
-                _ => "null".to_string(),
-            },
-            IRType::List(inner) => {
-                let inner_read = self.generate_ts_type_read(inner, indent);
-                format!(
-                    "(() => {{\n{}  const count = this.reader.readU32();\n{}  const items = [];\n{}  for (let i = 0; i < count; i++) {{\n{}    items.push({});\n{}  }}\n{}  return items;\n{}}})()",
-                    indent, indent, indent, indent, inner_read, indent, indent, indent
-                )
+    Ok(report)
+}
+
+/// Output directories for `files` under the configured layout, each with
+/// the resources generated into it
+fn output_dirs(options: &CliOptions, files: &[PathBuf], ir: &IRProgram) -> Result<Vec<(PathBuf, IRProgram)>, CompileError> {
+    if options.layout == OutputLayout::Merged {
+        return Ok(vec![(options.output_dir.clone(), ir.clone())]);
+    }
+
+    let names = per_file_names(files);
+    let mut dirs: Vec<(PathBuf, IRProgram)> = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let dir = options.output_dir.join(&names[i]);
+        if let Some(other) = (0..i).find(|&j| names[j] == names[i]).map(|j| &files[j]) {
+            return Err(DiagnosticKind::Codegen {
+                target: "per-file layout".to_string(),
+                message: format!(
+                    "'{}' and '{}' would both be written to '{}'",
+                    other.display(),
+                    file.display(),
+                    dir.display()
+                ),
             }
-            IRType::ResourceRef(idx) => {
-                let resource = &self.ir.resources[*idx];
-                format!("new {}(this.reader.buffer.slice(this.reader.offset))", resource.name)
+            .into());
+        }
+
+        let roots: Vec<usize> = ir
+            .resources
+            .iter()
+            .enumerate()
+            .filter(|(_, resource)| resource.file.as_ref() == Some(file))
+            .map(|(idx, _)| idx)
+            .collect();
+        let services: Vec<usize> = ir
+            .services
+            .iter()
+            .enumerate()
+            .filter(|(_, service)| service.file.as_ref() == Some(file))
+            .map(|(idx, _)| idx)
+            .collect();
+        dirs.push((dir, ir.subset(&roots, &services)));
+    }
+    Ok(dirs)
+}
+
+/// Output subdirectory of each input for the per-file layout: its path
+/// relative to the directory the inputs share, without the extension, so
+/// `a/user.pr` and `b/user.pr` get `a/user` and `b/user`
+fn per_file_names(files: &[PathBuf]) -> Vec<PathBuf> {
+    let paths: Vec<PathBuf> = files
+        .iter()
+        .map(|file| fs::canonicalize(file).unwrap_or_else(|_| file.clone()))
+        .collect();
+    let mut base: Vec<Component> = match paths.first().and_then(|path| path.parent()) {
+        Some(parent) => parent.components().collect(),
+        None => Vec::new(),
+    };
+    for path in &paths {
+        let parent: Vec<Component> = path.parent().map(|p| p.components().collect()).unwrap_or_default();
+        let shared = base.iter().zip(&parent).take_while(|(a, b)| a == b).count();
+        base.truncate(shared);
+    }
+
+    paths
+        .iter()
+        .map(|path| {
+            path.components()
+                .skip(base.len())
+                .filter(|component| matches!(component, Component::Normal(_)))
+                .collect::<PathBuf>()
+                .with_extension("")
+        })
+        .collect()
+}
+
+/// Write every target into the output directories, skipping files that
+/// are already up to date
+fn write_outputs(options: &CliOptions, files: &[PathBuf], ir: &IRProgram) -> Result<RebuildReport, CompileError> {
+    let mut report = RebuildReport::default();
+    for (dir, ir) in output_dirs(options, files, ir)? {
+        // Create output directory if it doesn't exist
+        fs::create_dir_all(&dir).map_err(|e| io_error("create output directory", &dir, e))?;
+
+        for target in &options.targets {
+            for (file_name, contents) in generate_target_files(&ir, *target, &options.codegen)? {
+                let path = dir.join(file_name);
+                if write_if_changed(&path, &contents)? {
+                    report.written.push(path);
+                } else {
+                    report.unchanged.push(path);
+                }
             }
         }
     }
+    Ok(report)
+}
 
-    fn ir_type_to_typescript(&self, ir_type: &IRType) -> String {
-        match ir_type {
-            IRType::Primitive(p) => match p.as_str() {
-                "string" => "string".to_string(),
-                "number" => "number".to_string(),
-                "bool" => "boolean".to_string(),
-                _ => "any".to_string(),
-            },
-            IRType::List(inner) => format!("{}[]", self.ir_type_to_typescript(inner)),
-            IRType::ResourceRef(idx) => format!("I{}", self.ir.resources[*idx].name),
+/// Generated contents of each file written for `target`, named as in
+/// `Target::file_names`
+pub fn generate_target_files(
+    ir: &IRProgram,
+    target: Target,
+    options: &CodegenOptions,
+) -> Result<Vec<(&'static str, String)>, CompileError> {
+    Ok(match target {
+        Target::TypeScript => {
+            let code = CodeGenerator::new(ir.clone()).with_options(*options).generate();
+            vec![("client.ts", code.typescript_client), ("server.ts", code.typescript_server)]
         }
-    }
+        Target::OpenApi => vec![("openapi.json", CodeGenerator::new(ir.clone()).generate_openapi())],
+        Target::Proto => vec![("schema.proto", CodeGenerator::new(ir.clone()).generate_proto()?)],
+        Target::GraphQl => vec![("schema.graphql", CodeGenerator::new(ir.clone()).generate_graphql())],
+        Target::Rust => vec![("previous.rs", CodeGenerator::new(ir.clone()).generate_rust()?)],
+    })
+}
 
-    fn ir_type_to_typescript_server(&self, ir_type: &IRType) -> String {
-        match ir_type {
-            IRType::Primitive(p) => match p.as_str() {
-                "string" => "string".to_string(),
-                "number" => "number".to_string(),
-                "bool" => "boolean".to_string(),
-                _ => "any".to_string(),
-            },
-            IRType::List(inner) => format!("{}[]", self.ir_type_to_typescript_server(inner)),
-            IRType::ResourceRef(idx) => self.ir.resources[*idx].name.clone(),
+/// Write `contents` unless the file already holds exactly that, so file
+/// watchers downstream (bundlers, dev servers) only see real changes
+///
+/// Returns whether the file was written.
+pub fn write_if_changed(path: &Path, contents: &str) -> Result<bool, CompileError> {
+    if let Ok(existing) = fs::read(path) {
+        if existing == contents.as_bytes() {
+            return Ok(false);
         }
     }
+    fs::write(path, contents).map_err(|e| io_error("write output file", path, e))?;
+    Ok(true)
+}
 
-    // ========================================================================
-    // TypeScript Server Generation
-    // ========================================================================
+/// Files touched by one compilation
+#[derive(Debug, Default)]
+pub struct RebuildReport {
+    /// Outputs whose contents changed and were rewritten
+    pub written: Vec<PathBuf>,
+    /// Outputs left alone because they were already up to date
+    pub unchanged: Vec<PathBuf>,
+}
 
-    fn generate_typescript_server(&self) -> String {
-        let mut code = String::new();
+/// Polls the schema files and recompiles them when their contents change
+///
+/// The schema language has no imports, so the input files are the only
+/// dependencies. Glob patterns are expanded on every poll, so new files are
+/// picked up. Only outputs whose contents differ are rewritten.
+pub struct SchemaWatcher {
+    options: CliOptions,
+    last_modified: Option<Vec<(PathBuf, std::time::SystemTime)>>,
+    last_sources: Option<Vec<(PathBuf, String)>>,
+    missing: bool,
+}
 
-        // Header
-        code.push_str("// Generated by Previous Compiler\n");
-        code.push_str("// DO NOT EDIT - This file is auto-generated\n\n");
+impl SchemaWatcher {
+    pub fn new(options: CliOptions) -> Self {
+        SchemaWatcher {
+            options,
+            last_modified: None,
+            last_sources: None,
+            missing: false,
+        }
+    }
 
-        // Binary writer utility class
-        code.push_str(&self.generate_binary_writer());
-        code.push_str("\n");
+    pub fn options(&self) -> &CliOptions {
+        &self.options
+    }
 
-        // Generate each resource
-        for resource in &self.ir.resources {
-            code.push_str(&self.generate_ts_server_resource(resource));
-            code.push_str("\n");
+    /// Rebuild if an input changed since the last call, the first call
+    /// always builds
+    ///
+    /// Returns `None` when there was nothing to do. A missing input is
+    /// reported once, editors often delete and recreate files on save.
+    pub fn check(&mut self) -> Option<Result<RebuildReport, CompileError>> {
+        let modified = expand_inputs(&self.options.inputs).and_then(|files| {
+            files
+                .into_iter()
+                .map(|file| match fs::metadata(&file).and_then(|m| m.modified()) {
+                    Ok(modified) => Ok((file, modified)),
+                    Err(e) => Err(io_error("read input file", &file, e).with_file(file)),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        });
+        let modified = match modified {
+            Ok(modified) => modified,
+            Err(e) => {
+                if self.missing {
+                    return None;
+                }
+                self.missing = true;
+                self.last_modified = None;
+                return Some(Err(e));
+            }
+        };
+        self.missing = false;
+
+        if self.last_modified.as_ref() == Some(&modified) {
+            return None;
         }
+        let files: Vec<PathBuf> = modified.iter().map(|(file, _)| file.clone()).collect();
+        self.last_modified = Some(modified);
 
-        code
+        let sources = match read_inputs(&files) {
+            Ok(sources) => sources,
+            Err(e) => return Some(Err(e)),
+        };
+        // Saving without edits only touches the modification time
+        if self.last_sources.as_ref() == Some(&sources) {
+            return None;
+        }
+        let result = compile_sources(&sources).and_then(|output| write_outputs(&self.options, &files, &output.ir));
+        self.last_sources = Some(sources);
+        Some(result)
     }
+}
 
-    fn generate_binary_writer(&self) -> String {
-        r#"class BinaryWriter {
-  private buffer: number[];
-
-  constructor() {
-    this.buffer = [];
-  }
+/// Compile a schema file and return the output (for testing/library use)
+pub fn compile_file_to_output(input_path: &Path) -> Result<CompiledOutput, CompileError> {
+    let schema_content = fs::read_to_string(input_path).map_err(|e| io_error("read input file", input_path, e))?;
 
-  writeString(value: string): void {
-    const encoder = new TextEncoder();
-    const bytes = encoder.encode(value);
-    this.writeU32(bytes.length);
-    this.buffer.push(...bytes);
-  }
+    compile_schema(&schema_content).map_err(|e| e.with_file(input_path.to_path_buf()))
+}
 
-  writeNumber(value: number): void {
-    const buffer = new ArrayBuffer(8);
-    const view = new DataView(buffer);
-    view.setBigInt64(0, BigInt(value), true); // little-endian
-    const bytes = new Uint8Array(buffer);
-    this.buffer.push(...bytes);
-  }
+/// Write generated code to files
+pub fn write_generated_code(
+    generated_code: &GeneratedCode,
+    output_dir: &Path,
+) -> Result<(), CompileError> {
+    // Create output directory
+    fs::create_dir_all(output_dir).map_err(|e| io_error("create output directory", output_dir, e))?;
 
-  writeBool(value: boolean): void {
-    this.buffer.push(value ? 1 : 0);
-  }
+    // Write TypeScript client
+    let ts_path = output_dir.join("client.ts");
+    fs::write(&ts_path, &generated_code.typescript_client)
+        .map_err(|e| io_error("write TypeScript client file", &ts_path, e))?;
 
-  writeU32(value: number): void {
-    const buffer = new ArrayBuffer(4);
-    const view = new DataView(buffer);
-    view.setUint32(0, value, true); // little-endian
-    const bytes = new Uint8Array(buffer);
-    this.buffer.push(...bytes);
-  }
+    // Write TypeScript server
+    let ts_server_path = output_dir.join("server.ts");
+    fs::write(&ts_server_path, &generated_code.typescript_server)
+        .map_err(|e| io_error("write TypeScript server file", &ts_server_path, e))?;
 
-  writeByte(value: number): void {
-    this.buffer.push(value & 0xFF);
-  }
+    Ok(())
+}
 
-  finish(): Uint8Array {
-    return new Uint8Array(this.buffer);
-  }
+// ============================================================================
+// PROJECT CONFIGURATION
+// ============================================================================
+//
+// A `previous.toml` at the project root pins how every schema is compiled,
+// so everyone on the team generates identical code:
+//
+//   [[schema]]
+//   input = "schemas/user.pr"
+//   targets = ["ts", "openapi"]
+//   out = "generated/{name}"          # {name} is the schema file stem
+//
+//   [[schema]]
+//   input = ["billing/*.pr", "shared.pr"]  # compiled as one program
+//   name = "billing"
+//   layout = "per-file"               # or "merged", the default
+//
+//   [targets.ts]
+//   out = "web/src/generated/{name}"  # overrides the schema's `out`
+//   module = "esm"                    # or "global"
+//   numbers = "bigint"                # or "number"
+//   naming = "camelCase"              # or "preserve"
+//
+//   [lint]
+//   deny = ["nested-list"]
+//   max-fields = 24
+//
+//   [wire]
+//   max-string-length = 1048576
+//   max-list-length = 10000
+//
+// Paths are relative to the directory holding the file.
+
+/// File name looked up by `ProjectConfig::discover`
+pub const CONFIG_FILE_NAME: &str = "previous.toml";
+
+/// Contents of a `previous.toml`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Schema entry points, each compiled on its own
+    #[serde(default, rename = "schema")]
+    pub schemas: Vec<SchemaEntry>,
+    #[serde(default)]
+    pub targets: TargetsConfig,
+    #[serde(default)]
+    pub lint: LintSettings,
+    #[serde(default)]
+    pub wire: WireOptions,
+    /// Directory the config was loaded from, relative paths start here
+    #[serde(skip)]
+    pub root: PathBuf,
 }
-"#.to_string()
-    }
 
-    fn generate_ts_server_resource(&self, resource: &IRResource) -> String {
-        let mut code = String::new();
+/// One `[[schema]]` entry point
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchemaEntry {
+    /// A schema file or a list of files and glob patterns, compiled as
+    /// one program
+    #[serde(deserialize_with = "one_or_many")]
+    pub input: Vec<PathBuf>,
+    /// Value of `{name}`, the input file stem when there is a single input
+    /// and `schema` otherwise
+    pub name: Option<String>,
+    /// Targets to generate, TypeScript when omitted
+    #[serde(default = "default_targets")]
+    pub targets: Vec<Target>,
+    /// Output directory, `{name}` expands to the entry name
+    pub out: Option<String>,
+    #[serde(default)]
+    pub layout: OutputLayout,
+}
 
-        // Interface definition
-        code.push_str(&format!("export interface I{}Builder {{\n", resource.name));
-        for field in &resource.fields {
-            let ts_type = self.ir_type_to_typescript_server(&field.field_type);
-            let optional = if field.optional || field.nullable { "?" } else { "" };
-            code.push_str(&format!("  {}{}: {};\n", field.name, optional, ts_type));
+impl SchemaEntry {
+    pub fn name(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
         }
-        code.push_str("}\n\n");
-
-        // Class definition
-        code.push_str(&format!("export class {} {{\n", resource.name));
-
-        // Private fields
-        for field in &resource.fields {
-            let ts_type = self.ir_type_to_typescript_server(&field.field_type);
-            let optional_type = if field.optional || field.nullable {
-                format!("{} | null | undefined", ts_type)
-            } else {
-                ts_type
-            };
-            code.push_str(&format!("  private _{}: {};\n", field.name, optional_type));
+        match self.input.as_slice() {
+            [single] if !single.to_string_lossy().contains(['*', '?', '[']) => single
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            _ => "schema".to_string(),
         }
-        code.push_str("\n");
+    }
+}
 
-        // Constructor
-        code.push_str("  constructor() {\n");
-        for field in &resource.fields {
-            let default = if field.optional {
-                "undefined".to_string()
-            } else if field.nullable {
-                "null".to_string()
-            } else {
-                self.ts_default_value(&field.field_type)
-            };
-            code.push_str(&format!("    this._{} = {};\n", field.name, default));
-        }
-        code.push_str("  }\n\n");
+fn default_targets() -> Vec<Target> {
+    vec![Target::TypeScript]
+}
 
-        // Setter methods (builder pattern)
-        for field in &resource.fields {
-            let ts_type = self.ir_type_to_typescript_server(&field.field_type);
-            let param_type = if field.optional || field.nullable {
-                format!("{} | null | undefined", ts_type)
-            } else {
-                ts_type.clone()
-            };
+/// Accept `input = "a.pr"` as well as `input = ["a.pr", "b/*.pr"]`
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    })
+}
 
-            code.push_str(&format!("  {}(value: {}): this {{\n", field.name, param_type));
-            code.push_str(&format!("    this._{} = value;\n", field.name));
-            code.push_str("    return this;\n");
-            code.push_str("  }\n\n");
-        }
+/// Per-target sections, `[targets.ts]`, `[targets.openapi]`, ...
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TargetsConfig {
+    #[serde(default)]
+    pub ts: TypeScriptTargetConfig,
+    #[serde(default)]
+    pub openapi: TargetOutput,
+    #[serde(default)]
+    pub proto: TargetOutput,
+    #[serde(default)]
+    pub graphql: TargetOutput,
+    #[serde(default)]
+    pub rust: TargetOutput,
+}
 
-        // Encode method
-        code.push_str("  encode(): Uint8Array {\n");
-        code.push_str("    const writer = new BinaryWriter();\n");
-        code.push_str("    this.encodeFields(writer);\n");
-        code.push_str("    return writer.finish();\n");
-        code.push_str("  }\n\n");
+/// `[targets.ts]`: output path and generator options
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TypeScriptTargetConfig {
+    pub out: Option<String>,
+    pub module: ModuleStyle,
+    pub numbers: NumberMode,
+    pub naming: NamingConvention,
+}
 
-        // encodeFields method
-        code.push_str("  private encodeFields(writer: BinaryWriter): void {\n");
-        for field in &resource.fields {
-            code.push_str(&self.generate_ts_field_encode(field));
-        }
-        code.push_str("  }\n");
+/// Output path of a target without options of its own
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetOutput {
+    pub out: Option<String>,
+}
 
-        code.push_str("}\n");
-        code
-    }
+/// `[lint]`: rule levels by name, as accepted by `previouscc lint`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct LintSettings {
+    pub allow: Vec<String>,
+    pub warn: Vec<String>,
+    pub deny: Vec<String>,
+    pub max_fields: Option<usize>,
+}
 
-    fn ts_default_value(&self, ir_type: &IRType) -> String {
-        match ir_type {
-            IRType::Primitive(p) => match p.as_str() {
-                "string" => "\"\"".to_string(),
-                "number" => "0".to_string(),
-                "bool" => "false".to_string(),
-                _ => "null".to_string(),
-            },
-            IRType::List(_) => "[]".to_string(),
-            IRType::ResourceRef(idx) => {
-                let resource = &self.ir.resources[*idx];
-                format!("new {}()", resource.name)
+/// Span of the bytes at `range` in `text`, lines and columns count from 1
+fn span_of_range(text: &str, range: std::ops::Range<usize>) -> Span {
+    let before = &text[..range.start.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map_or(0, |line| line.chars().count()) + 1;
+    Span { line, column, length: range.len().max(1) }
+}
+
+impl ProjectConfig {
+    /// Parse a config, `root` is the directory paths are relative to
+    pub fn parse(text: &str, root: &Path) -> Result<Self, CompileError> {
+        let mut config: ProjectConfig = toml::from_str(text).map_err(|e| {
+            let mut diagnostic = Diagnostic::error(DiagnosticKind::InvalidConfig { message: e.message().to_string() });
+            if let Some(range) = e.span() {
+                diagnostic = diagnostic.with_span(span_of_range(text, range));
             }
+            CompileError::from(diagnostic).with_source(text)
+        })?;
+        config.root = root.to_path_buf();
+        // Rule names are checked up front rather than on the first lint run
+        config.lint_config()?;
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, CompileError> {
+        let text = fs::read_to_string(path).map_err(|e| io_error("read config file", path, e))?;
+        let root = path.parent().unwrap_or_else(|| Path::new("."));
+        Self::parse(&text, root).map_err(|e| e.with_file(path.to_path_buf()))
+    }
+
+    /// Find `previous.toml` in `start` or the closest parent directory
+    pub fn discover(start: &Path) -> Option<PathBuf> {
+        start
+            .ancestors()
+            .map(|dir| dir.join(CONFIG_FILE_NAME))
+            .find(|path| path.is_file())
+    }
+
+    pub fn codegen_options(&self) -> CodegenOptions {
+        CodegenOptions {
+            module: self.targets.ts.module,
+            numbers: self.targets.ts.numbers,
+            naming: self.targets.ts.naming,
+            wire: self.wire,
         }
     }
 
-    fn generate_ts_field_encode(&self, field: &IRField) -> String {
-        let mut code = String::new();
-        let indent = "    ";
-
-        if field.optional {
-            // Optional field: write 1 byte flag, then value if present
-            code.push_str(&format!("{}if (this._{} === undefined) {{\n", indent, field.name));
-            code.push_str(&format!("{}  writer.writeByte(0); // absent\n", indent));
-            code.push_str(&format!("{}}} else {{\n", indent));
-            code.push_str(&format!("{}  writer.writeByte(1); // present\n", indent));
-            code.push_str(&self.generate_ts_value_write(&format!("this._{}", field.name), &field.field_type, "      "));
-            code.push_str(&format!("{}}}\n", indent));
-        } else if field.nullable {
-            // Nullable field: write 1 byte flag, then value if not null
-            code.push_str(&format!("{}if (this._{} === null || this._{} === undefined) {{\n", indent, field.name, field.name));
-            code.push_str(&format!("{}  writer.writeByte(0); // null\n", indent));
-            code.push_str(&format!("{}}} else {{\n", indent));
-            code.push_str(&format!("{}  writer.writeByte(1); // present\n", indent));
-            code.push_str(&self.generate_ts_value_write(&format!("this._{}", field.name), &field.field_type, "      "));
-            code.push_str(&format!("{}}}\n", indent));
-        } else {
-            // Required field: write value directly
-            code.push_str(&self.generate_ts_value_write(&format!("this._{}", field.name), &field.field_type, indent));
+    /// Lint levels from the `[lint]` section on top of the defaults
+    pub fn lint_config(&self) -> Result<LintConfig, CompileError> {
+        let mut config = LintConfig::default();
+        if let Some(max_fields) = self.lint.max_fields {
+            config.max_fields = max_fields;
         }
-
-        code
-    }
-
-    fn generate_ts_value_write(&self, var_name: &str, ir_type: &IRType, indent: &str) -> String {
-        let mut code = String::new();
-
-        match ir_type {
-            IRType::Primitive(p) => {
-                let write_method = match p.as_str() {
-                    "string" => "writeString",
-                    "number" => "writeNumber",
-                    "bool" => "writeBool",
-                    _ => "writeByte",
-                };
-                code.push_str(&format!("{}writer.{}({});\n", indent, write_method, var_name));
-            }
-            IRType::List(inner) => {
-                code.push_str(&format!("{}writer.writeU32({}.length);\n", indent, var_name));
-                code.push_str(&format!("{}for (const item of {}) {{\n", indent, var_name));
-                code.push_str(&self.generate_ts_value_write("item", inner, &format!("{}  ", indent)));
-                code.push_str(&format!("{}}}\n", indent));
+        let levels = [
+            (&self.lint.allow, LintLevel::Allow),
+            (&self.lint.warn, LintLevel::Warn),
+            (&self.lint.deny, LintLevel::Deny),
+        ];
+        for (names, level) in levels {
+            for name in names {
+                let rule = LintRule::from_name(name).ok_or_else(|| DiagnosticKind::InvalidConfig {
+                    message: format!("unknown lint rule '{}'", name),
+                })?;
+                config.set_level(rule, level);
             }
-            IRType::ResourceRef(_) => {
-                code.push_str(&format!("{}{}.encodeFields(writer);\n", indent, var_name));
+        }
+        Ok(config)
+    }
+
+    /// Compilations to run, one per schema and output directory
+    ///
+    /// Targets of a schema that share an output directory are compiled
+    /// together.
+    pub fn compile_options(&self) -> Vec<CliOptions> {
+        let mut runs: Vec<CliOptions> = Vec::new();
+        for schema in &self.schemas {
+            let inputs: Vec<PathBuf> = schema.input.iter().map(|input| self.root.join(input)).collect();
+            let name = schema.name();
+
+            for &target in &schema.targets {
+                let out = self
+                    .target_out(target)
+                    .or(schema.out.as_deref())
+                    .unwrap_or("generated/{name}");
+                let output_dir = self.root.join(out.replace("{name}", &name));
+
+                match runs
+                    .iter_mut()
+                    .find(|run| run.inputs == inputs && run.output_dir == output_dir)
+                {
+                    Some(run) => {
+                        if !run.targets.contains(&target) {
+                            run.targets.push(target);
+                        }
+                    }
+                    None => runs.push(CliOptions {
+                        inputs: inputs.clone(),
+                        output_dir,
+                        verbose: false,
+                        targets: vec![target],
+                        codegen: self.codegen_options(),
+                        layout: schema.layout,
+                    }),
+                }
             }
         }
-
-        code
+        runs
     }
 
-    fn capitalize_first(&self, s: &str) -> String {
-        let mut chars = s.chars();
-        match chars.next() {
-            None => String::new(),
-            Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+    fn target_out(&self, target: Target) -> Option<&str> {
+        match target {
+            Target::TypeScript => self.targets.ts.out.as_deref(),
+            Target::OpenApi => self.targets.openapi.out.as_deref(),
+            Target::Proto => self.targets.proto.out.as_deref(),
+            Target::GraphQl => self.targets.graphql.out.as_deref(),
+            Target::Rust => self.targets.rust.out.as_deref(),
         }
     }
 }
 
 // ============================================================================
-// TOKEN TYPES
+// MOCK SERVER
 // ============================================================================
+//
+// `previouscc mock` answers with random but schema-valid payloads:
+// - `GET /resources/{Name}` serves any resource, service endpoints serve
+//   their response resource (204 when they have none)
+// - a payload only depends on the seed and the request line, reruns are stable
+// - fixtures, JSON objects keyed by resource name or endpoint
+//   (`"GET /users/:id"`), replace the random payloads
+// - responses are binary unless the Accept header prefers JSON
+//
 
-#[derive(Debug, Clone, PartialEq)]
-pub enum Token {
-    // Keywords
-    Resource,
-    String,
-    Number,
-    Bool,
-    Nullable,
-    Optional,
-    Default,
-    List,
-    True,
-    False,
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
 
-    // Identifiers and literals
-    Identifier(String),
-    StringLiteral(String),
-    NumberLiteral(i64),
+/// SplitMix64, small and stable across platforms and releases
+struct MockRng(u64);
 
-    // Symbols
-    LeftBrace,
-    RightBrace,
-    LeftParen,
-    RightParen,
+impl MockRng {
+    fn new(seed: u64, request: &str) -> Self {
+        // FNV-1a of the request line, mixed into the seed
+        let hash = request
+            .bytes()
+            .fold(0xcbf2_9ce4_8422_2325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x0100_0000_01b3));
+        MockRng(seed ^ hash)
+    }
 
-    // Special
-    Eof,
-}
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
 
-// ============================================================================
-// LEXER
-// ============================================================================
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+}
 
-#[derive(Debug)]
-pub struct Lexer {
-    input: Vec<char>,
-    position: usize,
+/// Response of the mock server to one request
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockResponse {
+    pub status: u16,
+    /// None for responses without a body
+    pub content_type: Option<&'static str>,
+    pub body: Vec<u8>,
 }
 
-impl Lexer {
-    pub fn new(input: &str) -> Self {
-        Lexer {
-            input: input.chars().collect(),
-            position: 0,
+impl MockResponse {
+    fn error(status: u16, message: String) -> Self {
+        MockResponse {
+            status,
+            content_type: Some("application/json"),
+            body: json!({ "message": message }).to_string().into_bytes(),
         }
     }
+}
 
-    fn current_char(&self) -> Option<char> {
-        if self.position < self.input.len() {
-            Some(self.input[self.position])
-        } else {
-            None
-        }
-    }
+/// Serves random payloads for every resource and endpoint of a program
+pub struct MockServer {
+    ir: IRProgram,
+    seed: u64,
+    /// Fixture values by resource name or `METHOD /path`
+    fixtures: HashMap<String, Value>,
+}
 
-    #[allow(dead_code)]
-    fn peek_char(&self, offset: usize) -> Option<char> {
-        let pos = self.position + offset;
-        if pos < self.input.len() {
-            Some(self.input[pos])
-        } else {
-            None
+impl MockServer {
+    pub fn new(ir: IRProgram, seed: u64) -> Self {
+        MockServer { ir, seed, fixtures: HashMap::new() }
+    }
+
+    /// Add fixtures from a JSON object keyed by resource name or endpoint
+    pub fn add_fixtures(&mut self, fixtures: &serde_json::Value) -> Result<(), CompileError> {
+        let entries = fixtures.as_object().ok_or_else(|| {
+            CompileError::from(DiagnosticKind::TypeMismatch {
+                expected: "an object of fixtures".to_string(),
+                got: json_kind_name(fixtures).to_string(),
+            })
+        })?;
+        for (key, fixture) in entries {
+            let resource = match self.ir.get_resource_index(key) {
+                Some(idx) => idx,
+                None => self
+                    .endpoints()
+                    .find(|(_, endpoint)| format!("{} {}", endpoint.method, endpoint.path) == *key)
+                    .and_then(|(_, endpoint)| endpoint.response)
+                    .ok_or_else(|| DiagnosticKind::UndefinedType { name: key.clone() })?,
+            };
+            let value = Value::from_json(fixture.clone(), &IRType::ResourceRef(resource), &self.ir)?;
+            self.fixtures.insert(key.clone(), value);
         }
+        Ok(())
     }
 
-    fn advance(&mut self) -> Option<char> {
-        let ch = self.current_char();
-        if ch.is_some() {
-            self.position += 1;
-        }
-        ch
-    }
+    /// Read fixtures from a JSON file, see `add_fixtures`
+    pub fn load_fixtures(&mut self, path: &Path) -> Result<(), CompileError> {
+        let text = fs::read_to_string(path).map_err(|e| io_error("read fixture file", path, e))?;
+        let fixtures: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+            CompileError::from(DiagnosticKind::Io {
+                operation: "parse fixture file".to_string(),
+                path: path.to_path_buf(),
+                error: e.to_string(),
+            })
+        })?;
+        self.add_fixtures(&fixtures).map_err(|e| e.with_file(path.to_path_buf()))
+    }
+
+    fn endpoints(&self) -> impl Iterator<Item = (&IRService, &IREndpoint)> {
+        self.ir
+            .services
+            .iter()
+            .flat_map(|service| service.endpoints.iter().map(move |endpoint| (service, endpoint)))
+    }
+
+    /// Routes served, e.g. `GET /resources/User` and `GET /users/:id`
+    pub fn routes(&self) -> Vec<String> {
+        let mut routes: Vec<String> = self
+            .ir
+            .resources
+            .iter()
+            .map(|resource| format!("GET /resources/{}", resource.qualified_name()))
+            .collect();
+        routes.extend(self.endpoints().map(|(_, endpoint)| format!("{} {}", endpoint.method, endpoint.path)));
+        routes
+    }
+
+    /// Answer a request; `path` may carry a query string, which is ignored
+    pub fn respond(&self, method: &str, path: &str, accept: &str) -> MockResponse {
+        let path = path.split('?').next().unwrap_or(path);
+        let request_line = format!("{} {}", method, path);
+        let segments: Vec<&str> = path.split('/').filter(|segment| !segment.is_empty()).collect();
+
+        let endpoint = self.endpoints().map(|(_, endpoint)| endpoint).find(|endpoint| {
+            let pattern: Vec<&str> = endpoint.path.split('/').filter(|segment| !segment.is_empty()).collect();
+            endpoint.method.to_string() == method
+                && pattern.len() == segments.len()
+                && pattern.iter().zip(&segments).all(|(p, s)| p.starts_with(':') || p == s)
+        });
+
+        let (resource, fixture) = match (endpoint, segments.as_slice()) {
+            (Some(endpoint), _) => match endpoint.response {
+                Some(resource) => {
+                    let key = format!("{} {}", endpoint.method, endpoint.path);
+                    let fixture = self
+                        .fixtures
+                        .get(&key)
+                        .or_else(|| self.fixtures.get(&self.ir.resources[resource].qualified_name()));
+                    (resource, fixture)
+                }
+                None => return MockResponse { status: 204, content_type: None, body: Vec::new() },
+            },
+            (None, ["resources", name]) if method == "GET" => match self.ir.get_resource_index(name) {
+                Some(resource) => (resource, self.fixtures.get(*name)),
+                None => return MockResponse::error(404, format!("no resource named {}", name)),
+            },
+            (None, _) => return MockResponse::error(404, format!("no endpoint for {}", request_line)),
+        };
 
-    fn skip_whitespace(&mut self) {
-        while let Some(ch) = self.current_char() {
-            if ch.is_whitespace() {
-                self.advance();
-            } else {
-                break;
-            }
+        let ir_type = IRType::ResourceRef(resource);
+        let value = match fixture {
+            Some(fixture) => fixture.clone(),
+            None => self.random_value(&ir_type, &mut MockRng::new(self.seed, &request_line)),
+        };
+        if prefers_json(accept) {
+            return MockResponse {
+                status: 200,
+                content_type: Some("application/json"),
+                body: value.to_json().to_string().into_bytes(),
+            };
         }
-    }
-
-    fn read_identifier(&mut self) -> String {
-        let mut ident = String::new();
-        while let Some(ch) = self.current_char() {
-            if ch.is_alphanumeric() || ch == '_' {
-                ident.push(ch);
-                self.advance();
-            } else {
-                break;
-            }
+        let mut encoder = BinaryEncoder::new();
+        match encoder.encode_value(&value, &ir_type, &self.ir) {
+            Ok(()) => MockResponse { status: 200, content_type: Some(PREVIOUS_MEDIA_TYPE), body: encoder.finish() },
+            Err(e) => MockResponse::error(500, e.to_string()),
         }
-        ident
     }
 
-    fn read_string(&mut self) -> String {
-        let mut string = String::new();
-        self.advance(); // skip opening quote
-        while let Some(ch) = self.current_char() {
-            if ch == '"' {
-                self.advance();
-                break;
-            }
-            string.push(ch);
-            self.advance();
+    fn random_value(&self, ir_type: &IRType, rng: &mut MockRng) -> Value {
+        match ir_type {
+            IRType::Primitive(p) => match p.as_str() {
+                "string" => {
+                    let len = 3 + rng.below(8);
+                    Value::String((0..len).map(|_| (b'a' + rng.below(26) as u8) as char).collect())
+                }
+                "number" => Value::Number(rng.below(1000) as i64),
+                _ => Value::Bool(rng.below(2) == 1),
+            },
+            IRType::List(inner) => Value::List((0..rng.below(4)).map(|_| self.random_value(inner, rng)).collect()),
+            IRType::ResourceRef(idx) => Value::Resource(
+                self.ir.resources[*idx]
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        let value = if field.optional && rng.below(4) == 0 {
+                            Value::Absent
+                        } else if field.nullable && rng.below(4) == 0 {
+                            Value::Null
+                        } else {
+                            self.random_value(&field.field_type, rng)
+                        };
+                        FieldValue {
+                            name: field.name.clone(),
+                            value,
+                            is_optional: field.optional,
+                            is_nullable: field.nullable,
+                        }
+                    })
+                    .collect(),
+            ),
         }
-        string
     }
 
-    fn read_number(&mut self) -> i64 {
-        let mut num_str = String::new();
-        while let Some(ch) = self.current_char() {
-            if ch.is_ascii_digit() {
-                num_str.push(ch);
-                self.advance();
-            } else {
-                break;
+    /// Answer requests on `listener` one at a time
+    ///
+    /// A connection that can't be accepted or breaks only affects its own
+    /// request, the server keeps going.
+    pub fn serve(&self, listener: TcpListener) -> std::io::Result<()> {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let _ = self.handle_connection(stream);
+                }
+                Err(e) => {
+                    eprintln!("✗ Failed to accept a connection: {}", e);
+                    // Errors like EMFILE persist for a while, don't spin on them
+                    std::thread::sleep(std::time::Duration::from_millis(50));
+                }
             }
         }
-        num_str.parse().unwrap_or(0)
+        Ok(())
     }
 
-    pub fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    fn handle_connection(&self, stream: TcpStream) -> std::io::Result<()> {
+        stream.set_read_timeout(Some(std::time::Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
 
-        match self.current_char() {
-            None => Token::Eof,
-            Some('{') => {
-                self.advance();
-                Token::LeftBrace
-            }
-            Some('}') => {
-                self.advance();
-                Token::RightBrace
-            }
-            Some('(') => {
-                self.advance();
-                Token::LeftParen
-            }
-            Some(')') => {
-                self.advance();
-                Token::RightParen
-            }
-            Some('"') => {
-                let string = self.read_string();
-                Token::StringLiteral(string)
-            }
-            Some(ch) if ch.is_ascii_digit() => {
-                let num = self.read_number();
-                Token::NumberLiteral(num)
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or("").to_string();
+        let path = parts.next().unwrap_or("/").to_string();
+
+        let mut accept = String::new();
+        let mut content_length = 0usize;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
             }
-            Some(ch) if ch.is_alphabetic() || ch == '_' => {
-                let ident = self.read_identifier();
-                match ident.as_str() {
-                    "resource" => Token::Resource,
-                    "string" => Token::String,
-                    "number" => Token::Number,
-                    "bool" => Token::Bool,
-                    "nullable" => Token::Nullable,
-                    "optional" => Token::Optional,
-                    "default" => Token::Default,
-                    "list" => Token::List,
-                    "true" => Token::True,
-                    "false" => Token::False,
-                    _ => Token::Identifier(ident),
+            if let Some((name, value)) = line.split_once(':') {
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "accept" => accept = value.trim().to_string(),
+                    "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                    _ => {}
                 }
             }
-            Some(_) => {
-                self.advance();
-                self.next_token()
-            }
         }
+        // Request bodies are not looked at, only drained
+        std::io::copy(&mut reader.by_ref().take(content_length as u64), &mut std::io::sink())?;
+
+        let response = if method == "OPTIONS" {
+            MockResponse { status: 204, content_type: None, body: Vec::new() }
+        } else {
+            self.respond(&method, &path, &accept)
+        };
+        let reason = match response.status {
+            200 => "OK",
+            204 => "No Content",
+            404 => "Not Found",
+            _ => "Internal Server Error",
+        };
+        let mut stream = stream;
+        write!(stream, "HTTP/1.1 {} {}\r\n", response.status, reason)?;
+        if let Some(content_type) = response.content_type {
+            write!(stream, "Content-Type: {}\r\n", content_type)?;
+        }
+        write!(
+            stream,
+            "Content-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: GET, POST, PUT, PATCH, DELETE\r\nAccess-Control-Allow-Headers: *\r\nConnection: close\r\n\r\n",
+            response.body.len()
+        )?;
+        stream.write_all(&response.body)?;
+        stream.flush()
     }
 }
 
 // ============================================================================
-// PARSER
+// AXUM INTEGRATION
 // ============================================================================
+//
+// Behind the `axum` feature. Handlers take a `PreviousRequest`, which looks up
+// the service endpoint of the matched route and decodes its request body, and
+// answer with the `PreviousResponse` it builds:
+// - request bodies must be sent as application/x-previous (415 otherwise)
+// - responses are encoded with BinaryEncoder, or as JSON when the Accept
+//   header asks for application/json and not application/x-previous
+// - errors are JSON `{"message": ...}` bodies, which the TypeScript client
+//   decodes into `PreviousHttpError`
+//
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    position: usize,
-}
+#[cfg(feature = "axum")]
+pub mod axum {
+    use std::sync::Arc;
 
-impl Parser {
-    pub fn new(input: &str) -> Self {
-        let mut lexer = Lexer::new(input);
-        let mut tokens = Vec::new();
+    use ::axum::body::{Body, Bytes};
+    use ::axum::extract::{FromRef, FromRequest, MatchedPath, Request};
+    use ::axum::http::{header, HeaderMap, Method, StatusCode};
+    use ::axum::response::{IntoResponse, Response};
 
-        loop {
-            let token = lexer.next_token();
-            if token == Token::Eof {
-                tokens.push(token);
-                break;
-            }
-            tokens.push(token);
+    use super::{
+        prefers_json, BinaryDecoder, BinaryEncoder, CompileError, IREndpoint, IRProgram, IRType, Value, WireOptions,
+        PREVIOUS_MEDIA_TYPE,
+    };
+
+    /// Compiled schema handlers decode and encode against, kept in the
+    /// router state
+    #[derive(Debug, Clone)]
+    pub struct PreviousSchema {
+        ir: Arc<IRProgram>,
+        limits: WireOptions,
+        prefix: String,
+    }
+
+    impl PreviousSchema {
+        pub fn new(ir: IRProgram) -> Self {
+            PreviousSchema { ir: Arc::new(ir), limits: WireOptions::default(), prefix: String::new() }
         }
 
-        Parser {
-            tokens,
-            position: 0,
+        /// Reject request bodies with strings or lists beyond the limits
+        pub fn with_limits(mut self, limits: WireOptions) -> Self {
+            self.limits = limits;
+            self
+        }
+
+        pub fn ir(&self) -> &IRProgram {
+            &self.ir
+        }
+
+        /// Serve the endpoints under `prefix`, e.g. `/api` for a service
+        /// nested at `/api`; routes outside of it match no endpoint
+        pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+            self.prefix = prefix.into();
+            self
+        }
+
+        /// Endpoint serving `method` on `route`, the route pattern axum
+        /// matched (`/users/{id}` or `/users/:id`) with the prefix removed
+        pub fn endpoint(&self, method: &Method, route: &str) -> Option<&IREndpoint> {
+            let route = route_segments(route);
+            let route = route.strip_prefix(route_segments(&self.prefix).as_slice())?;
+            self.ir
+                .services
+                .iter()
+                .flat_map(|service| service.endpoints.iter())
+                .filter(|endpoint| endpoint.method.to_string() == method.as_str())
+                .find(|endpoint| route_segments(&endpoint.path) == route)
         }
     }
 
-    fn current_token(&self) -> &Token {
-        self.tokens.get(self.position).unwrap_or(&Token::Eof)
+    /// Path segments with every parameter replaced by `:`
+    fn route_segments(route: &str) -> Vec<&str> {
+        route
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(|segment| {
+                if segment.starts_with(':') || (segment.starts_with('{') && segment.ends_with('}')) {
+                    ":"
+                } else {
+                    segment
+                }
+            })
+            .collect()
+    }
+
+    /// Encoding of a response body
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Format {
+        Binary,
+        Json,
+    }
+
+    impl Format {
+        /// JSON when the Accept header prefers it, see `prefers_json`
+        pub fn from_headers(headers: &HeaderMap) -> Self {
+            let accept = headers
+                .get_all(header::ACCEPT)
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .collect::<Vec<_>>()
+                .join(",");
+            if prefers_json(&accept) {
+                Format::Json
+            } else {
+                Format::Binary
+            }
+        }
     }
 
-    #[allow(dead_code)]
-    fn peek_token(&self, offset: usize) -> &Token {
-        self.tokens
-            .get(self.position + offset)
-            .unwrap_or(&Token::Eof)
+    /// A request to a service endpoint, with its body decoded
+    #[derive(Debug)]
+    pub struct PreviousRequest {
+        schema: PreviousSchema,
+        endpoint: IREndpoint,
+        format: Format,
+        /// Request body resource, `None` when the endpoint takes no body
+        pub body: Option<Value>,
     }
 
-    fn advance(&mut self) -> Token {
-        let token = self.current_token().clone();
-        if self.position < self.tokens.len() {
-            self.position += 1;
+    impl PreviousRequest {
+        pub fn endpoint(&self) -> &IREndpoint {
+            &self.endpoint
         }
-        token
-    }
 
-    fn expect(&mut self, expected: Token) -> Result<(), String> {
-        let current = self.current_token();
-        let matches = match (&expected, current) {
-            (Token::Resource, Token::Resource) => true,
-            (Token::LeftBrace, Token::LeftBrace) => true,
-            (Token::RightBrace, Token::RightBrace) => true,
-            (Token::LeftParen, Token::LeftParen) => true,
-            (Token::RightParen, Token::RightParen) => true,
-            _ => std::mem::discriminant(&expected) == std::mem::discriminant(current),
-        };
-        if matches {
-            self.advance();
-            Ok(())
-        } else {
-            Err(format!("Expected {:?}, got {:?}", expected, current))
+        /// Format the response will be encoded in
+        pub fn format(&self) -> Format {
+            self.format
         }
-    }
 
-    pub fn parse(&mut self) -> Result<Program, String> {
-        let mut resources = Vec::new();
+        /// Answer with `value` as the endpoint's response resource
+        pub fn respond(&self, value: Value) -> PreviousResponse {
+            let resource = match self.endpoint.response {
+                Some(resource) => resource,
+                None => {
+                    return PreviousResponse::error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        format!("{} {} has no response resource", self.endpoint.method, self.endpoint.path),
+                    )
+                }
+            };
 
-        while self.current_token() != &Token::Eof {
-            let resource = self.parse_resource()?;
-            resources.push(resource);
+            let mut encoder = BinaryEncoder::new();
+            if let Err(e) = encoder.encode_value(&value, &IRType::ResourceRef(resource), self.schema.ir()) {
+                return PreviousResponse::error(StatusCode::INTERNAL_SERVER_ERROR, messages(&e));
+            }
+            match self.format {
+                Format::Binary => PreviousResponse::new(StatusCode::OK, PREVIOUS_MEDIA_TYPE, encoder.finish()),
+                Format::Json => PreviousResponse::new(
+                    StatusCode::OK,
+                    "application/json",
+                    serde_json::to_vec(&value.to_json()).unwrap_or_default(),
+                ),
+            }
         }
 
-        Ok(Program { resources })
+        /// Answer an endpoint without a response resource
+        pub fn no_content(&self) -> PreviousResponse {
+            PreviousResponse { status: StatusCode::NO_CONTENT, content_type: None, body: Vec::new() }
+        }
     }
 
-    fn parse_resource(&mut self) -> Result<Resource, String> {
-        self.expect(Token::Resource)?;
+    impl<S> FromRequest<S> for PreviousRequest
+    where
+        PreviousSchema: FromRef<S>,
+        S: Send + Sync,
+    {
+        type Rejection = PreviousResponse;
 
-        let name = match self.advance() {
-            Token::Identifier(id) => id,
-            _ => return Err("Expected resource name".to_string()),
-        };
+        async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+            let schema = PreviousSchema::from_ref(state);
+            let format = Format::from_headers(req.headers());
+
+            let route = match req.extensions().get::<MatchedPath>() {
+                Some(route) => route.as_str().to_string(),
+                None => req.uri().path().to_string(),
+            };
+            let endpoint = schema.endpoint(req.method(), &route).cloned().ok_or_else(|| {
+                PreviousResponse::error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    format!("no service endpoint for {} {}", req.method(), route),
+                )
+            })?;
+
+            let body = match endpoint.request {
+                None => None,
+                Some(resource) => {
+                    let content_type = req
+                        .headers()
+                        .get(header::CONTENT_TYPE)
+                        .and_then(|value| value.to_str().ok())
+                        .unwrap_or("");
+                    // Parameters such as `; charset=binary` don't change the media type
+                    let media_type = content_type.split(';').next().unwrap_or("").trim();
+                    if !media_type.eq_ignore_ascii_case(PREVIOUS_MEDIA_TYPE) {
+                        return Err(PreviousResponse::error(
+                            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                            format!("expected Content-Type {}", PREVIOUS_MEDIA_TYPE),
+                        ));
+                    }
+                    let bytes = Bytes::from_request(req, state)
+                        .await
+                        .map_err(|rejection| PreviousResponse::error(rejection.status(), rejection.body_text()))?;
+
+                    let mut decoder = BinaryDecoder::new(&bytes).with_limits(schema.limits);
+                    let value = decoder
+                        .decode_value(&IRType::ResourceRef(resource), schema.ir())
+                        .and_then(|value| decoder.finish().map(|_| value))
+                        .map_err(|e| PreviousResponse::error(StatusCode::BAD_REQUEST, messages(&e)))?;
+                    Some(value)
+                }
+            };
 
-        // Validate PascalCase
-        if !name.chars().next().unwrap().is_uppercase() {
-            return Err(format!("Resource name must be PascalCase: {}", name));
+            Ok(PreviousRequest { schema, endpoint, format, body })
         }
+    }
 
-        self.expect(Token::LeftBrace)?;
+    fn messages(error: &CompileError) -> String {
+        error.diagnostics.iter().map(|d| d.message()).collect::<Vec<_>>().join("; ")
+    }
 
-        let mut fields = Vec::new();
-        let mut index = 0;
+    /// Encoded response body with its status and Content-Type
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PreviousResponse {
+        pub status: StatusCode,
+        /// None for responses without a body
+        pub content_type: Option<&'static str>,
+        pub body: Vec<u8>,
+    }
 
-        while self.current_token() != &Token::RightBrace && self.current_token() != &Token::Eof {
-            let field = self.parse_field(index)?;
-            fields.push(field);
-            index += 1;
+    impl PreviousResponse {
+        pub fn new(status: StatusCode, content_type: &'static str, body: Vec<u8>) -> Self {
+            PreviousResponse { status, content_type: Some(content_type), body }
         }
 
-        self.expect(Token::RightBrace)?;
+        /// JSON `{"message": ...}` error body
+        pub fn error(status: StatusCode, message: impl Into<String>) -> Self {
+            let body = serde_json::json!({ "message": message.into() });
+            PreviousResponse::new(status, "application/json", serde_json::to_vec(&body).unwrap_or_default())
+        }
 
-        Ok(Resource { name, fields })
+        pub fn with_status(mut self, status: StatusCode) -> Self {
+            self.status = status;
+            self
+        }
     }
 
-    fn parse_field(&mut self, index: usize) -> Result<Field, String> {
-        let mut nullable = false;
-        let mut optional = false;
-        let mut default = None;
-
-        // Parse attributes
-        loop {
-            match self.current_token() {
-                Token::Nullable => {
-                    nullable = true;
-                    self.advance();
-                }
-                Token::Optional => {
-                    optional = true;
-                    self.advance();
-                }
-                Token::Default => {
-                    self.advance();
-                    self.expect(Token::LeftParen)?;
-                    let literal = self.parse_literal()?;
-                    self.expect(Token::RightParen)?;
-                    default = Some(DefaultValue { value: literal });
-                }
-                _ => break,
+    impl IntoResponse for PreviousResponse {
+        fn into_response(self) -> Response {
+            match self.content_type {
+                Some(content_type) => (self.status, [(header::CONTENT_TYPE, content_type)], self.body).into_response(),
+                // A bare Vec<u8> would be labelled application/octet-stream
+                None => (self.status, Body::from(self.body)).into_response(),
             }
         }
+    }
+}
 
-        // Parse type
-        let field_type = self.parse_type()?;
+// ============================================================================
+// SERDE INTEGRATION
+// ============================================================================
+//
+// Behind the `serde-wire` feature. `to_bytes` and `from_bytes` write and read
+// the layout BinaryEncoder produces, straight from serde types:
+// - integers are numbers (i64), `String`/`&str`/`char` are strings, `bool`
+//   is a bool and sequences (`Vec`, slices) are lists
+// - structs and tuples are resources, their fields in declaration order,
+//   which must be the order of the schema's fields
+// - `Option<T>` is one presence flag, so it stands for an optional or a
+//   nullable field; an optional nullable field is `Option<Option<T>>`
+// The format is not self-describing: maps, enums, floats and byte strings
+// have no Previous type and are rejected.
+//
 
-        // Parse identifier
-        let name = match self.advance() {
-            Token::Identifier(id) => id,
-            _ => return Err("Expected field name".to_string()),
-        };
+#[cfg(feature = "serde-wire")]
+pub mod serde_wire {
+    use serde::de::{self, DeserializeSeed, SeqAccess, Visitor};
+    use serde::ser::{self, Serialize};
 
-        Ok(Field {
-            name,
-            field_type,
-            nullable,
-            optional,
-            default,
-            index,
-        })
+    use super::{BinaryDecoder, BinaryEncoder, CompileError, DiagnosticKind, WireOptions};
+
+    /// Encode `value` in the Previous binary format
+    pub fn to_bytes<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, CompileError> {
+        let mut serializer = Serializer::new();
+        value.serialize(&mut serializer)?;
+        Ok(serializer.finish())
     }
 
-    fn parse_type(&mut self) -> Result<ASTType, String> {
-        match self.current_token() {
-            Token::String => {
-                self.advance();
-                Ok(ASTType::Primitive("string".to_string()))
-            }
-            Token::Number => {
-                self.advance();
-                Ok(ASTType::Primitive("number".to_string()))
-            }
-            Token::Bool => {
-                self.advance();
-                Ok(ASTType::Primitive("bool".to_string()))
-            }
-            Token::List => {
-                self.advance();
-                let inner_type = self.parse_type()?;
-                Ok(ASTType::List(Box::new(inner_type)))
-            }
-            Token::Identifier(name) => {
-                let name = name.clone();
-                self.advance();
-                Ok(ASTType::Named(name))
-            }
-            _ => Err(format!("Expected type, got {:?}", self.current_token())),
+    /// Decode a `T` from the Previous binary format, the whole buffer must be
+    /// consumed
+    pub fn from_bytes<'de, T: de::Deserialize<'de>>(bytes: &'de [u8]) -> Result<T, CompileError> {
+        let mut deserializer = Deserializer::new(bytes);
+        let value = T::deserialize(&mut deserializer).map_err(|e| deserializer.locate(e))?;
+        deserializer.finish()?;
+        Ok(value)
+    }
+
+    // `custom` errors are raised by serde without access to the offset, they
+    // are given this one and located by `Deserializer::locate`
+    const UNKNOWN_OFFSET: usize = usize::MAX;
+
+    impl ser::Error for CompileError {
+        fn custom<T: std::fmt::Display>(msg: T) -> Self {
+            DiagnosticKind::Serialize { message: msg.to_string() }.into()
         }
     }
 
-    fn parse_literal(&mut self) -> Result<Literal, String> {
-        match self.current_token() {
-            Token::StringLiteral(s) => {
-                let s = s.clone();
-                self.advance();
-                Ok(Literal::String(s))
-            }
-            Token::NumberLiteral(n) => {
-                let n = *n;
-                self.advance();
-                Ok(Literal::Number(n))
-            }
-            Token::True => {
-                self.advance();
-                Ok(Literal::Bool(true))
-            }
-            Token::False => {
-                self.advance();
-                Ok(Literal::Bool(false))
-            }
-            _ => Err(format!("Expected literal, got {:?}", self.current_token())),
+    impl de::Error for CompileError {
+        fn custom<T: std::fmt::Display>(msg: T) -> Self {
+            DiagnosticKind::Decode { offset: UNKNOWN_OFFSET, reason: msg.to_string() }.into()
         }
     }
-}
 
-// ============================================================================
-// COMPILER
-// ============================================================================
+    fn unsupported(got: &str) -> CompileError {
+        DiagnosticKind::TypeMismatch {
+            expected: "a string, number, bool, list or resource".to_string(),
+            got: got.to_string(),
+        }
+        .into()
+    }
 
-#[allow(dead_code)]
-pub struct Compiler {
-    program: Program,
-}
+    /// Serializer writing through a `BinaryEncoder`
+    #[derive(Default)]
+    pub struct Serializer {
+        encoder: BinaryEncoder,
+    }
 
-impl Compiler {
-    pub fn new(program: Program) -> Result<Self, String> {
-        // Validate uniqueness of resource names
-        let mut resource_names = std::collections::HashSet::new();
-        for resource in &program.resources {
-            if !resource_names.insert(resource.name.clone()) {
-                return Err(format!("Duplicate resource name: {}", resource.name));
-            }
+    impl Serializer {
+        pub fn new() -> Self {
+            Serializer { encoder: BinaryEncoder::new() }
         }
 
-        // Validate uniqueness of field names within each resource
-        for resource in &program.resources {
-            let mut field_names = std::collections::HashSet::new();
-            for field in &resource.fields {
-                if !field_names.insert(field.name.clone()) {
-                    return Err(format!(
-                        "Duplicate field name in {}: {}",
-                        resource.name, field.name
-                    ));
-                }
-            }
+        /// Get the encoded bytes
+        pub fn finish(self) -> Vec<u8> {
+            self.encoder.finish()
         }
+    }
 
-        Ok(Compiler { program })
+    /// List being serialized, its count is written once the items are
+    pub struct ListSerializer<'a> {
+        serializer: &'a mut Serializer,
+        count_at: usize,
+        count: u32,
     }
 
-    pub fn compile(&self) -> Result<CompiledOutput, String> {
-        // 1. Validate AST (already done in new())
+    impl<'a> ser::Serializer for &'a mut Serializer {
+        type Ok = ();
+        type Error = CompileError;
+        type SerializeSeq = ListSerializer<'a>;
+        type SerializeTuple = Self;
+        type SerializeTupleStruct = Self;
+        type SerializeTupleVariant = ser::Impossible<(), CompileError>;
+        type SerializeMap = ser::Impossible<(), CompileError>;
+        type SerializeStruct = Self;
+        type SerializeStructVariant = ser::Impossible<(), CompileError>;
 
-        // 2. Type resolution
-        let resolver = TypeResolver::new(&self.program)?;
-        let ir = resolver.resolve(self.program.clone())?;
+        fn serialize_bool(self, v: bool) -> Result<(), CompileError> {
+            self.encoder.encode_bool(v);
+            Ok(())
+        }
 
-        // 3. Cycle detection
-        let cycle_detector = CycleDetector::build(&ir)?;
-        cycle_detector.detect()?;
+        fn serialize_i8(self, v: i8) -> Result<(), CompileError> {
+            self.serialize_i64(v.into())
+        }
 
-        // 4. Code generation
-        let code_generator = CodeGenerator::new(ir.clone());
-        let generated_code = code_generator.generate();
+        fn serialize_i16(self, v: i16) -> Result<(), CompileError> {
+            self.serialize_i64(v.into())
+        }
 
-        // 5. Return compiled output with IR and generated code
-        Ok(CompiledOutput {
-            ir,
-            generated_code,
-        })
-    }
-}
+        fn serialize_i32(self, v: i32) -> Result<(), CompileError> {
+            self.serialize_i64(v.into())
+        }
 
-// ============================================================================
-// TYPE RESOLVER
-// ============================================================================
+        fn serialize_i64(self, v: i64) -> Result<(), CompileError> {
+            self.encoder.encode_number(v);
+            Ok(())
+        }
 
-pub struct TypeResolver {
-    resource_map: std::collections::HashMap<String, usize>,
-}
+        fn serialize_u8(self, v: u8) -> Result<(), CompileError> {
+            self.serialize_i64(v.into())
+        }
 
-impl TypeResolver {
-    /// Build a type resolver from an AST program
-    ///
-    /// Creates a mapping of resource names to their indices for fast lookup
-    /// during type resolution.
-    pub fn new(program: &Program) -> Result<Self, String> {
-        let mut resource_map = std::collections::HashMap::new();
+        fn serialize_u16(self, v: u16) -> Result<(), CompileError> {
+            self.serialize_i64(v.into())
+        }
 
-        for (index, resource) in program.resources.iter().enumerate() {
-            if resource_map.insert(resource.name.clone(), index).is_some() {
-                // This shouldn't happen because Compiler::new validates uniqueness
-                return Err(format!("Duplicate resource name: {}", resource.name));
-            }
+        fn serialize_u32(self, v: u32) -> Result<(), CompileError> {
+            self.serialize_i64(v.into())
         }
 
-        Ok(TypeResolver { resource_map })
-    }
+        fn serialize_u64(self, v: u64) -> Result<(), CompileError> {
+            let v = i64::try_from(v).map_err(|_| unsupported(&format!("{} (out of range for a number)", v)))?;
+            self.serialize_i64(v)
+        }
 
-    /// Resolve a single AST type to an IR type
-    ///
-    /// Converts:
-    /// - ASTType::Primitive(s) → IRType::Primitive(s)
-    /// - ASTType::Named(s) → IRType::ResourceRef(index) or error
-    /// - ASTType::List(inner) → IRType::List(resolved_inner)
-    fn resolve_type(&self, ast_type: &ASTType) -> Result<IRType, String> {
-        match ast_type {
-            ASTType::Primitive(name) => {
-                // Validate it's one of the three primitives
-                match name.as_str() {
-                    "string" | "number" | "bool" => Ok(IRType::Primitive(name.clone())),
-                    _ => Err(format!("Invalid primitive type: {}", name)),
-                }
-            }
-            ASTType::Named(name) => {
-                // Look up the resource name
-                match self.resource_map.get(name) {
-                    Some(&index) => Ok(IRType::ResourceRef(index)),
-                    None => Err(format!("Undefined type: {}", name)),
-                }
-            }
-            ASTType::List(inner) => {
-                // Recursively resolve the inner type
-                let resolved_inner = self.resolve_type(inner)?;
-                Ok(IRType::List(Box::new(resolved_inner)))
-            }
+        fn serialize_f32(self, _v: f32) -> Result<(), CompileError> {
+            Err(unsupported("f32"))
         }
-    }
 
-    /// Transform an entire AST program to an IR program
-    ///
-    /// Converts all field types from AST to IR, preserving all field attributes.
-    pub fn resolve(&self, program: Program) -> Result<IRProgram, String> {
-        let mut ir_resources = Vec::new();
+        fn serialize_f64(self, _v: f64) -> Result<(), CompileError> {
+            Err(unsupported("f64"))
+        }
 
-        for ast_resource in program.resources {
-            let mut ir_fields = Vec::new();
+        fn serialize_char(self, v: char) -> Result<(), CompileError> {
+            self.serialize_str(v.encode_utf8(&mut [0u8; 4]))
+        }
 
-            for ast_field in ast_resource.fields {
-                let resolved_type = self.resolve_type(&ast_field.field_type)?;
-                ir_fields.push(IRField {
-                    name: ast_field.name,
-                    field_type: resolved_type,
-                    nullable: ast_field.nullable,
-                    optional: ast_field.optional,
-                    default: ast_field.default,
-                    index: ast_field.index,
-                });
-            }
+        fn serialize_str(self, v: &str) -> Result<(), CompileError> {
+            self.encoder.encode_string(v);
+            Ok(())
+        }
 
-            ir_resources.push(IRResource {
-                name: ast_resource.name,
-                fields: ir_fields,
-            });
+        fn serialize_bytes(self, _v: &[u8]) -> Result<(), CompileError> {
+            Err(unsupported("bytes"))
         }
 
-        Ok(IRProgram {
-            resources: ir_resources,
-        })
-    }
-}
+        fn serialize_none(self) -> Result<(), CompileError> {
+            self.encoder.buffer.push(0x00); // absent or null
+            Ok(())
+        }
 
-// ============================================================================
-// CYCLE DETECTOR
-// ============================================================================
+        fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), CompileError> {
+            self.encoder.buffer.push(0x01); // present
+            value.serialize(self)
+        }
 
-pub struct CycleDetector {
-    graph: Vec<Vec<usize>>,
-    resource_names: Vec<String>,
-}
+        fn serialize_unit(self) -> Result<(), CompileError> {
+            Ok(())
+        }
 
-impl CycleDetector {
-    /// Build a dependency graph from the IR program
-    ///
-    /// Creates an adjacency list where each node represents a resource
-    /// and edges represent references to other resources.
-    pub fn build(ir: &IRProgram) -> Result<Self, String> {
-        let mut graph = vec![Vec::new(); ir.resources.len()];
+        fn serialize_unit_struct(self, _name: &'static str) -> Result<(), CompileError> {
+            Ok(())
+        }
 
-        // For each resource and its fields, collect all resource references
-        for (res_idx, resource) in ir.resources.iter().enumerate() {
-            for field in &resource.fields {
-                Self::collect_refs(res_idx, &field.field_type, &mut graph);
-            }
+        fn serialize_unit_variant(self, name: &'static str, _index: u32, variant: &'static str) -> Result<(), CompileError> {
+            Err(unsupported(&format!("enum {}::{}", name, variant)))
         }
 
-        // Extract resource names for error reporting
-        let resource_names: Vec<String> = ir.resources.iter().map(|r| r.name.clone()).collect();
+        fn serialize_newtype_struct<T: Serialize + ?Sized>(self, _name: &'static str, value: &T) -> Result<(), CompileError> {
+            value.serialize(self)
+        }
 
-        Ok(CycleDetector {
-            graph,
-            resource_names,
-        })
-    }
+        fn serialize_newtype_variant<T: Serialize + ?Sized>(
+            self,
+            name: &'static str,
+            _index: u32,
+            variant: &'static str,
+            _value: &T,
+        ) -> Result<(), CompileError> {
+            Err(unsupported(&format!("enum {}::{}", name, variant)))
+        }
 
-    /// Helper: extract all resource references from a type recursively
-    ///
-    /// - Primitive types: no references
-    /// - ResourceRef: add edge from current resource to referenced resource
-    /// - List: recursively process inner type
-    fn collect_refs(from_idx: usize, ir_type: &IRType, graph: &mut Vec<Vec<usize>>) {
-        match ir_type {
-            IRType::Primitive(_) => {
-                // No resource references in primitive types
-            }
-            IRType::ResourceRef(to_idx) => {
-                // Add edge: from_idx → to_idx
-                graph[from_idx].push(*to_idx);
-            }
-            IRType::List(inner) => {
-                // Recursively process list inner type
-                Self::collect_refs(from_idx, inner, graph);
-            }
+        fn serialize_seq(self, _len: Option<usize>) -> Result<ListSerializer<'a>, CompileError> {
+            let count_at = self.encoder.buffer.len();
+            self.encoder.buffer.extend_from_slice(&[0; 4]);
+            Ok(ListSerializer { serializer: self, count_at, count: 0 })
+        }
+
+        fn serialize_tuple(self, _len: usize) -> Result<Self, CompileError> {
+            Ok(self)
         }
-    }
 
-    /// Detect cycles in the resource dependency graph
-    ///
-    /// Uses depth-first search with recursion stack tracking.
-    /// If a node is encountered that's already in the current recursion stack,
-    /// a cycle has been found.
-    pub fn detect(&self) -> Result<(), String> {
-        let n = self.graph.len();
-        let mut visited = vec![false; n];
-        let mut rec_stack = vec![false; n];
-        let mut path = Vec::new();
+        fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self, CompileError> {
+            Ok(self)
+        }
 
-        for i in 0..n {
-            if !visited[i] {
-                self.dfs(i, &mut visited, &mut rec_stack, &mut path)?;
-            }
+        fn serialize_tuple_variant(
+            self,
+            name: &'static str,
+            _index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeTupleVariant, CompileError> {
+            Err(unsupported(&format!("enum {}::{}", name, variant)))
         }
 
-        Ok(())
+        fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, CompileError> {
+            Err(unsupported("map"))
+        }
+
+        fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self, CompileError> {
+            Ok(self)
+        }
+
+        fn serialize_struct_variant(
+            self,
+            name: &'static str,
+            _index: u32,
+            variant: &'static str,
+            _len: usize,
+        ) -> Result<Self::SerializeStructVariant, CompileError> {
+            Err(unsupported(&format!("enum {}::{}", name, variant)))
+        }
     }
 
-    /// Depth-first search for cycle detection
-    ///
-    /// Maintains:
-    /// - visited: tracks nodes we've processed
-    /// - rec_stack: tracks nodes in the current path (to detect back edges)
-    /// - path: tracks the current traversal path for error messages
-    fn dfs(
-        &self,
-        node: usize,
-        visited: &mut Vec<bool>,
-        rec_stack: &mut Vec<bool>,
-        path: &mut Vec<usize>,
-    ) -> Result<(), String> {
-        // Mark as visited and in current recursion path
-        visited[node] = true;
-        rec_stack[node] = true;
-        path.push(node);
+    impl ser::SerializeSeq for ListSerializer<'_> {
+        type Ok = ();
+        type Error = CompileError;
 
-        // Visit all neighbors
-        for &neighbor in &self.graph[node] {
-            if !visited[neighbor] {
-                // Unvisited neighbor: recurse
-                self.dfs(neighbor, visited, rec_stack, path)?;
-            } else if rec_stack[neighbor] {
-                // Neighbor is in current path: found a cycle!
-                // Extract the cycle from the path
-                let cycle_start = path.iter().position(|&n| n == neighbor).unwrap();
-                let cycle_path = &path[cycle_start..];
+        fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CompileError> {
+            self.count += 1;
+            value.serialize(&mut *self.serializer)
+        }
 
-                // Convert node indices to names
-                let cycle_names: Vec<String> = cycle_path
-                    .iter()
-                    .map(|&idx| self.resource_names[idx].clone())
-                    .collect();
+        fn end(self) -> Result<(), CompileError> {
+            let buffer = &mut self.serializer.encoder.buffer;
+            buffer[self.count_at..self.count_at + 4].copy_from_slice(&self.count.to_le_bytes());
+            Ok(())
+        }
+    }
 
-                // Format error message: A → B → C → A
-                let mut msg = cycle_names.join(" → ");
-                msg.push_str(" → ");
-                msg.push_str(&self.resource_names[neighbor]);
+    impl ser::SerializeTuple for &mut Serializer {
+        type Ok = ();
+        type Error = CompileError;
 
-                return Err(format!("Cyclic dependency detected: {}", msg));
-            }
+        fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CompileError> {
+            value.serialize(&mut **self)
         }
 
-        // Backtrack: remove from current path
-        path.pop();
-        rec_stack[node] = false;
-        Ok(())
+        fn end(self) -> Result<(), CompileError> {
+            Ok(())
+        }
     }
-}
 
-#[derive(Debug)]
-pub struct CompiledOutput {
-    pub ir: IRProgram,
-    pub generated_code: GeneratedCode,
-}
+    impl ser::SerializeTupleStruct for &mut Serializer {
+        type Ok = ();
+        type Error = CompileError;
 
-impl CompiledOutput {
-    pub fn new() -> Self {
-        CompiledOutput {
-            ir: IRProgram {
-                resources: Vec::new(),
-            },
-            generated_code: GeneratedCode {
-                typescript_client: String::new(),
-                typescript_server: String::new(),
-            },
+        fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), CompileError> {
+            value.serialize(&mut **self)
         }
-    }
-}
 
-// ============================================================================
-// PUBLIC API
-// ============================================================================
+        fn end(self) -> Result<(), CompileError> {
+            Ok(())
+        }
+    }
 
-pub fn run() {
-    println!("Previous Compiler v0.1.0");
-}
+    impl ser::SerializeStruct for &mut Serializer {
+        type Ok = ();
+        type Error = CompileError;
 
-pub fn parse_schema(input: &str) -> Result<Program, String> {
-    let mut parser = Parser::new(input);
-    parser.parse()
-}
+        fn serialize_field<T: Serialize + ?Sized>(&mut self, _key: &'static str, value: &T) -> Result<(), CompileError> {
+            value.serialize(&mut **self)
+        }
 
-pub fn compile_schema(input: &str) -> Result<CompiledOutput, String> {
-    let program = parse_schema(input)?;
-    let compiler = Compiler::new(program)?;
-    compiler.compile()
-}
+        fn skip_field(&mut self, key: &'static str) -> Result<(), CompileError> {
+            // Every field has a place in the layout, skipping one would shift the rest
+            Err(unsupported(&format!("skipped field `{}`", key)))
+        }
 
-// ============================================================================
-// CLI & FILE I/O (Phase 5)
-// ============================================================================
+        fn end(self) -> Result<(), CompileError> {
+            Ok(())
+        }
+    }
 
-use std::fs;
-use std::path::{Path, PathBuf};
+    /// Deserializer reading through a `BinaryDecoder`, strings are borrowed
+    /// from the buffer when the target type allows it
+    pub struct Deserializer<'de> {
+        decoder: BinaryDecoder<'de>,
+    }
 
-/// CLI options for the Previous compiler
-#[derive(Debug, Clone)]
-pub struct CliOptions {
-    pub input_file: PathBuf,
-    pub output_dir: PathBuf,
-    pub verbose: bool,
-}
+    impl<'de> Deserializer<'de> {
+        pub fn new(bytes: &'de [u8]) -> Self {
+            Deserializer { decoder: BinaryDecoder::new(bytes) }
+        }
 
-impl Default for CliOptions {
-    fn default() -> Self {
-        CliOptions {
-            input_file: PathBuf::from("schema.pr"),
-            output_dir: PathBuf::from("./generated"),
-            verbose: false,
+        /// Reject strings and lists longer than the limits, before allocating
+        pub fn with_limits(mut self, limits: WireOptions) -> Self {
+            self.decoder = self.decoder.with_limits(limits);
+            self
         }
-    }
-}
 
-/// Compile a schema file and write generated code to files
-pub fn compile_file(options: &CliOptions) -> Result<(), String> {
-    // Read the input file
-    let schema_content = fs::read_to_string(&options.input_file)
-        .map_err(|e| format!("Failed to read input file '{}': {}", options.input_file.display(), e))?;
+        /// Check the whole buffer was consumed
+        pub fn finish(self) -> Result<(), CompileError> {
+            self.decoder.finish()
+        }
 
-    if options.verbose {
-        eprintln!("Reading schema from: {}", options.input_file.display());
+        /// Give `custom` errors the offset decoding stopped at
+        fn locate(&self, mut error: CompileError) -> CompileError {
+            for diagnostic in &mut error.diagnostics {
+                if let DiagnosticKind::Decode { offset, .. } = &mut diagnostic.kind {
+                    if *offset == UNKNOWN_OFFSET {
+                        *offset = self.decoder.offset();
+                    }
+                }
+            }
+            error
+        }
     }
 
-    // Compile the schema
-    let output = compile_schema(&schema_content)?;
-
-    if options.verbose {
-        eprintln!("Compilation successful!");
-        eprintln!("  Resources: {}", output.ir.resources.len());
-        eprintln!("  TypeScript Client lines: {}", output.generated_code.typescript_client.lines().count());
-        eprintln!("  TypeScript Server lines: {}", output.generated_code.typescript_server.lines().count());
+    /// Items of a list, or fields of a resource
+    struct Items<'a, 'de> {
+        deserializer: &'a mut Deserializer<'de>,
+        remaining: usize,
+        /// Items read so far that took no bytes, e.g. `()`
+        empty: u32,
     }
 
-    // Create output directory if it doesn't exist
-    fs::create_dir_all(&options.output_dir)
-        .map_err(|e| format!("Failed to create output directory '{}': {}", options.output_dir.display(), e))?;
+    impl<'de> SeqAccess<'de> for Items<'_, 'de> {
+        type Error = CompileError;
 
-    // Write TypeScript client
-    let ts_path = options.output_dir.join("client.ts");
-    fs::write(&ts_path, &output.generated_code.typescript_client)
-        .map_err(|e| format!("Failed to write TypeScript file '{}': {}", ts_path.display(), e))?;
+        fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, CompileError> {
+            if self.remaining == 0 {
+                return Ok(None);
+            }
+            self.remaining -= 1;
+            let start = self.deserializer.decoder.offset();
+            let value = seed.deserialize(&mut *self.deserializer)?;
+            // Serde types have no known size, so the count can only be bounded
+            // by the payload once items are seen to take bytes
+            if self.deserializer.decoder.offset() == start {
+                self.empty += 1;
+                let max = self.deserializer.decoder.max_empty_items();
+                if self.empty > max {
+                    return Err(self.deserializer.decoder.error(format!("list of more than {} empty items", max)));
+                }
+            }
+            Ok(Some(value))
+        }
 
-    if options.verbose {
-        eprintln!("  Generated: {}", ts_path.display());
+        fn size_hint(&self) -> Option<usize> {
+            Some(self.remaining)
+        }
     }
 
-    // Write TypeScript server
-    let ts_server_path = options.output_dir.join("server.ts");
-    fs::write(&ts_server_path, &output.generated_code.typescript_server)
-        .map_err(|e| format!("Failed to write TypeScript server file '{}': {}", ts_server_path.display(), e))?;
+    macro_rules! deserialize_number {
+        ($($method:ident)*) => {
+            $(
+                fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+                    visitor.visit_i64(self.decoder.decode_number()?)
+                }
+            )*
+        };
+    }
 
-    if options.verbose {
-        eprintln!("  Generated: {}", ts_server_path.display());
+    macro_rules! deserialize_unsupported {
+        ($($method:ident: $what:expr,)*) => {
+            $(
+                fn $method<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, CompileError> {
+                    Err(unsupported($what))
+                }
+            )*
+        };
     }
 
-    Ok(())
-}
+    impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
+        type Error = CompileError;
 
-/// Compile a schema file and return the output (for testing/library use)
-pub fn compile_file_to_output(input_path: &Path) -> Result<CompiledOutput, String> {
-    let schema_content = fs::read_to_string(input_path)
-        .map_err(|e| format!("Failed to read input file '{}': {}", input_path.display(), e))?;
+        deserialize_number!(
+            deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64
+            deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        );
 
-    compile_schema(&schema_content)
-}
+        deserialize_unsupported!(
+            deserialize_any: "a value of unknown type, the format is not self-describing",
+            deserialize_f32: "f32",
+            deserialize_f64: "f64",
+            deserialize_bytes: "bytes",
+            deserialize_byte_buf: "bytes",
+            deserialize_map: "map",
+            deserialize_identifier: "an identifier",
+            deserialize_ignored_any: "an ignored value",
+        );
 
-/// Write generated code to files
-pub fn write_generated_code(
-    generated_code: &GeneratedCode,
-    output_dir: &Path,
-) -> Result<(), String> {
-    // Create output directory
-    fs::create_dir_all(output_dir)
-        .map_err(|e| format!("Failed to create output directory '{}': {}", output_dir.display(), e))?;
+        fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            visitor.visit_bool(self.decoder.decode_bool()?)
+        }
 
-    // Write TypeScript client
-    let ts_path = output_dir.join("client.ts");
-    fs::write(&ts_path, &generated_code.typescript_client)
-        .map_err(|e| format!("Failed to write TypeScript client file: {}", e))?;
+        fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            self.deserialize_str(visitor)
+        }
 
-    // Write TypeScript server
-    let ts_server_path = output_dir.join("server.ts");
-    fs::write(&ts_server_path, &generated_code.typescript_server)
-        .map_err(|e| format!("Failed to write TypeScript server file: {}", e))?;
+        fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            visitor.visit_borrowed_str(self.decoder.decode_str()?)
+        }
 
-    Ok(())
-}
+        fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            self.deserialize_str(visitor)
+        }
 
-/// Enhanced error type with file location context
-#[derive(Debug, Clone)]
-pub struct CompileError {
-    pub message: String,
-    pub file: Option<PathBuf>,
-    pub line: Option<usize>,
-    pub column: Option<usize>,
-}
+        fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            if self.decoder.decode_flag()? {
+                visitor.visit_some(self)
+            } else {
+                visitor.visit_none()
+            }
+        }
 
-impl CompileError {
-    pub fn new(message: String) -> Self {
-        CompileError {
-            message,
-            file: None,
-            line: None,
-            column: None,
+        fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            visitor.visit_unit()
         }
-    }
 
-    pub fn with_file(mut self, file: PathBuf) -> Self {
-        self.file = Some(file);
-        self
-    }
+        fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, CompileError> {
+            visitor.visit_unit()
+        }
 
-    pub fn with_location(mut self, line: usize, column: usize) -> Self {
-        self.line = Some(line);
-        self.column = Some(column);
-        self
-    }
+        fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, CompileError> {
+            visitor.visit_newtype_struct(self)
+        }
 
-    pub fn format(&self) -> String {
-        let mut msg = String::new();
+        fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, CompileError> {
+            let remaining = self.decoder.decode_count()? as usize;
+            visitor.visit_seq(Items { deserializer: self, remaining, empty: 0 })
+        }
 
-        if let Some(file) = &self.file {
-            msg.push_str(&format!("Error in {}", file.display()));
-            if let (Some(line), Some(col)) = (self.line, self.column) {
-                msg.push_str(&format!(" at line {}, column {}", line, col));
-            } else if let Some(line) = self.line {
-                msg.push_str(&format!(" at line {}", line));
-            }
-            msg.push_str(": ");
-        } else {
-            msg.push_str("Error: ");
+        fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value, CompileError> {
+            visitor.visit_seq(Items { deserializer: self, remaining: len, empty: 0 })
         }
 
-        msg.push_str(&self.message);
-        msg
-    }
-}
+        fn deserialize_tuple_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            len: usize,
+            visitor: V,
+        ) -> Result<V::Value, CompileError> {
+            self.deserialize_tuple(len, visitor)
+        }
 
-impl std::fmt::Display for CompileError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.format())
-    }
-}
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, CompileError> {
+            self.deserialize_tuple(fields.len(), visitor)
+        }
 
-impl From<String> for CompileError {
-    fn from(message: String) -> Self {
-        CompileError::new(message)
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            _variants: &'static [&'static str],
+            _visitor: V,
+        ) -> Result<V::Value, CompileError> {
+            Err(unsupported(&format!("enum {}", name)))
+        }
+
+        fn is_human_readable(&self) -> bool {
+            false
+        }
     }
 }
 
@@ -1876,11 +9044,526 @@ mod tests {
     }
 
     #[test]
-    fn test_duplicate_field_names() {
-        let schema = "resource User { string name string name }";
-        let program = parse_schema(schema).unwrap();
-        let result = Compiler::new(program);
-        assert!(result.is_err());
+    fn test_duplicate_field_names() {
+        let schema = "resource User { string name string name }";
+        let program = parse_schema(schema).unwrap();
+        let result = Compiler::new(program);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_parser_recovers_from_errors() {
+        let schema = "resource User {\n    string\n    number age\n    list 42 tags\n}\nresource post { string title }\n";
+        let mut parser = Parser::new(schema);
+        let (program, errors) = parser.parse_with_errors();
+
+        assert_eq!(errors.len(), 3);
+        assert_eq!(errors[0].span.unwrap().line, 3);
+        assert_eq!(errors[1].span.unwrap().line, 4);
+        assert_eq!(errors[2].kind, DiagnosticKind::InvalidResourceName { name: "post".to_string() });
+
+        // Everything that parsed is still in the program
+        assert_eq!(program.resources.len(), 2);
+        assert_eq!(program.resources[0].fields.len(), 1);
+        assert_eq!(program.resources[0].fields[0].name, "age");
+        assert_eq!(program.resources[1].fields[0].name, "title");
+
+        let err = parse_schema(schema).unwrap_err();
+        assert_eq!(err.diagnostics.len(), 3);
+        assert_eq!(err.diagnostics[0].message(), "Expected field name, got Number");
+    }
+
+    #[test]
+    fn test_parser_recovers_from_missing_brace() {
+        let schema = "resource A { string name\nresource B { string title }\nstray\nresource C {}";
+        let mut parser = Parser::new(schema);
+        let (program, errors) = parser.parse_with_errors();
+
+        assert_eq!(errors.len(), 2);
+        assert!(errors[1].message().contains("Identifier(\"stray\")"));
+        let names: Vec<_> = program.resources.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, vec!["A", "B", "C"]);
+    }
+
+    #[test]
+    fn test_compile_reports_all_errors() {
+        let schema = r#"
+            resource User { string name string name Missing a }
+            resource User { Other b }
+            resource Post { string title bool title }
+        "#;
+        let err = compile_schema(schema).unwrap_err();
+        let errors: Vec<_> = err.diagnostics.iter().map(|d| d.message()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Duplicate resource name: User",
+                "Duplicate field name in User: name",
+                "Duplicate field name in Post: title",
+                "Undefined type: Missing",
+                "Undefined type: Other",
+            ]
+        );
+
+        // Duplicates don't hide undefined types or cycles
+        let err = compile_schema("resource A { Missing x C c }\nresource B { }\nresource B { }\nresource C { A a }")
+            .unwrap_err();
+        let errors: Vec<_> = err.diagnostics.iter().map(|d| d.message()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "Duplicate resource name: B",
+                "Undefined type: Missing",
+                "Cyclic dependency detected: A → C → A",
+            ]
+        );
+
+        let err = compile_schema("resource User { Missing a Other b list Gone c }").unwrap_err();
+        let codes: Vec<_> = err.diagnostics.iter().map(|d| d.code()).collect();
+        assert_eq!(codes, vec!["P0003", "P0003", "P0003"]);
+        assert_eq!(err.diagnostics[2].kind, DiagnosticKind::UndefinedType { name: "Gone".to_string() });
+
+        // Undefined types are reported next to syntax errors
+        let err = compile_schema("resource User { Missing a\n string }").unwrap_err();
+        let codes: Vec<_> = err.diagnostics.iter().map(|d| d.code()).collect();
+        assert_eq!(codes, vec!["P0006", "P0003"]);
+    }
+
+    #[test]
+    fn test_compile_error_renders_snippets() {
+        let schema = "resource User {\n    string name\n    Missing other\n}\nresource A { B b }\nresource B { A a }\n";
+        let err = compile_schema(schema).unwrap_err();
+        assert_eq!(err.diagnostics[0].span, Some(Span { line: 3, column: 5, length: 7 }));
+        // Cycles point at the field type that starts the loop
+        let err = compile_schema("resource A { B b }\nresource B { A a }\n").unwrap_err();
+        assert_eq!(err.diagnostics[0].span, Some(Span { line: 1, column: 14, length: 1 }));
+
+        let err = compile_schema(schema).unwrap_err().with_file(PathBuf::from("schema.pr"));
+        assert_eq!(
+            err.format(),
+            "error[P0003]: Undefined type: Missing\n \
+              --> schema.pr:3:5\n  \
+               |\n\
+             3 |     Missing other\n  \
+               |     ^^^^^^^\n"
+        );
+
+        // Without a span only the header is printed
+        let err = CompileError::from(DiagnosticKind::TypeMismatch {
+            expected: "string".to_string(),
+            got: "number".to_string(),
+        });
+        assert_eq!(err.to_string(), "error[P0005]: Type mismatch: expected string, got number");
+    }
+
+    #[test]
+    fn test_diagnostic_json_and_exit_codes() {
+        let err = compile_schema("resource User {\n    Missing other\n}\n").unwrap_err();
+        assert_eq!(err.category(), Some(ErrorCategory::Semantic));
+        assert_eq!(err.exit_code(), 4);
+        assert_eq!(
+            err.diagnostics[0].to_json(Some(Path::new("schema.pr"))),
+            json!({
+                "type": "diagnostic",
+                "code": "P0003",
+                "category": "semantic",
+                "severity": "error",
+                "message": "Undefined type: Missing",
+                "file": "schema.pr",
+                "line": 2,
+                "column": 5,
+                "length": 7,
+                "notes": []
+            })
+        );
+
+        // Syntax errors win over the semantic errors found alongside them
+        let err = compile_schema("resource User { Missing a\n string }").unwrap_err();
+        assert_eq!(err.exit_code(), 3);
+
+        let err = compile_file_to_output(Path::new("/nonexistent/schema.pr")).unwrap_err();
+        assert_eq!(err.exit_code(), 1);
+        assert_eq!(err.diagnostics[0].to_json(None)["line"], Json::Null);
+    }
+
+    #[test]
+    fn test_schema_watcher_rewrites_only_changed_outputs() {
+        let dir = std::env::temp_dir().join(format!("previous-watch-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("schema.pr");
+        fs::write(&input, "resource User {\n    string name\n}\n").unwrap();
+
+        let mut watcher = SchemaWatcher::new(CliOptions {
+            inputs: vec![input.clone()],
+            output_dir: dir.join("out"),
+            verbose: false,
+            targets: vec![Target::TypeScript, Target::GraphQl],
+            codegen: CodegenOptions::default(),
+            layout: OutputLayout::Merged,
+        });
+
+        let report = watcher.check().unwrap().unwrap();
+        assert_eq!(report.written.len(), 3);
+        assert!(watcher.check().is_none());
+
+        // A comment changes no output, a new field changes every output
+        fs::write(&input, "// users\nresource User {\n    string name\n}\n").unwrap();
+        watcher.last_modified = None;
+        let report = watcher.check().unwrap().unwrap();
+        assert!(report.written.is_empty());
+        assert_eq!(report.unchanged.len(), 3);
+
+        fs::write(&input, "resource User {\n    string name\n    Missing m\n}\n").unwrap();
+        watcher.last_modified = None;
+        let err = watcher.check().unwrap().unwrap_err();
+        assert_eq!(err.diagnostics[0].code(), "P0003");
+
+        fs::remove_file(&input).unwrap();
+        assert_eq!(watcher.check().unwrap().unwrap_err().exit_code(), 1);
+        assert!(watcher.check().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_compile_several_files() {
+        let sources = vec![
+            (PathBuf::from("a.pr"), "resource User {\n    string name\n}\n".to_string()),
+            (PathBuf::from("b.pr"), "\nresource User {\n    bool active\n}\n".to_string()),
+        ];
+        let err = compile_sources(&sources).unwrap_err();
+        assert_eq!(err.diagnostics.len(), 1);
+        assert_eq!(err.diagnostics[0].file, Some(PathBuf::from("b.pr")));
+        assert_eq!(err.diagnostics[0].notes, vec!["`User` first defined at a.pr:1:10".to_string()]);
+        let rendered = err.format();
+        assert!(rendered.contains(" --> b.pr:2:10\n"));
+        assert!(rendered.contains("2 | resource User {\n"));
+        assert!(rendered.contains("  = note: `User` first defined at a.pr:1:10\n"));
+
+        let dir = std::env::temp_dir().join(format!("previous-multi-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("user.pr"), "resource User {\n    string name\n}\n").unwrap();
+        fs::write(dir.join("blog.pr"), "resource Post {\n    User author\n}\n").unwrap();
+
+        let mut options = CliOptions {
+            inputs: vec![dir.join("*.pr")],
+            output_dir: dir.join("out"),
+            targets: vec![Target::GraphQl],
+            ..CliOptions::default()
+        };
+        let report = compile_file(&options).unwrap();
+        assert_eq!(report.written, vec![dir.join("out/schema.graphql")]);
+        let merged = fs::read_to_string(dir.join("out/schema.graphql")).unwrap();
+        assert!(merged.contains("type User") && merged.contains("type Post"));
+
+        // Each file gets its own resources plus the ones they reference
+        options.layout = OutputLayout::PerFile;
+        compile_file(&options).unwrap();
+        let blog = fs::read_to_string(dir.join("out/blog/schema.graphql")).unwrap();
+        assert!(blog.contains("type Post") && blog.contains("type User"));
+        let user = fs::read_to_string(dir.join("out/user/schema.graphql")).unwrap();
+        assert!(user.contains("type User") && !user.contains("type Post"));
+
+        // Files sharing a stem are told apart by their directory
+        fs::create_dir_all(dir.join("admin")).unwrap();
+        fs::write(dir.join("admin/user.pr"), "resource Admin {\n    User user\n}\n").unwrap();
+        options.inputs = vec![dir.join("*.pr"), dir.join("admin/*.pr")];
+        compile_file(&options).unwrap();
+        let admin = fs::read_to_string(dir.join("out/admin/user/schema.graphql")).unwrap();
+        assert!(admin.contains("type Admin") && admin.contains("type User"));
+        assert!(!fs::read_to_string(dir.join("out/user/schema.graphql")).unwrap().contains("type Admin"));
+        fs::remove_dir_all(dir.join("admin")).unwrap();
+        options.inputs = vec![dir.join("*.pr")];
+
+        let err = expand_inputs(&[dir.join("*.proto")]).unwrap_err();
+        assert_eq!(err.exit_code(), 1);
+
+        // The same file spelled twice or overlapping a glob is read once
+        let files = expand_inputs(&[dir.join("user.pr"), dir.join("./user.pr"), dir.join("*.pr")]).unwrap();
+        assert_eq!(files, vec![dir.join("user.pr"), dir.join("blog.pr")]);
+        options.inputs = vec![dir.join("user.pr"), dir.join("./user.pr")];
+        options.layout = OutputLayout::Merged;
+        compile_file(&options).unwrap();
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_packages() {
+        let sources = vec![
+            (
+                PathBuf::from("billing.pr"),
+                "package billing\n\nresource User {\n    string id\n}\n\nresource Invoice {\n    User owner\n    shop.User buyer\n}\n"
+                    .to_string(),
+            ),
+            (PathBuf::from("shop.pr"), "package shop\n\nresource User {\n    string name\n}\n".to_string()),
+            (PathBuf::from("order.pr"), "resource Order {\n    billing.Invoice invoice\n}\n".to_string()),
+        ];
+        let output = compile_sources(&sources).unwrap();
+        let invoice = output.ir.get_resource("billing.Invoice").unwrap();
+        assert_eq!(output.ir.type_name(&invoice.fields[0].field_type), "billing.User");
+        assert_eq!(output.ir.type_name(&invoice.fields[1].field_type), "shop.User");
+
+        let client = output.generated_code.typescript_client;
+        assert!(client.contains("export namespace billing {\n  export interface IUser {"));
+        assert!(client.contains("  export class Invoice {"));
+        assert!(client.contains("    owner: billing.IUser;\n    buyer: shop.IUser;"));
+        assert!(client.contains("  invoice: billing.IInvoice;"));
+        assert!(client.contains("new shop.User(this.reader.buffer.slice(this.reader.offset))"));
+        let graphql = CodeGenerator::new(output.ir.clone()).generate_graphql();
+        assert!(graphql.contains("type billing_Invoice {"));
+        let mermaid = CycleDetector::build(&output.ir).unwrap().to_mermaid();
+        let billing = output.ir.get_resource_index("billing.User").unwrap();
+        assert!(mermaid.contains(&format!("    n{}[\"billing.User\"]\n", billing)));
+        assert!(!mermaid.contains("billing.User -->"));
+        let openapi = CodeGenerator::new(output.ir).generate_openapi();
+        assert!(openapi.contains("\"#/components/schemas/shop.User\""));
+
+        // Uniqueness is per package, plain names don't see other packages
+        let err = compile_schema("package billing\nresource User { string id }\nresource User { bool x }").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Duplicate resource name: billing.User");
+        let err = compile_sources(&sources[1..]).unwrap_err();
+        assert!(matches!(&err.diagnostics[0].kind, DiagnosticKind::UndefinedType { name } if name == "billing.Invoice"));
+
+        let schema = "// Billing domain\npackage billing\n\nresource User {\n    string id\n}\n";
+        assert_eq!(format_schema(schema).unwrap(), schema);
+    }
+
+    #[test]
+    fn test_services() {
+        let schema = r#"
+resource User {
+    string id
+    optional string nick
+}
+
+resource UserInput {
+    string name
+}
+
+service UserApi {
+    get "/users/:id" -> User;
+    post "/users" UserInput -> User
+    delete "/users/:id"
+}
+"#;
+        let output = compile_schema(schema).unwrap();
+        let service = &output.ir.services[0];
+        assert_eq!(service.endpoints.len(), 3);
+        assert_eq!(service.endpoints[0].params(), vec!["id"]);
+        assert_eq!(service.endpoints[1].request, output.ir.get_resource_index("UserInput"));
+
+        let client = &output.generated_code.typescript_client;
+        assert!(client.contains("export class UserApiClient {"));
+        assert!(client.contains(
+            "  async getUsersById(params: { id: string }, options: PreviousRequestOptions = {}): Promise<User> {"
+        ));
+        assert!(client.contains("${this.baseUrl}/users/${encodeURIComponent(params.id)}"));
+        assert!(client.contains(
+            "  async postUsers(body: IUserInput, options: PreviousRequestOptions = {}): Promise<User> {"
+        ));
+        assert!(client.contains("    writeUserInput(writer, body);"));
+        assert!(client.contains("\"POST /users\", `${this.baseUrl}/users`, \"POST\", options, writer.finish());"));
+        assert!(client.contains(
+            "  async deleteUsersById(params: { id: string }, options: PreviousRequestOptions = {}): Promise<void> {"
+        ));
+        assert!(client.contains("  if (value.nick === undefined) {"));
+
+        // Negotiation, error decoding and cancellation live in shared helpers
+        assert!(client.contains("\"Accept\": \"application/x-previous, application/json;q=0.5\""));
+        assert!(client.contains("export class PreviousHttpError extends Error {"));
+        assert!(client.contains("signal: options.signal"));
+        assert!(client.contains("(data: IUser) => User.fromJSON(data)"));
+        assert!(client.contains("  static fromJSON(data: IUser): User {"));
+        // Only service clients decode JSON answers
+        assert!(!compile_schema("resource User { string name }").unwrap().generated_code.typescript_client.contains("fromJSON"));
+
+        let server = &output.generated_code.typescript_server;
+        assert!(server.contains("export interface UserApiHandlers {"));
+        assert!(server.contains("  postUsers(body: IUserInput): User | Promise<User>;"));
+        assert!(server.contains("export function readUserInput(reader: BinaryReader): IUserInput {"));
+        assert!(server.contains("path.match(/^\\/users\\/([^/]+)$/)"));
+        assert!(server.contains("  if (method === \"POST\" && path === \"/users\") {"));
+        assert!(server.contains("await handlers.postUsers(readUserInput(new BinaryReader(body)))"));
+
+        let openapi: serde_json::Value =
+            serde_json::from_str(&CodeGenerator::new(output.ir.clone()).generate_openapi()).unwrap();
+        let item = &openapi["paths"]["/users/{id}"];
+        assert_eq!(item["get"]["operationId"], "UserApi.getUsersById");
+        assert_eq!(item["get"]["responses"]["200"]["$ref"], "#/components/responses/User");
+        assert_eq!(item["delete"]["parameters"][0]["name"], "id");
+        assert!(item["delete"]["responses"]["204"].is_object());
+        assert_eq!(
+            openapi["paths"]["/users"]["post"]["requestBody"]["$ref"],
+            "#/components/requestBodies/UserInput"
+        );
+
+        // Resources without a service generate the same code as before
+        let plain = compile_schema("resource User { string id }").unwrap();
+        assert!(!plain.generated_code.typescript_client.contains("BinaryWriter"));
+        assert!(!plain.generated_code.typescript_client.contains("previousRequest"));
+
+        let err = compile_schema("resource User { string id }\nservice Api { get \"/users\" User -> User }").unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Invalid endpoint GET /users in Api: GET requests cannot have a body"
+        );
+        let err = compile_schema("service Api { get \"/users/:id\"\n get \"/users/:name\" }").unwrap_err();
+        assert!(matches!(&err.diagnostics[0].kind, DiagnosticKind::DuplicateEndpoint { .. }));
+        // Every service is served from the same routes
+        let err = compile_schema("service Api { get \"/users/:id\" }\nservice Admin { get \"/users/:key\" }").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Duplicate endpoint in Admin: GET /users/:key");
+        assert_eq!(err.diagnostics[0].notes, vec!["also declared by service Api".to_string()]);
+        let err = compile_schema("service Api { get \"/users/:id\" -> Missing }").unwrap_err();
+        assert!(matches!(&err.diagnostics[0].kind, DiagnosticKind::UndefinedType { name } if name == "Missing"));
+        let err = compile_schema("service Api { get \"/users/:id\" -> list string }").unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Invalid endpoint GET /users/:id in Api: body `list string` is not a resource"
+        );
+        let err = compile_schema("service Api { get \"users\" }").unwrap_err();
+        assert!(matches!(&err.diagnostics[0].kind, DiagnosticKind::InvalidEndpoint { .. }));
+
+        // `;` may end an endpoint, and nothing else
+        let output = compile_schema("resource User { string id }\nservice Api { get \"/users/:id\" -> User; delete \"/users/:id\"; }").unwrap();
+        assert_eq!(output.ir.services[0].endpoints.len(), 2);
+        let err = compile_schema("resource User {\n    string id;\n}").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Expected type, got Semicolon");
+
+        let formatted = "resource User {\n    string id\n}\n\n// Users\nservice UserApi {\n    get \"/users/:id\" -> User // fetch one\n    post \"/users\" User\n}\n";
+        assert_eq!(format_schema(formatted).unwrap(), formatted);
+        // A service declared between resources stays there
+        let interleaved = "resource User {\n    string id\n}\n\nservice UserApi {\n    get \"/users/:id\" -> User\n}\n\nresource Post {\n    string title\n}\n";
+        assert_eq!(format_schema(interleaved).unwrap(), interleaved);
+    }
+
+
+    #[test]
+    fn test_typescript_from_json_converts_fields() {
+        let schema = "resource Tag {\n    number weight\n}\n\nresource User {\n    string name\n    optional number age\n    nullable Tag primary\n    list Tag tags\n    list list number grid\n}\n\nservice UserApi {\n    get \"/users/:id\" -> User\n}\n";
+        let ir = compile_schema(schema).unwrap().ir;
+        let options = CodegenOptions { numbers: NumberMode::BigInt, ..CodegenOptions::default() };
+        let client = CodeGenerator::new(ir.clone()).with_options(options).generate().typescript_client;
+        assert!(client.contains(
+            "  static fromJSON(data: IUser): User {\n\
+             \x20   const instance = Object.create(User.prototype) as User;\n\
+             \x20   instance.data = {\n\
+             \x20     name: data.name,\n\
+             \x20     age: data.age == null ? data.age : BigInt(data.age),\n\
+             \x20     primary: data.primary == null ? data.primary : Tag.fromJSON(data.primary),\n\
+             \x20     tags: data.tags.map((item) => Tag.fromJSON(item)),\n\
+             \x20     grid: data.grid.map((item) => item.map((item) => BigInt(item))),\n\
+             \x20   } as unknown as IUser;\n\
+             \x20   return instance;\n\
+             \x20 }\n"
+        ), "{}", client);
+        assert!(client.contains("      weight: BigInt(data.weight),\n"));
+
+        // Plain numbers are already what the decoder produces
+        let client = CodeGenerator::new(ir).generate().typescript_client;
+        assert!(client.contains("      age: data.age,\n"));
+        assert!(client.contains("      grid: data.grid,\n"));
+        assert!(client.contains("      tags: data.tags.map((item) => Tag.fromJSON(item)),\n"));
+    }
+    #[test]
+    fn test_project_config() {
+        let text = r#"
+            [[schema]]
+            input = "schemas/user.pr"
+            targets = ["ts", "openapi", "graphql"]
+            out = "generated/{name}"
+
+            [[schema]]
+            input = "blog.pr"
+
+            [targets.ts]
+            out = "web/{name}"
+            numbers = "bigint"
+            naming = "camelCase"
+
+            [lint]
+            deny = ["nested-list"]
+            max-fields = 8
+
+            [wire]
+            max-list-length = 100
+        "#;
+        let config = ProjectConfig::parse(text, Path::new("/project")).unwrap();
+
+        let runs = config.compile_options();
+        let summary: Vec<_> = runs
+            .iter()
+            .map(|run| (run.inputs[0].clone(), run.output_dir.clone(), run.targets.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    PathBuf::from("/project/schemas/user.pr"),
+                    PathBuf::from("/project/web/user"),
+                    vec![Target::TypeScript]
+                ),
+                (
+                    PathBuf::from("/project/schemas/user.pr"),
+                    PathBuf::from("/project/generated/user"),
+                    vec![Target::OpenApi, Target::GraphQl]
+                ),
+                (PathBuf::from("/project/blog.pr"), PathBuf::from("/project/web/blog"), vec![Target::TypeScript]),
+            ]
+        );
+        assert_eq!(runs[0].codegen.numbers, NumberMode::BigInt);
+        assert_eq!(runs[0].codegen.wire.max_list_length, Some(100));
+
+        let lint = config.lint_config().unwrap();
+        assert_eq!(lint.level(LintRule::NestedList), LintLevel::Deny);
+        assert_eq!(lint.max_fields, 8);
+
+        assert!(ProjectConfig::parse("[lint]\ndeny = [\"no-such-rule\"]", Path::new(".")).is_err());
+        assert!(ProjectConfig::parse("[targets.ts]\nmodule = \"amd\"", Path::new(".")).is_err());
+        assert!(ProjectConfig::parse("[unknown]", Path::new(".")).is_err());
+        let err = ProjectConfig::parse("[wire]\nmax-list-length = \"many\"", Path::new(".")).unwrap_err();
+        assert_eq!(err.diagnostics[0].code(), "P0014");
+        assert_eq!(err.diagnostics[0].span, Some(Span { line: 2, column: 19, length: 6 }));
+        assert_eq!(err.exit_code(), 6);
+
+        let config = ProjectConfig::parse(
+            "[[schema]]\ninput = [\"billing/*.pr\", \"shared.pr\"]\nlayout = \"per-file\"",
+            Path::new("/project"),
+        )
+        .unwrap();
+        let runs = config.compile_options();
+        assert_eq!(
+            runs[0].inputs,
+            vec![PathBuf::from("/project/billing/*.pr"), PathBuf::from("/project/shared.pr")]
+        );
+        assert_eq!(runs[0].output_dir, PathBuf::from("/project/generated/schema"));
+        assert_eq!(runs[0].layout, OutputLayout::PerFile);
+    }
+
+    #[test]
+    fn test_codegen_options() {
+        let output = compile_schema("resource Stats {\n    number total_count\n    list string tags\n}\n").unwrap();
+        let options = CodegenOptions {
+            module: ModuleStyle::Global,
+            numbers: NumberMode::BigInt,
+            naming: NamingConvention::CamelCase,
+            wire: WireOptions {
+                max_string_length: Some(64),
+                ..WireOptions::default()
+            },
+        };
+        let code = CodeGenerator::new(output.ir).with_options(options).generate();
+
+        assert!(code.typescript_client.contains("  totalCount: bigint;"));
+        assert!(code.typescript_client.contains("getTotalCount(): bigint"));
+        assert!(code.typescript_client.contains("readNumber(): bigint"));
+        assert!(code.typescript_client.contains("if (length > 64)"));
+        assert!(!code.typescript_client.contains("items exceeds the limit"));
+        assert!(code.typescript_server.contains("this._totalCount = 0n;"));
+        assert!(!code.typescript_client.contains("export "));
+        assert!(!code.typescript_server.contains("export "));
     }
 
     #[test]
@@ -1965,13 +9648,18 @@ mod tests {
             resources: vec![
                 IRResource {
                     name: "User".to_string(),
+                    package: None,
                     fields: vec![],
+                    file: None,
                 },
                 IRResource {
                     name: "Post".to_string(),
+                    package: None,
                     fields: vec![],
+                    file: None,
                 },
             ],
+            services: Vec::new(),
         };
 
         assert_eq!(ir.get_resource_index("User"), Some(0));
@@ -1984,8 +9672,11 @@ mod tests {
         let ir = IRProgram {
             resources: vec![IRResource {
                 name: "User".to_string(),
+                package: None,
                 fields: vec![],
+                file: None,
             }],
+            services: Vec::new(),
         };
 
         assert!(ir.get_resource("User").is_some());
@@ -2198,7 +9889,7 @@ mod tests {
         let result = resolver.resolve(program);
 
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Undefined type"));
+        assert!(result.unwrap_err().to_string().contains("Undefined type"));
     }
 
     #[test]
@@ -2310,7 +10001,7 @@ mod tests {
         let result = detector.detect();
 
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         assert!(err.contains("Cyclic dependency detected"));
         assert!(err.contains("A"));
     }
@@ -2329,7 +10020,7 @@ mod tests {
         let result = detector.detect();
 
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         assert!(err.contains("Cyclic dependency detected"));
         assert!(err.contains("A"));
         assert!(err.contains("B"));
@@ -2350,7 +10041,7 @@ mod tests {
         let result = detector.detect();
 
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         assert!(err.contains("Cyclic dependency detected"));
     }
 
@@ -2371,7 +10062,7 @@ mod tests {
 
         // Should detect the A ↔ B cycle
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         assert!(err.contains("Cyclic dependency detected"));
     }
 
@@ -2389,7 +10080,7 @@ mod tests {
         let result = detector.detect();
 
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         assert!(err.contains("Cyclic dependency detected"));
     }
 
@@ -2419,7 +10110,7 @@ mod tests {
         let result = compile_schema(schema);
 
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         assert!(err.contains("Cyclic dependency detected"));
     }
 
@@ -2450,11 +10141,30 @@ mod tests {
         let result = detector.detect();
 
         assert!(result.is_err());
-        let err = result.unwrap_err();
+        let err = result.unwrap_err().to_string();
         // Should show the cycle path with arrows
         assert!(err.contains(" → "));
     }
 
+    #[test]
+    fn test_cycle_detector_reports_every_cycle() {
+        let schema = r#"
+            resource A { B b }
+            resource B { A a }
+            resource C { C self }
+            resource D { string name }
+        "#;
+        let err = compile_schema(schema).unwrap_err();
+        let cycles: Vec<_> = err.diagnostics.iter().map(|d| &d.kind).collect();
+        assert_eq!(
+            cycles,
+            vec![
+                &DiagnosticKind::Cycle { path: vec!["A".to_string(), "B".to_string(), "A".to_string()] },
+                &DiagnosticKind::Cycle { path: vec!["C".to_string(), "C".to_string()] },
+            ]
+        );
+    }
+
     #[test]
     fn test_cycle_detector_multiple_fields_with_cycle() {
         let schema = r#"
@@ -2764,6 +10474,105 @@ mod tests {
         assert!(bytes.len() > 9);
     }
 
+    #[test]
+    fn test_decode_roundtrip_and_errors() {
+        let ir = compile_schema(
+            "resource Tag { string name }\nresource User {\n    string name\n    optional number age\n    nullable bool active\n    list Tag tags\n}",
+        )
+        .unwrap()
+        .ir;
+        let user = IRType::ResourceRef(ir.get_resource_index("User").unwrap());
+        let field = |name: &str, value: Value, is_optional: bool, is_nullable: bool| FieldValue {
+            name: name.to_string(),
+            value,
+            is_optional,
+            is_nullable,
+        };
+        let value = Value::Resource(vec![
+            field("name", Value::String("Alice".to_string()), false, false),
+            field("age", Value::Absent, true, false),
+            field("active", Value::Null, false, true),
+            field(
+                "tags",
+                Value::List(vec![Value::Resource(vec![field("name", Value::String("admin".to_string()), false, false)])]),
+                false,
+                false,
+            ),
+        ]);
+
+        let mut encoder = BinaryEncoder::new();
+        encoder.encode_value(&value, &user, &ir).unwrap();
+        let bytes = encoder.finish();
+        let mut decoder = BinaryDecoder::new(&bytes);
+        assert_eq!(decoder.decode_value(&user, &ir).unwrap(), value);
+        assert!(decoder.finish().is_ok());
+        assert_eq!(value.to_json(), json!({ "name": "Alice", "active": null, "tags": [{ "name": "admin" }] }));
+
+        let err = BinaryDecoder::new(&bytes[..bytes.len() - 1]).decode_value(&user, &ir).unwrap_err();
+        assert_eq!(err.diagnostics[0].code(), "P0013");
+        assert_eq!(err.diagnostics[0].message(), "Malformed payload at byte 19: expected 5 more bytes, 4 left");
+
+        let mut decoder = BinaryDecoder::new(&bytes).with_limits(WireOptions {
+            max_string_length: Some(3),
+            ..WireOptions::default()
+        });
+        let err = decoder.decode_value(&user, &ir).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Malformed payload at byte 4: string of 5 bytes exceeds the limit of 3");
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        let mut decoder = BinaryDecoder::new(&trailing);
+        decoder.decode_value(&user, &ir).unwrap();
+        assert!(decoder.finish().is_err());
+        let err = BinaryDecoder::new(&[2]).decode_value(&IRType::Primitive("bool".to_string()), &ir).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Malformed payload at byte 0: invalid bool byte 0x02");
+    }
+
+    #[test]
+    fn test_decode_list_count_bounded_by_payload() {
+        let ir = compile_schema("resource Empty {\n}\nresource Bag {\n    list Empty items\n    list string names\n}")
+            .unwrap()
+            .ir;
+        let bag = IRType::ResourceRef(ir.get_resource_index("Bag").unwrap());
+        let bag_bytes = |items: usize| {
+            let field = |name: &str, value| FieldValue {
+                name: name.to_string(),
+                value,
+                is_optional: false,
+                is_nullable: false,
+            };
+            let value = Value::Resource(vec![
+                field("items", Value::List(vec![Value::Resource(Vec::new()); items])),
+                field("names", Value::List(Vec::new())),
+            ]);
+            let mut encoder = BinaryEncoder::new();
+            encoder.encode_value(&value, &bag, &ir).unwrap();
+            (value, encoder.finish())
+        };
+
+        // Empty items round-trip up to the limit, one more is rejected
+        let (value, bytes) = bag_bytes(DEFAULT_MAX_EMPTY_LIST_LENGTH as usize);
+        assert_eq!(BinaryDecoder::new(&bytes).decode_value(&bag, &ir).unwrap(), value);
+        let (value, bytes) = bag_bytes(DEFAULT_MAX_EMPTY_LIST_LENGTH as usize + 1);
+        let err = BinaryDecoder::new(&bytes).decode_value(&bag, &ir).unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Malformed payload at byte 4: list of 65537 empty items exceeds the limit of 65536"
+        );
+        let limits = WireOptions { max_empty_list_length: Some(65537), ..WireOptions::default() };
+        assert_eq!(BinaryDecoder::new(&bytes).with_limits(limits).decode_value(&bag, &ir).unwrap(), value);
+
+        // Strings take at least their length prefix
+        let mut bytes = 0u32.to_le_bytes().to_vec();
+        bytes.extend_from_slice(&1000u32.to_le_bytes());
+        bytes.extend_from_slice(&[0; 12]);
+        let err = BinaryDecoder::new(&bytes).decode_value(&bag, &ir).unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Malformed payload at byte 8: list of 1000 items needs at least 4 bytes per item, 12 left"
+        );
+    }
+
     #[test]
     fn test_encode_nested_resource() {
         let schema = r#"
@@ -2820,7 +10629,7 @@ mod tests {
 
         let result = encoder.encode_value(&value, &ir_type, &ir);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Type mismatch"));
+        assert!(result.unwrap_err().to_string().contains("Type mismatch"));
     }
 
     #[test]
@@ -2848,7 +10657,10 @@ mod tests {
 
         let result = encoder.encode_value(&value, &IRType::ResourceRef(0), &ir);
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Field count mismatch"));
+        assert!(matches!(
+            &result.unwrap_err().diagnostics[0].kind,
+            DiagnosticKind::TypeMismatch { expected, got } if expected == "2 fields for User" && got == "1"
+        ));
     }
 
     // ========================================================================
@@ -3073,4 +10885,1037 @@ mod tests {
         assert!(output.generated_code.typescript_server.contains("writer.writeString"));
         assert!(output.generated_code.typescript_server.contains("writer.writeNumber"));
     }
+
+    #[test]
+    fn test_openapi_component_schemas() {
+        let schema = r#"
+            resource User {
+                string name
+                optional number age
+                nullable bool active
+                default(10) number limit
+            }
+            resource Users {
+                list User users
+            }
+        "#;
+        let output = compile_schema(schema).unwrap();
+        let openapi = CodeGenerator::new(output.ir).generate_openapi();
+        let doc: serde_json::Value = serde_json::from_str(&openapi).unwrap();
+
+        assert_eq!(doc["openapi"], "3.1.0");
+        let user = &doc["components"]["schemas"]["User"];
+        assert_eq!(user["properties"]["name"]["type"], "string");
+        assert_eq!(user["properties"]["age"]["format"], "int64");
+        assert_eq!(user["properties"]["active"]["type"], serde_json::json!(["boolean", "null"]));
+        assert_eq!(user["properties"]["limit"]["default"], 10);
+        assert_eq!(user["properties"]["limit"]["x-previous-index"], 3);
+        assert_eq!(user["required"], serde_json::json!(["name", "active", "limit"]));
+
+        let users = &doc["components"]["schemas"]["Users"];
+        assert_eq!(users["properties"]["users"]["type"], "array");
+        assert_eq!(users["properties"]["users"]["items"]["$ref"], "#/components/schemas/User");
+    }
+
+    #[test]
+    fn test_openapi_previous_media_type() {
+        let output = compile_schema("resource User { string name }").unwrap();
+        let openapi = CodeGenerator::new(output.ir).generate_openapi();
+        let doc: serde_json::Value = serde_json::from_str(&openapi).unwrap();
+
+        let content = &doc["components"]["responses"]["User"]["content"];
+        assert_eq!(content[PREVIOUS_MEDIA_TYPE]["schema"]["$ref"], "#/components/schemas/User");
+        assert_eq!(doc["x-previous"]["mediaType"], "application/x-previous");
+    }
+
+    #[test]
+    fn test_proto_export() {
+        let schema = r#"
+            resource User {
+                string name
+                optional number age
+                nullable bool active
+                list string tags
+                list list number matrix
+            }
+        "#;
+        let output = compile_schema(schema).unwrap();
+        let proto = CodeGenerator::new(output.ir).generate_proto().unwrap();
+
+        assert!(proto.contains("syntax = \"proto3\";"));
+        assert!(proto.contains("message User {"));
+        assert!(proto.contains("  string name = 1;"));
+        assert!(proto.contains("  optional int64 age = 2;"));
+        assert!(proto.contains("  optional bool active = 3; // nullable"));
+        assert!(proto.contains("  repeated string tags = 4;"));
+        assert!(proto.contains("  message MatrixItem {\n    repeated int64 items = 1;\n  }"));
+        assert!(proto.contains("  repeated MatrixItem matrix = 5;"));
+        assert!(proto.contains("`optional` and `nullable` fields\n// are both emitted as `optional`"));
+    }
+
+    #[test]
+    fn test_proto_name_collisions() {
+        let schema = r#"
+            resource TagsItem { string name }
+            resource Post {
+                list list string tags
+                TagsItem featured
+            }
+        "#;
+        let output = compile_schema(schema).unwrap();
+        let err = CodeGenerator::new(output.ir).generate_proto().unwrap_err();
+        assert_eq!(err.diagnostics[0].kind.code(), "P0009");
+        assert_eq!(
+            err.diagnostics[0].kind.to_string(),
+            "Failed to generate proto: nested message 'Post.TagsItem' would shadow the referenced message 'TagsItem'"
+        );
+
+        let proto = r#"
+            message Order {
+                message Item { string sku = 1; }
+            }
+            message OrderItem { string note = 1; }
+        "#;
+        let err = import_proto(proto).unwrap_err();
+        assert_eq!(err.diagnostics[0].code(), "P0015");
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Invalid proto file: Messages 'Order.Item' and 'OrderItem' would both be imported as resource 'OrderItem'"
+        );
+    }
+
+    #[test]
+    fn test_proto_import_messages() {
+        let proto = r#"
+            syntax = "proto3";
+            package shop;
+
+            message Order {
+                string id = 1;
+                optional int64 total = 2;
+                repeated Item items = 3;
+                message Item {
+                    string sku = 1;
+                    uint32 quantity = 2;
+                }
+            }
+        "#;
+        let imported = import_proto(proto).unwrap();
+        assert!(imported.warnings.is_empty());
+
+        let resources = &imported.program.resources;
+        assert_eq!(resources.len(), 2);
+        assert_eq!(resources[0].name, "Order");
+        assert!(resources[0].fields[1].optional);
+        assert_eq!(
+            resources[0].fields[2].field_type,
+            ASTType::List(Box::new(ASTType::Named("OrderItem".to_string())))
+        );
+        assert_eq!(resources[1].name, "OrderItem");
+        assert_eq!(resources[1].fields[1].field_type, ASTType::Primitive("number".to_string()));
+
+        // The printed schema must compile
+        let source = format_program(&imported.program);
+        assert!(compile_schema(&source).is_ok());
+    }
+
+    #[test]
+    fn test_proto_import_unsupported_warnings() {
+        let proto = r#"
+            message Profile {
+                enum Kind { A = 0; }
+                Kind kind = 1;
+                map<string, int32> scores = 2;
+                oneof contact {
+                    string email = 3;
+                }
+            }
+        "#;
+        let imported = import_proto(proto).unwrap();
+        assert_eq!(imported.warnings.len(), 3);
+        assert!(imported.warnings.iter().any(|w| w.contains("enum")));
+        assert!(imported.warnings.iter().any(|w| w.contains("map")));
+        assert!(imported.warnings.iter().any(|w| w.contains("oneof")));
+
+        let fields = &imported.program.resources[0].fields;
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].field_type, ASTType::Primitive("number".to_string()));
+        assert!(fields[1].optional);
+    }
+
+    #[test]
+    fn test_graphql_sdl_types() {
+        let schema = r#"
+            resource Friend { string name }
+            resource User {
+                string name
+                optional number age
+                nullable bool active
+                list string tags
+                optional list Friend friends
+                list list number matrix
+            }
+        "#;
+        let output = compile_schema(schema).unwrap();
+        let sdl = CodeGenerator::new(output.ir).generate_graphql();
+
+        assert!(sdl.contains("scalar Int64"));
+        assert!(sdl.contains("type User {"));
+        assert!(sdl.contains("  name: String!\n"));
+        assert!(sdl.contains("  age: Int64\n"));
+        assert!(sdl.contains("  active: Boolean\n"));
+        assert!(sdl.contains("  tags: [String!]!\n"));
+        assert!(sdl.contains("  friends: [Friend!]\n"));
+        assert!(sdl.contains("  matrix: [[Int64!]!]!\n"));
+    }
+
+    #[test]
+    fn test_graphql_default_description() {
+        let output = compile_schema("resource Notification { default(10) number interval }").unwrap();
+        let sdl = CodeGenerator::new(output.ir).generate_graphql();
+
+        assert!(sdl.contains("  \"Default: 10\"\n  interval: Int64!\n"));
+
+        let output = compile_schema(r#"resource Session { default("guest") string role }"#).unwrap();
+        let sdl = CodeGenerator::new(output.ir).generate_graphql();
+        assert!(sdl.contains("  \"Default: \\\"guest\\\"\"\n  role: String!\n"), "{}", sdl);
+    }
+
+    #[test]
+    fn test_rust_target() {
+        let sources = vec![
+            (
+                PathBuf::from("billing.pr"),
+                "package billing\n\nresource Invoice {\n    Tag tag\n    optional nullable number total\n}\n".to_string(),
+            ),
+            (
+                PathBuf::from("shop.pr"),
+                "resource Tag {\n    string type\n    default(true) bool isActive\n}\n\nresource Order {\n    list billing.Invoice invoices\n}\n\nresource Empty {\n}\n"
+                    .to_string(),
+            ),
+        ];
+        let output = compile_sources(&sources).unwrap();
+        let code = CodeGenerator::new(output.ir).generate_rust().unwrap();
+
+        assert!(code.contains("pub struct Tag {\n    pub r#type: String,\n    /// Default: `true`\n    pub is_active: bool,\n}"));
+        assert!(code.contains("pub struct Order {\n    pub invoices: Vec<billing::Invoice>,\n}"));
+        assert!(code.contains(
+            "pub mod billing {\n    #[derive(Debug, Clone, PartialEq)]\n    pub struct Invoice {\n        pub tag: super::Tag,\n        pub total: Option<Option<i64>>,\n    }"
+        ));
+        assert!(code.contains("impl ::previous::Wire for Tag {\n    const MIN_SIZE: usize = 5;\n"));
+        assert!(code.contains("        ::previous::Wire::encode(&self.is_active, encoder);\n"));
+        assert!(code.contains("            r#type: ::previous::Wire::decode(decoder)?,\n"));
+        assert!(code.contains("fn encode(&self, _encoder: &mut ::previous::BinaryEncoder) {\n    }"));
+
+        let output = compile_schema("resource User { string userId\n string user_id }").unwrap();
+        let err = CodeGenerator::new(output.ir).generate_rust().unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Failed to generate Rust: two fields of 'User' become the Rust field 'user_id'"
+        );
+    }
+
+    #[test]
+    fn test_docs_markdown_pages() {
+        let schema = r#"
+            resource Author {
+                string name
+                optional number age
+                default(true) bool active
+            }
+            resource Post {
+                Author author
+                list Author reviewers
+            }
+        "#;
+        let output = compile_schema(schema).unwrap();
+        let files = DocsGenerator::new(&output.ir, DocsFormat::Markdown).unwrap().generate();
+
+        let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(paths, vec!["index.md", "Author.md", "Post.md"]);
+
+        let author = &files[1].contents;
+        assert!(author.contains("| 1 | `age` | `number` | yes |  |  |"));
+        assert!(author.contains("| 2 | `active` | `bool` |  |  | `true` |"));
+        assert!(author.contains("- [Post](Post.md) via `author`, `reviewers`"));
+
+        let post = &files[2].contents;
+        assert!(post.contains("| 1 | `reviewers` | `list` [Author](Author.md) |"));
+        assert!(post.contains("Not referenced by any resource."));
+    }
+
+    #[test]
+    fn test_docs_html_single_page() {
+        let schema = r#"
+            resource Author { string name }
+            resource Post { Author author }
+        "#;
+        let output = compile_schema(schema).unwrap();
+        let files = DocsGenerator::new(&output.ir, DocsFormat::Html).unwrap().generate();
+
+        assert_eq!(files.len(), 1);
+        let html = &files[0].contents;
+        assert!(html.contains("<section id=\"Author\">"));
+        assert!(html.contains("<td><a href=\"#Author\">Author</a></td>"));
+        assert!(html.contains("Used by: <a href=\"#Post\">Post</a> (author)"));
+    }
+
+    #[test]
+    fn test_cycle_detector_edges() {
+        let schema = r#"
+            resource Author { string name }
+            resource Post {
+                Author author
+                list list Author reviewers
+            }
+        "#;
+        let ir = resolve_schema(schema).unwrap();
+        let graph = CycleDetector::build(&ir).unwrap();
+
+        assert!(graph.edges(0).is_empty());
+        assert_eq!(
+            graph.edges(1),
+            &[
+                DependencyEdge { to: 0, field: "author".to_string(), through_list: false },
+                DependencyEdge { to: 0, field: "reviewers".to_string(), through_list: true },
+            ]
+        );
+        assert_eq!(graph.references(1), vec![0]);
+        assert_eq!(graph.referenced_by(0), vec![1]);
+    }
+
+    #[test]
+    fn test_graph_dot_and_mermaid() {
+        let schema = r#"
+            resource A { B b }
+            resource B { list A parents }
+        "#;
+        // Cyclic schemas can still be rendered
+        let ir = resolve_schema(schema).unwrap();
+        let graph = CycleDetector::build(&ir).unwrap();
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph resources {"));
+        assert!(dot.contains("\"A\" -> \"B\" [label=\"b\"];"));
+        assert!(dot.contains("\"B\" -> \"A\" [label=\"parents[]\", style=dashed];"));
+
+        let mermaid = graph.to_mermaid();
+        assert!(mermaid.starts_with("flowchart LR"));
+        assert!(mermaid.contains("    n0[\"A\"]\n    n1[\"B\"]\n"));
+        assert!(mermaid.contains("n0 -->|\"b\"| n1"));
+        assert!(mermaid.contains("n1 -.->|\"parents[]\"| n0"));
+    }
+
+    #[test]
+    fn test_parse_comments_are_trivia() {
+        let schema = r#"
+            // Users of the system
+            resource User { // main entity
+                string name // display name
+                // contact
+                string email
+            }
+            // end of file
+        "#;
+        let program = parse_schema(schema).unwrap();
+        let user = &program.resources[0];
+        assert_eq!(user.trivia.leading, vec![" Users of the system".to_string()]);
+        assert_eq!(user.trivia.trailing, Some(" main entity".to_string()));
+        assert_eq!(user.fields.len(), 2);
+        assert_eq!(user.fields[0].trivia.trailing, Some(" display name".to_string()));
+        assert_eq!(user.fields[1].trivia.leading, vec![" contact".to_string()]);
+        assert_eq!(program.trailing_comments, vec![" end of file".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_default_after_type() {
+        let schema = "resource Notification { number default(10) interval }";
+        let program = parse_schema(schema).unwrap();
+        let field = &program.resources[0].fields[0];
+        assert_eq!(field.name, "interval");
+        assert_eq!(field.field_type, ASTType::Primitive("number".to_string()));
+        assert!(field.default.is_some());
+    }
+
+    #[test]
+    fn test_format_canonical_layout() {
+        let schema = r#"
+resource User {
+  string name   // display name
+      optional number age
+
+
+  number default(10) interval
+  nullable optional list string tags
+}
+resource Empty {   }
+"#;
+        let formatted = format_schema(schema).unwrap();
+        let expected = "\
+resource User {
+    string                        name // display name
+    optional number               age
+
+    default(10) number            interval
+    optional nullable list string tags
+}
+
+resource Empty {}
+";
+        assert_eq!(formatted, expected);
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let schema = r#"
+            // header
+            resource Author { // authors
+                string name
+                nullable string bio // optional bio
+
+                // closing
+            }
+
+            resource Post {
+                Author author
+                list string default("x") tags
+            }
+            // footer
+        "#;
+        let once = format_schema(schema).unwrap();
+        let twice = format_schema(&once).unwrap();
+        assert_eq!(once, twice);
+        assert!(once.contains("// header\nresource Author { // authors\n"));
+        assert!(once.contains("    // closing\n}"));
+        assert!(once.ends_with("}\n\n// footer\n"));
+    }
+
+    #[test]
+    fn test_lint_default_rules() {
+        let schema = r#"
+            resource User {
+                string first_name
+                optional default(1) number level
+                list list string matrix
+                string class
+                string encode
+            }
+            resource Users { list User users }
+        "#;
+        let ir = resolve_schema(schema).unwrap();
+        let warnings = lint(&ir, &LintConfig::default());
+
+        let found: Vec<(LintRule, Option<&str>, Severity)> = warnings
+            .iter()
+            .map(|w| (w.rule, w.field.as_deref(), w.severity))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (LintRule::FieldCamelCase, Some("first_name"), Severity::Warning),
+                (LintRule::DefaultOnOptional, Some("level"), Severity::Warning),
+                (LintRule::NestedList, Some("matrix"), Severity::Warning),
+                (LintRule::ReservedWord, Some("class"), Severity::Error),
+                (LintRule::ReservedWord, Some("encode"), Severity::Error),
+            ]
+        );
+        assert!(warnings[0].message.contains("firstName"));
+        assert_eq!(
+            warnings[3].to_string(),
+            "error[reserved-word]: User.class: `class` is a reserved word in TypeScript"
+        );
+    }
+
+    #[test]
+    fn test_lint_config_levels() {
+        let mut fields = String::new();
+        for i in 0..5 {
+            fields.push_str(&format!("string field{}\n", i));
+        }
+        let schema = format!("resource Wide {{ {} }} resource Other {{ string snake_case }}", fields);
+        let ir = resolve_schema(&schema).unwrap();
+
+        let mut config = LintConfig {
+            max_fields: 4,
+            ..Default::default()
+        };
+        config.set_level(LintRule::UnusedResource, LintLevel::Deny);
+        config.set_level(LintRule::FieldCamelCase, LintLevel::Allow);
+        let warnings = lint(&ir, &config);
+
+        let rules: Vec<LintRule> = warnings.iter().map(|w| w.rule).collect();
+        assert_eq!(
+            rules,
+            vec![LintRule::UnusedResource, LintRule::WideResource, LintRule::UnusedResource]
+        );
+        assert_eq!(warnings[0].severity, Severity::Error);
+        assert_eq!(LintRule::from_name("wide-resource"), Some(LintRule::WideResource));
+    }
+
+    fn lsp_open(server: &mut LanguageServer, text: &str) -> Json {
+        let (mut responses, _) = server.handle(&json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///schema.pr", "text": text } }
+        }));
+        responses.remove(0)
+    }
+
+    fn lsp_request(server: &mut LanguageServer, method: &str, params: Json) -> Json {
+        let (mut responses, _) = server.handle(&json!({
+            "jsonrpc": "2.0", "id": 1, "method": method, "params": params
+        }));
+        responses.remove(0)
+    }
+
+    fn lsp_position(line: usize, character: usize) -> Json {
+        json!({
+            "textDocument": { "uri": "file:///schema.pr" },
+            "position": { "line": line, "character": character }
+        })
+    }
+
+    #[test]
+    fn test_lsp_diagnostics() {
+        let mut server = LanguageServer::new();
+
+        let published = lsp_open(&mut server, "resource User {\n    string name\n    Missing other\n}\n");
+        let diagnostics = &published["params"]["diagnostics"];
+        assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+        assert_eq!(diagnostics[0]["message"], "Undefined type: Missing");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 2, "character": 4 }));
+
+        let published = lsp_open(&mut server, "resource User {\n    string\n}\n");
+        let diagnostics = &published["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(diagnostics[0]["range"]["start"]["line"], 2);
+
+        let published = lsp_open(&mut server, "resource User {\n    string first_name\n}\n");
+        let diagnostics = &published["params"]["diagnostics"];
+        assert_eq!(diagnostics[0]["code"], "field-camel-case");
+        assert_eq!(diagnostics[0]["severity"], 2);
+    }
+
+    #[test]
+    fn test_lsp_definition_references_and_rename() {
+        let mut server = LanguageServer::new();
+        let text = "resource Author {\n    string name\n}\n\nresource Post {\n    Author author\n    list Author reviewers\n}\n";
+        lsp_open(&mut server, text);
+
+        // `Author` in `list Author reviewers`
+        let definition = lsp_request(&mut server, "textDocument/definition", lsp_position(6, 10));
+        assert_eq!(definition["result"]["range"]["start"], json!({ "line": 0, "character": 9 }));
+
+        let mut params = lsp_position(0, 10);
+        params["context"] = json!({ "includeDeclaration": false });
+        let references = lsp_request(&mut server, "textDocument/references", params);
+        let lines: Vec<u64> = references["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|l| l["range"]["start"]["line"].as_u64().unwrap())
+            .collect();
+        assert_eq!(lines, vec![5, 6]);
+
+        let mut params = lsp_position(5, 5);
+        params["newName"] = json!("Writer");
+        let rename = lsp_request(&mut server, "textDocument/rename", params);
+        let edits = rename["result"]["changes"]["file:///schema.pr"].as_array().unwrap();
+        assert_eq!(edits.len(), 3);
+        assert!(edits.iter().all(|e| e["newText"] == "Writer"));
+
+        let mut params = lsp_position(5, 5);
+        params["newName"] = json!("lowercase");
+        let rename = lsp_request(&mut server, "textDocument/rename", params);
+        assert_eq!(rename["error"]["code"], -32602);
+    }
+
+    #[test]
+    fn test_lsp_hover_and_completion() {
+        let mut server = LanguageServer::new();
+        lsp_open(&mut server, "resource User {\n    string name\n    optional number age\n}\n");
+
+        let hover = lsp_request(&mut server, "textDocument/hover", lsp_position(2, 21));
+        let value = hover["result"]["contents"]["value"].as_str().unwrap();
+        assert!(value.contains("**User.age**: index 1"));
+        assert!(value.contains("1 byte presence flag"));
+        assert!(value.contains("i64 (8 bytes, little-endian)"));
+
+        let completion = lsp_request(&mut server, "textDocument/completion", lsp_position(3, 0));
+        let labels: Vec<&str> = completion["result"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["label"].as_str().unwrap())
+            .collect();
+        assert!(labels.contains(&"resource"));
+        assert!(labels.contains(&"nullable"));
+        assert!(labels.contains(&"User"));
+    }
+
+    #[test]
+    fn test_lsp_utf16_positions() {
+        let mut server = LanguageServer::new();
+        lsp_open(&mut server, "resource User {\n    string id\n}\n\nservice Emoji {\n    get \"/😀\" -> User;\n}\n");
+
+        // The emoji is one char but two UTF-16 code units
+        let mut params = lsp_position(0, 9);
+        params["context"] = json!({ "includeDeclaration": false });
+        let references = lsp_request(&mut server, "textDocument/references", params);
+        assert_eq!(
+            references["result"][0]["range"],
+            json!({ "start": { "line": 5, "character": 17 }, "end": { "line": 5, "character": 21 } })
+        );
+
+        assert_eq!(lsp_column("a😀b", 1, 3), 3);
+        assert_eq!(lsp_character("a😀b", 1, 3), 3);
+        assert_eq!(lsp_column("ab", 1, 5), 6);
+    }
+
+    #[test]
+    fn test_lsp_stdio_framing() {
+        let mut input = Vec::new();
+        for message in [
+            json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+            json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+            json!({ "jsonrpc": "2.0", "method": "exit" }),
+        ] {
+            let body = message.to_string();
+            input.extend_from_slice(format!("Content-Length: {}\r\n\r\n{}", body.len(), body).as_bytes());
+        }
+
+        let mut output = Vec::new();
+        run_language_server(std::io::Cursor::new(input), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output.matches("Content-Length:").count(), 2);
+        assert!(output.contains("\"definitionProvider\":true"));
+        assert!(output.contains("\"result\":null"));
+    }
+
+    #[test]
+    fn test_value_from_json() {
+        let ir = compile_schema(
+            "resource Tag { string name }\nresource User {\n    string name\n    optional number age\n    nullable bool active\n    number default(3) level\n    list Tag tags\n}",
+        )
+        .unwrap()
+        .ir;
+        let user = IRType::ResourceRef(ir.get_resource_index("User").unwrap());
+
+        let value = Value::from_json(json!({ "name": "Ada", "active": null, "tags": [{ "name": "admin" }] }), &user, &ir)
+            .unwrap();
+        let Value::Resource(fields) = &value else { panic!("expected a resource") };
+        assert_eq!(fields[1].value, Value::Absent);
+        assert_eq!(fields[2].value, Value::Null);
+        assert_eq!(fields[3].value, Value::Number(3));
+        assert_eq!(
+            value.to_json(),
+            json!({ "name": "Ada", "active": null, "level": 3, "tags": [{ "name": "admin" }] })
+        );
+        let mut encoder = BinaryEncoder::new();
+        assert!(encoder.encode_value(&value, &user, &ir).is_ok());
+
+        // Round trip through the binary encoding
+        let json = json!({ "name": "Ada", "age": 36, "active": true, "level": 9, "tags": [] });
+        let mut encoder = BinaryEncoder::new();
+        encoder.encode_value(&Value::from_json(json.clone(), &user, &ir).unwrap(), &user, &ir).unwrap();
+        let bytes = encoder.finish();
+        assert_eq!(BinaryDecoder::new(&bytes).decode_value(&user, &ir).unwrap().to_json(), json);
+
+        let err = Value::from_json(json!({ "name": "Ada", "active": true, "tags": [{ "name": 1 }] }), &user, &ir)
+            .unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected string, got number");
+        assert_eq!(err.diagnostics[0].notes, vec!["at $.tags[0].name".to_string()]);
+        let err = Value::from_json(json!({ "name": "Ada", "tags": [] }), &user, &ir).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Missing field `active` of User");
+        let err = Value::from_json(json!({ "name": null, "active": true, "tags": [] }), &user, &ir).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected string, got null");
+        let err = Value::from_json(json!({ "name": "Ada", "active": true, "tags": [], "x": 1 }), &user, &ir)
+            .unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "No field `x` in User");
+        assert_eq!(err.diagnostics[0].code(), "P0016");
+        let err = Value::from_json(json!(1.5), &IRType::Primitive("number".to_string()), &ir).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected a 64-bit integer, got 1.5");
+    }
+
+    #[test]
+    fn test_resource_view() {
+        let ir = compile_schema(
+            "resource Tag { string name\n list number scores }\nresource User {\n    string name\n    optional number age\n    nullable string nickname\n    list Tag tags\n    Tag primary\n    bool active\n}",
+        )
+        .unwrap()
+        .ir;
+        let user_idx = ir.get_resource_index("User").unwrap();
+        let user = IRType::ResourceRef(user_idx);
+        let value = Value::from_json(
+            json!({
+                "name": "Ada",
+                "nickname": null,
+                "tags": [{ "name": "admin", "scores": [1, 2] }, { "name": "ops", "scores": [] }],
+                "primary": { "name": "lead", "scores": [3] },
+                "active": true
+            }),
+            &user,
+            &ir,
+        )
+        .unwrap();
+        let mut encoder = BinaryEncoder::new();
+        encoder.encode_value(&value, &user, &ir).unwrap();
+        let bytes = encoder.finish();
+
+        let view = ResourceView::new(&bytes, user_idx, &ir).unwrap();
+        let name: &str = view.string("name").unwrap().unwrap();
+        assert_eq!(name, "Ada");
+        assert!(std::ptr::eq(name.as_ptr(), bytes[4..].as_ptr()));
+        assert_eq!(view.number("age").unwrap(), None);
+        assert!(matches!(view.field("nickname").unwrap(), ValueView::Null));
+        assert_eq!(view.bool("active").unwrap(), Some(true));
+        assert_eq!(view.nested("primary").unwrap().unwrap().string("name").unwrap(), Some("lead"));
+
+        let tags = view.list("tags").unwrap().unwrap();
+        assert_eq!(tags.len(), 2);
+        let names: Vec<&str> = tags
+            .iter()
+            .map(|tag| tag.unwrap().as_resource().unwrap().string("name").unwrap().unwrap())
+            .collect();
+        assert_eq!(names, ["admin", "ops"]);
+        let first = tags.iter().next().unwrap().unwrap().as_resource().unwrap();
+        let scores: Vec<i64> = first.list("scores").unwrap().unwrap().iter().map(|s| s.unwrap().as_number().unwrap()).collect();
+        assert_eq!(scores, [1, 2]);
+
+        let err = view.number("name").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected number for User.name, got string");
+        // The schema decides, not whether this payload happens to be null
+        let err = view.number("nickname").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected number for User.nickname, got string");
+        assert_eq!(view.string("nickname").unwrap(), None);
+        let err = view.list("primary").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected list for User.primary, got Tag");
+        let err = view.field("email").unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "No field `email` in User");
+
+        // Truncated buffers fail at the field that reaches the end
+        let truncated = ResourceView::new(&bytes[..20], user_idx, &ir).unwrap();
+        assert_eq!(truncated.string("name").unwrap(), Some("Ada"));
+        let err = truncated.bool("active").unwrap_err();
+        assert!(err.diagnostics[0].message().starts_with("Malformed payload at byte"));
+        // Two tags can't fit in the 7 bytes left
+        let err = truncated.list("tags").unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Malformed payload at byte 13: list of 2 items needs at least 8 bytes per item, 7 left"
+        );
+    }
+
+    #[test]
+    fn test_inspect_payload() {
+        let ir = compile_schema("resource User {\n    string name\n    optional number age\n    nullable list bool flags\n}")
+            .unwrap()
+            .ir;
+        let user = IRType::ResourceRef(0);
+        let value = Value::from_json(json!({ "name": "Ada", "age": 36, "flags": [true] }), &user, &ir).unwrap();
+        let mut encoder = BinaryEncoder::new();
+        encoder.encode_value(&value, &user, &ir).unwrap();
+        let bytes = encoder.finish();
+
+        let inspection = inspect_payload(&bytes, &user, &ir);
+        assert!(inspection.error.is_none());
+        assert_eq!(
+            inspection.hex_dump(&bytes),
+            "00000000  03 00 00 00              name      length 3\n\
+             00000004  41 64 61                 name      \"Ada\"\n\
+             00000007  01                       age       present\n\
+             00000008  24 00 00 00 00 00 00 00  age       36\n\
+             00000010  01                       flags     not null\n\
+             00000011  01 00 00 00              flags     count 1\n\
+             00000015  01                       flags[0]  true\n"
+        );
+
+        let inspection = inspect_payload(&bytes[..10], &user, &ir);
+        assert_eq!(inspection.parts.len(), 3);
+        assert!(inspection.hex_dump(&bytes[..10]).ends_with(
+            "00000008  24 00                    ??\nerror: Malformed payload at byte 8: expected 8 more bytes, 2 left\n"
+        ));
+
+        // A count the rest of the payload can't hold stops before the items
+        let mut short = bytes[..0x15].to_vec();
+        short[0x11] = 0xff;
+        let inspection = inspect_payload(&short, &user, &ir);
+        assert_eq!(
+            inspection.error.unwrap().diagnostics[0].message(),
+            "Malformed payload at byte 21: list of 255 items needs at least 1 bytes per item, 0 left"
+        );
+    }
+
+    #[test]
+    fn test_mock_server() {
+        let ir = compile_schema(
+            "resource User {\n    string id\n    optional string nick\n    list number scores\n}\nservice UserApi {\n    get \"/users/:id\" -> User\n    delete \"/users/:id\"\n}",
+        )
+        .unwrap()
+        .ir;
+        let user = IRType::ResourceRef(0);
+
+        // Payloads are valid, and stable for a seed and request line
+        let server = MockServer::new(ir.clone(), 7);
+        let response = server.respond("GET", "/users/1", "");
+        assert_eq!((response.status, response.content_type), (200, Some(PREVIOUS_MEDIA_TYPE)));
+        let mut decoder = BinaryDecoder::new(&response.body);
+        decoder.decode_value(&user, &ir).unwrap();
+        assert!(decoder.finish().is_ok());
+        assert_eq!(MockServer::new(ir.clone(), 7).respond("GET", "/users/1?x=1", "").body, response.body);
+        assert_ne!(MockServer::new(ir.clone(), 8).respond("GET", "/users/1", "").body, response.body);
+        let deleted = server.respond("DELETE", "/users/1", "");
+        assert_eq!((deleted.status, deleted.content_type, deleted.body.len()), (204, None, 0));
+        assert_eq!(server.respond("GET", "/resources/User", "").status, 200);
+        let missing = server.respond("GET", "/posts", "");
+        assert_eq!(missing.status, 404);
+        assert_eq!(missing.body, br#"{"message":"no endpoint for GET /posts"}"#.to_vec());
+
+        // Endpoint fixtures win over resource fixtures
+        let mut server = MockServer::new(ir.clone(), 7);
+        server
+            .add_fixtures(&json!({
+                "User": { "id": "any", "scores": [] },
+                "GET /users/:id": { "id": "u1", "nick": "ada", "scores": [1, 2] }
+            }))
+            .unwrap();
+        let response = server.respond("GET", "/users/9", "application/json");
+        assert_eq!(response.content_type, Some("application/json"));
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&response.body).unwrap(),
+            json!({ "id": "u1", "nick": "ada", "scores": [1, 2] })
+        );
+        let response = server.respond("GET", "/resources/User", "application/json");
+        assert_eq!(response.body, br#"{"id":"any","scores":[]}"#.to_vec());
+        let err = server.add_fixtures(&json!({ "User": { "id": 3, "scores": [] } })).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Type mismatch: expected string, got number");
+        assert!(server.add_fixtures(&json!({ "Post": {} })).is_err());
+
+        // Served over HTTP on localhost
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || server.serve(listener));
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(b"GET /users/9 HTTP/1.1\r\nAccept: application/json\r\n\r\n").unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        assert!(reply.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(reply.ends_with(r#"{"id":"u1","nick":"ada","scores":[1,2]}"#));
+    }
+
+    #[cfg(feature = "axum")]
+    #[tokio::test]
+    async fn test_axum_integration() {
+        use crate::axum::{PreviousRequest, PreviousResponse, PreviousSchema};
+        use ::axum::body::{to_bytes, Body};
+        use ::axum::http::{header, Request, StatusCode};
+        use ::axum::routing::{get, post};
+        use ::axum::Router;
+        use tower::ServiceExt;
+
+        let ir = compile_schema(
+            "resource User { string id\n string name }\nresource UserInput { string name }\nservice UserApi {\n    get \"/users/:id\" -> User\n    post \"/users\" UserInput -> User\n    delete \"/users/:id\"\n}",
+        )
+        .unwrap()
+        .ir;
+        let user = |id: &str, name: &str| {
+            Value::Resource(vec![
+                FieldValue { name: "id".to_string(), value: Value::String(id.to_string()), is_optional: false, is_nullable: false },
+                FieldValue { name: "name".to_string(), value: Value::String(name.to_string()), is_optional: false, is_nullable: false },
+            ])
+        };
+
+        async fn show(request: PreviousRequest) -> PreviousResponse {
+            request.respond(Value::Resource(vec![
+                FieldValue { name: "id".to_string(), value: Value::String("1".to_string()), is_optional: false, is_nullable: false },
+                FieldValue { name: "name".to_string(), value: Value::String("Ada".to_string()), is_optional: false, is_nullable: false },
+            ]))
+        }
+        async fn create(request: PreviousRequest) -> PreviousResponse {
+            let name = match &request.body {
+                Some(Value::Resource(fields)) => fields[0].value.clone(),
+                _ => Value::Null,
+            };
+            let value = Value::Resource(vec![
+                FieldValue { name: "id".to_string(), value: Value::String("2".to_string()), is_optional: false, is_nullable: false },
+                FieldValue { name: "name".to_string(), value: name, is_optional: false, is_nullable: false },
+            ]);
+            request.respond(value).with_status(StatusCode::CREATED)
+        }
+        async fn remove(request: PreviousRequest) -> PreviousResponse {
+            request.no_content()
+        }
+        let app = Router::new()
+            .route("/api/users/{id}", get(show).delete(remove))
+            .route("/api/users", post(create))
+            .route("/api/admin/users/{id}", get(show))
+            .with_state(PreviousSchema::new(ir.clone()).with_prefix("/api"));
+
+        let call = |request: Request<Body>| {
+            let app = app.clone();
+            async move {
+                let response = app.oneshot(request).await.unwrap();
+                let status = response.status();
+                let content_type = response.headers()[header::CONTENT_TYPE].to_str().unwrap().to_string();
+                let body = to_bytes(response.into_body(), usize::MAX).await.unwrap().to_vec();
+                (status, content_type, body)
+            }
+        };
+        let encode = |value: &Value, resource: &str| {
+            let mut encoder = BinaryEncoder::new();
+            let idx = ir.get_resource_index(resource).unwrap();
+            encoder.encode_value(value, &IRType::ResourceRef(idx), &ir).unwrap();
+            encoder.finish()
+        };
+
+        // Binary by default, JSON when asked for
+        let (status, content_type, body) =
+            call(Request::get("/api/users/1").body(Body::empty()).unwrap()).await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::OK, PREVIOUS_MEDIA_TYPE));
+        assert_eq!(body, encode(&user("1", "Ada"), "User"));
+        let (_, content_type, body) = call(
+            Request::get("/api/users/1").header(header::ACCEPT, "application/json").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(content_type, "application/json");
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&body).unwrap(), json!({ "id": "1", "name": "Ada" }));
+
+        // Request bodies are decoded against the endpoint's resource
+        let input = Value::Resource(vec![FieldValue {
+            name: "name".to_string(),
+            value: Value::String("Grace".to_string()),
+            is_optional: false,
+            is_nullable: false,
+        }]);
+        let (status, _, body) = call(
+            Request::post("/api/users")
+                .header(header::CONTENT_TYPE, PREVIOUS_MEDIA_TYPE)
+                .body(Body::from(encode(&input, "UserInput")))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, encode(&user("2", "Grace"), "User"));
+
+        let (status, content_type, body) = call(
+            Request::post("/api/users").header(header::CONTENT_TYPE, PREVIOUS_MEDIA_TYPE).body(Body::from(vec![9, 0])).unwrap(),
+        )
+        .await;
+        assert_eq!((status, content_type.as_str()), (StatusCode::BAD_REQUEST, "application/json"));
+        assert!(String::from_utf8(body).unwrap().contains("Malformed payload at byte 0"));
+        let (status, _, _) = call(
+            Request::post("/api/users").header(header::CONTENT_TYPE, "application/json").body(Body::from("{}")).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let (status, _, _) = call(
+            Request::post("/api/users").header(header::CONTENT_TYPE, "application/x-previousfoo").body(Body::empty()).unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        let (status, _, _) = call(
+            Request::post("/api/users")
+                .header(header::CONTENT_TYPE, "Application/X-Previous; charset=binary")
+                .body(Body::from(encode(&input, "UserInput")))
+                .unwrap(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+
+        // Responses without a body have no Content-Type
+        let response = app.clone().oneshot(Request::delete("/api/users/1").body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!response.headers().contains_key(header::CONTENT_TYPE));
+
+        // A route that only ends like an endpoint's path is not that endpoint
+        let (status, _, body) = call(Request::get("/api/admin/users/1").body(Body::empty()).unwrap()).await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(String::from_utf8(body).unwrap().contains("no service endpoint for GET /api/admin/users/{id}"));
+    }
+
+    #[cfg(feature = "serde-wire")]
+    #[test]
+    fn test_serde_wire() {
+        use serde::{Deserialize, Serialize};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Tag {
+            name: String,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct User {
+            name: String,
+            age: Option<u32>,
+            active: Option<bool>,
+            nickname: Option<Option<String>>,
+            tags: Vec<Tag>,
+        }
+
+        let ir = compile_schema(
+            "resource Tag { string name }\nresource User {\n    string name\n    optional number age\n    nullable bool active\n    optional nullable string nickname\n    list Tag tags\n}",
+        )
+        .unwrap()
+        .ir;
+        let user_type = IRType::ResourceRef(ir.get_resource_index("User").unwrap());
+
+        let user = User {
+            name: "Ada".to_string(),
+            age: None,
+            active: Some(true),
+            nickname: Some(None),
+            tags: vec![Tag { name: "admin".to_string() }, Tag { name: "ops".to_string() }],
+        };
+        let value = Value::from_json(
+            json!({ "name": "Ada", "active": true, "nickname": null, "tags": [{ "name": "admin" }, { "name": "ops" }] }),
+            &user_type,
+            &ir,
+        )
+        .unwrap();
+        let mut encoder = BinaryEncoder::new();
+        encoder.encode_value(&value, &user_type, &ir).unwrap();
+        let expected = encoder.finish();
+
+        // Same layout as BinaryEncoder, both ways
+        let bytes = serde_wire::to_bytes(&user).unwrap();
+        assert_eq!(bytes, expected);
+        assert_eq!(serde_wire::from_bytes::<User>(&bytes).unwrap(), user);
+
+        // Strings can be borrowed from the buffer
+        #[derive(Deserialize)]
+        struct Name<'a> {
+            name: &'a str,
+        }
+        let tag = serde_wire::to_bytes(&Tag { name: "admin".to_string() }).unwrap();
+        assert_eq!(serde_wire::from_bytes::<Name>(&tag).unwrap().name, "admin");
+
+        let err = serde_wire::to_bytes(&1.5f64).unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Type mismatch: expected a string, number, bool, list or resource, got f64"
+        );
+        let err = serde_wire::from_bytes::<Tag>(&bytes).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Malformed payload at byte 7: 25 trailing bytes");
+        // Errors raised by serde itself get the offset decoding stopped at
+        let err = serde_wire::from_bytes::<char>(&tag).unwrap_err();
+        assert_eq!(
+            err.diagnostics[0].message(),
+            "Malformed payload at byte 9: invalid value: string \"admin\", expected a character"
+        );
+
+        // Items encoded in no bytes are bounded by the empty list limit
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Marker;
+        let limit = DEFAULT_MAX_EMPTY_LIST_LENGTH as usize;
+        let markers: Vec<Marker> = (0..limit).map(|_| Marker).collect();
+        let bytes = serde_wire::to_bytes(&markers).unwrap();
+        assert_eq!(serde_wire::from_bytes::<Vec<Marker>>(&bytes).unwrap(), markers);
+        let bytes = serde_wire::to_bytes(&vec![(); limit + 1]).unwrap();
+        let err = serde_wire::from_bytes::<Vec<()>>(&bytes).unwrap_err();
+        assert_eq!(err.diagnostics[0].message(), "Malformed payload at byte 4: list of more than 65536 empty items");
+        let limits = WireOptions { max_empty_list_length: Some(limit as u32 + 1), ..WireOptions::default() };
+        let mut deserializer = serde_wire::Deserializer::new(&bytes).with_limits(limits);
+        assert_eq!(Vec::<()>::deserialize(&mut deserializer).unwrap().len(), limit + 1);
+        assert!(deserializer.finish().is_ok());
+    }
 }
@@ -1,5 +1,5 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
 use std::process;
 
 #[derive(Parser)]
@@ -10,9 +10,9 @@ struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
 
-    /// Input schema file (.pr)
+    /// Input schema files or glob patterns (.pr), compiled as one program
     #[arg(value_name = "FILE")]
-    input: Option<PathBuf>,
+    inputs: Vec<PathBuf>,
 
     /// Output directory for generated code
     #[arg(short, long, value_name = "DIR", default_value = "./generated")]
@@ -21,14 +21,94 @@ struct Cli {
     /// Enable verbose output
     #[arg(short, long)]
     verbose: bool,
+
+    /// Output targets to generate (repeatable)
+    #[arg(short, long = "target", value_name = "TARGET", default_value = "ts")]
+    target: Vec<TargetArg>,
+
+    /// How to print diagnostics
+    #[arg(long, value_enum, default_value = "human")]
+    message_format: MessageFormatArg,
+
+    /// Output layout when compiling several files
+    #[arg(long, value_enum, default_value = "merged")]
+    layout: LayoutArg,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TargetArg {
+    /// TypeScript client and server
+    Ts,
+    /// OpenAPI 3.1 document
+    Openapi,
+    /// Protocol Buffers (proto3)
+    Proto,
+    /// GraphQL SDL
+    Graphql,
+    /// Rust structs with Wire impls
+    Rust,
+}
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+enum MessageFormatArg {
+    /// Rendered diagnostics with source snippets
+    Human,
+    /// One JSON object per line for each diagnostic, then a summary object
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LayoutArg {
+    /// All resources in one set of files
+    Merged,
+    /// One subdirectory per input file
+    PerFile,
+}
+
+impl From<LayoutArg> for previous::OutputLayout {
+    fn from(layout: LayoutArg) -> Self {
+        match layout {
+            LayoutArg::Merged => previous::OutputLayout::Merged,
+            LayoutArg::PerFile => previous::OutputLayout::PerFile,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DocsFormatArg {
+    /// One Markdown page per resource
+    Markdown,
+    /// A single HTML page
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GraphFormatArg {
+    /// Graphviz DOT
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+}
+
+impl From<TargetArg> for previous::Target {
+    fn from(target: TargetArg) -> Self {
+        match target {
+            TargetArg::Ts => previous::Target::TypeScript,
+            TargetArg::Openapi => previous::Target::OpenApi,
+            TargetArg::Proto => previous::Target::Proto,
+            TargetArg::Graphql => previous::Target::GraphQl,
+            TargetArg::Rust => previous::Target::Rust,
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Compile a schema file
+    /// Compile one or more schema files
     Compile {
-        /// Input schema file (.pr)
-        input: PathBuf,
+        /// Input schema files or glob patterns (.pr), compiled as one program
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
 
         /// Output directory for generated code
         #[arg(short, long, value_name = "DIR", default_value = "./generated")]
@@ -37,19 +117,216 @@ enum Commands {
         /// Enable verbose output
         #[arg(short, long)]
         verbose: bool,
+
+        /// Output targets to generate (repeatable)
+        #[arg(short, long = "target", value_name = "TARGET", default_value = "ts")]
+        target: Vec<TargetArg>,
+
+        /// How to print diagnostics
+        #[arg(long, value_enum, default_value = "human")]
+        message_format: MessageFormatArg,
+
+        /// Output layout when compiling several files
+        #[arg(long, value_enum, default_value = "merged")]
+        layout: LayoutArg,
+    },
+    /// Compile every schema listed in previous.toml
+    Build {
+        /// Config file, found in the current or a parent directory by default
+        #[arg(long, value_name = "FILE")]
+        config: Option<PathBuf>,
+
+        /// Enable verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Recompile schema files whenever they change
+    Watch {
+        /// Input schema files or glob patterns (.pr), compiled as one program
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Output directory for generated code
+        #[arg(short, long, value_name = "DIR", default_value = "./generated")]
+        out: PathBuf,
+
+        /// Output targets to generate (repeatable)
+        #[arg(short, long = "target", value_name = "TARGET", default_value = "ts")]
+        target: Vec<TargetArg>,
+
+        /// Output layout when compiling several files
+        #[arg(long, value_enum, default_value = "merged")]
+        layout: LayoutArg,
+
+        /// Polling interval in milliseconds
+        #[arg(long, value_name = "MS", default_value_t = 250)]
+        interval: u64,
+    },
+    /// Translate Protocol Buffers messages into a schema file
+    ImportProto {
+        /// Input proto file (.proto)
+        input: PathBuf,
+
+        /// Output schema file (defaults to stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Generate reference documentation for a schema
+    Docs {
+        /// Input schema file (.pr)
+        input: PathBuf,
+
+        /// Output directory for the documentation
+        #[arg(short, long, value_name = "DIR", default_value = "./docs")]
+        out: PathBuf,
+
+        /// Documentation format
+        #[arg(short, long, value_enum, default_value = "markdown")]
+        format: DocsFormatArg,
+    },
+    /// Render the resource reference graph
+    Graph {
+        /// Input schema file (.pr)
+        input: PathBuf,
+
+        /// Graph format
+        #[arg(short, long, value_enum, default_value = "dot")]
+        format: GraphFormatArg,
+
+        /// Output file (defaults to stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Format schema files in place
+    Fmt {
+        /// Schema files to format (.pr)
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Only check formatting, exit with an error if a file would change
+        #[arg(long)]
+        check: bool,
+    },
+    /// Check a schema for style and risk problems
+    Lint {
+        /// Input schema file (.pr)
+        input: PathBuf,
+
+        /// Disable a rule (repeatable)
+        #[arg(long, value_name = "RULE")]
+        allow: Vec<String>,
+
+        /// Report a rule as a warning (repeatable)
+        #[arg(long, value_name = "RULE")]
+        warn: Vec<String>,
+
+        /// Report a rule as an error (repeatable)
+        #[arg(long, value_name = "RULE")]
+        deny: Vec<String>,
+
+        /// Field count above which a resource is reported as too wide [default: 32]
+        #[arg(long, value_name = "N")]
+        max_fields: Option<usize>,
+    },
+    /// Serve random schema-valid payloads for every resource and endpoint
+    Mock {
+        /// Input schema files or glob patterns (.pr), compiled as one program
+        #[arg(required = true)]
+        inputs: Vec<PathBuf>,
+
+        /// Port to listen on at 127.0.0.1, 0 picks a free one
+        #[arg(short, long, default_value_t = 4010)]
+        port: u16,
+
+        /// Seed of the random payloads
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+
+        /// JSON file of payloads keyed by resource name or endpoint, e.g. "GET /users/:id" (repeatable)
+        #[arg(long = "fixtures", value_name = "FILE")]
+        fixtures: Vec<PathBuf>,
     },
+    /// Encode a JSON payload from stdin into the binary format on stdout
+    Encode {
+        #[command(flatten)]
+        payload: PayloadArgs,
+    },
+    /// Decode a binary payload from stdin into JSON on stdout
+    Decode {
+        #[command(flatten)]
+        payload: PayloadArgs,
+    },
+    /// Print an annotated hex dump of a binary payload from stdin
+    Inspect {
+        #[command(flatten)]
+        payload: PayloadArgs,
+    },
+    /// Run the language server over stdio
+    Lsp,
     /// Show version information
     Version,
     /// Run demo examples
     Demo,
 }
 
+/// Schema and resource a payload is read as
+#[derive(clap::Args)]
+struct PayloadArgs {
+    /// Schema files or glob patterns (.pr), compiled as one program (repeatable)
+    #[arg(long, value_name = "FILE", required = true)]
+    schema: Vec<PathBuf>,
+
+    /// Resource the payload holds, qualified with its package if it has one
+    #[arg(long = "type", value_name = "RESOURCE")]
+    type_name: String,
+}
+
 fn main() {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::Compile { input, out, verbose }) => {
-            compile_command(input, out, verbose);
+        Some(Commands::Compile { inputs, out, verbose, target, message_format, layout }) => {
+            compile_command(inputs, out, verbose, target, message_format, layout);
+        }
+        Some(Commands::Build { config, verbose }) => {
+            build_command(config, verbose);
+        }
+        Some(Commands::Watch { inputs, out, target, layout, interval }) => {
+            watch_command(inputs, out, target, layout, interval);
+        }
+        Some(Commands::ImportProto { input, output }) => {
+            import_proto_command(input, output);
+        }
+        Some(Commands::Docs { input, out, format }) => {
+            docs_command(input, out, format);
+        }
+        Some(Commands::Graph { input, format, output }) => {
+            graph_command(input, format, output);
+        }
+        Some(Commands::Fmt { inputs, check }) => {
+            fmt_command(inputs, check);
+        }
+        Some(Commands::Lint { input, allow, warn, deny, max_fields }) => {
+            lint_command(input, allow, warn, deny, max_fields);
+        }
+        Some(Commands::Mock { inputs, port, seed, fixtures }) => {
+            mock_command(inputs, port, seed, fixtures);
+        }
+        Some(Commands::Encode { payload }) => {
+            encode_command(payload);
+        }
+        Some(Commands::Decode { payload }) => {
+            decode_command(payload);
+        }
+        Some(Commands::Inspect { payload }) => {
+            inspect_command(payload);
+        }
+        Some(Commands::Lsp) => {
+            let stdin = std::io::stdin();
+            let stdout = std::io::stdout();
+            if let Err(e) = previous::run_language_server(stdin.lock(), stdout.lock()) {
+                exit_with(e);
+            }
         }
         Some(Commands::Version) => {
             println!("previouscc {}", env!("CARGO_PKG_VERSION"));
@@ -59,51 +336,546 @@ fn main() {
             run_demo();
         }
         None => {
-            // Default behavior: compile if input file provided
-            if let Some(input) = cli.input {
-                compile_command(input, cli.out, cli.verbose);
+            // Default behavior: compile if input files provided
+            if !cli.inputs.is_empty() {
+                compile_command(cli.inputs, cli.out, cli.verbose, cli.target, cli.message_format, cli.layout);
+            } else if previous::ProjectConfig::discover(Path::new(".")).is_some() {
+                // No input file, build the project
+                build_command(None, cli.verbose);
             } else {
-                // No input file, run demo
+                // No input file and no project, run demo
                 run_demo();
             }
         }
     }
 }
 
-fn compile_command(input: PathBuf, out: PathBuf, verbose: bool) {
-    let options = previous::CliOptions {
-        input_file: input.clone(),
+fn compile_command(
+    inputs: Vec<PathBuf>,
+    out: PathBuf,
+    verbose: bool,
+    targets: Vec<TargetArg>,
+    message_format: MessageFormatArg,
+    layout: LayoutArg,
+) {
+    let options = project_codegen_options().map(|codegen| previous::CliOptions {
+        inputs: inputs.clone(),
         output_dir: out.clone(),
         verbose,
-    };
+        targets: targets.into_iter().map(previous::Target::from).collect(),
+        codegen,
+        layout: layout.into(),
+    });
+
+    if message_format == MessageFormatArg::Json {
+        compile_json(options);
+        return;
+    }
+    let options = options.unwrap_or_else(|e| exit_with(e));
 
     println!("Previous Compiler v{}", env!("CARGO_PKG_VERSION"));
     println!();
 
     if verbose {
-        println!("Input:  {}", input.display());
+        println!("Input:  {}", display_inputs(&inputs));
         println!("Output: {}", out.display());
         println!();
     }
 
     match previous::compile_file(&options) {
-        Ok(_) => {
+        Ok(report) => {
             println!("✓ Compilation successful!");
             println!();
             println!("Generated files:");
-            println!("  {}/client.ts", out.display());
-            println!("  {}/server.ts", out.display());
-            println!();
-            println!("Next steps:");
-            println!("  - Client: Import client.ts for binary decoding");
-            println!("  - Server: Import server.ts for binary encoding");
+            for path in report.written.iter().chain(&report.unchanged) {
+                println!("  {}", path.display());
+            }
+            if options.targets.contains(&previous::Target::TypeScript) {
+                println!();
+                println!("Next steps:");
+                println!("  - Client: Import client.ts for binary decoding");
+                println!("  - Server: Import server.ts for binary encoding");
+            }
         }
         Err(e) => {
             eprintln!("✗ Compilation failed!");
             eprintln!();
-            eprintln!("Error: {}", e);
+            eprint!("{}", e.format());
+            process::exit(e.exit_code());
+        }
+    }
+}
+
+/// Inputs as typed, for progress messages
+fn display_inputs(inputs: &[PathBuf]) -> String {
+    inputs
+        .iter()
+        .map(|input| input.display().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Compile printing only JSON lines on stdout, for CI annotations; an
+/// invalid project config is reported the same way
+fn compile_json(options: Result<previous::CliOptions, previous::CompileError>) {
+    let result = options.and_then(|options| previous::compile_file(&options));
+
+    let (diagnostics, file) = match &result {
+        Ok(_) => (Vec::new(), None),
+        Err(e) => (e.diagnostics.clone(), e.file.clone()),
+    };
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic.to_json(file.as_deref()));
+    }
+
+    let count = |severity| diagnostics.iter().filter(|d| d.severity == severity).count();
+    let files: Vec<String> = match &result {
+        Ok(report) => report
+            .written
+            .iter()
+            .chain(&report.unchanged)
+            .map(|path| path.display().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    let exit_code = result.as_ref().err().map(|e| e.exit_code()).unwrap_or(0);
+    let summary = serde_json::json!({
+        "type": "summary",
+        "success": result.is_ok(),
+        "errors": count(previous::Severity::Error),
+        "warnings": count(previous::Severity::Warning),
+        "category": result.as_ref().err().and_then(|e| e.category()).map(|c| c.name()),
+        "exit_code": exit_code,
+        "files": files,
+    });
+    println!("{}", summary);
+    process::exit(exit_code);
+}
+
+/// Load the given config file, or the one discovered from the current directory
+fn find_project_config(path: Option<PathBuf>) -> Result<Option<previous::ProjectConfig>, previous::CompileError> {
+    let path = path.or_else(|| {
+        let cwd = std::env::current_dir().ok()?;
+        previous::ProjectConfig::discover(&cwd)
+    });
+    path.map(|path| previous::ProjectConfig::load(&path)).transpose()
+}
+
+/// `find_project_config`, exiting when the config is invalid
+fn load_project_config(path: Option<PathBuf>) -> Option<previous::ProjectConfig> {
+    find_project_config(path).unwrap_or_else(|e| exit_with(e))
+}
+
+/// Generator options from the project config, so ad-hoc compiles match `build`
+fn project_codegen_options() -> Result<previous::CodegenOptions, previous::CompileError> {
+    Ok(find_project_config(None)?
+        .map(|config| config.codegen_options())
+        .unwrap_or_default())
+}
+
+fn build_command(config: Option<PathBuf>, verbose: bool) {
+    let project = match load_project_config(config) {
+        Some(project) => project,
+        None => {
+            eprintln!("✗ No {} found in this or any parent directory", previous::CONFIG_FILE_NAME);
             process::exit(1);
         }
+    };
+
+    println!("Previous Compiler v{}", env!("CARGO_PKG_VERSION"));
+    println!();
+
+    let runs = project.compile_options();
+    if runs.is_empty() {
+        println!("No [[schema]] entries to compile");
+        return;
+    }
+
+    let mut exit_code = 0;
+    for mut options in runs {
+        options.verbose = verbose;
+        match previous::compile_file(&options) {
+            Ok(_) => {
+                println!("✓ {} → {}", display_inputs(&options.inputs), options.output_dir.display());
+            }
+            Err(e) => {
+                eprintln!("✗ {}", display_inputs(&options.inputs));
+                eprint!("{}", e.format());
+                if exit_code == 0 {
+                    exit_code = e.exit_code();
+                }
+            }
+        }
+    }
+    process::exit(exit_code);
+}
+
+fn watch_command(inputs: Vec<PathBuf>, out: PathBuf, targets: Vec<TargetArg>, layout: LayoutArg, interval: u64) {
+    let options = previous::CliOptions {
+        inputs: inputs.clone(),
+        output_dir: out,
+        verbose: false,
+        targets: targets.into_iter().map(previous::Target::from).collect(),
+        codegen: project_codegen_options().unwrap_or_else(|e| exit_with(e)),
+        layout: layout.into(),
+    };
+    let mut watcher = previous::SchemaWatcher::new(options);
+
+    println!("Watching {} (Ctrl-C to stop)", display_inputs(&inputs));
+    println!();
+
+    loop {
+        match watcher.check() {
+            Some(Ok(report)) => {
+                println!(
+                    "✓ Compiled: {} written, {} unchanged",
+                    report.written.len(),
+                    report.unchanged.len()
+                );
+                for path in &report.written {
+                    println!("  {}", path.display());
+                }
+            }
+            Some(Err(e)) => {
+                eprintln!("✗ Compilation failed!");
+                eprint!("{}", e.format());
+            }
+            None => {}
+        }
+        std::thread::sleep(std::time::Duration::from_millis(interval));
+    }
+}
+
+fn import_proto_command(input: PathBuf, output: Option<PathBuf>) {
+    let source = match std::fs::read_to_string(&input) {
+        Ok(source) => source,
+        Err(e) => {
+            eprintln!("✗ Failed to read input file '{}': {}", input.display(), e);
+            process::exit(1);
+        }
+    };
+
+    let imported = match previous::import_proto(&source) {
+        Ok(imported) => imported,
+        Err(e) => {
+            eprintln!("✗ Import failed!");
+            eprintln!();
+            eprint!("{}", e.with_file(input).format());
+            process::exit(1);
+        }
+    };
+
+    for warning in &imported.warnings {
+        eprintln!("warning: {}", warning);
+    }
+
+    let schema = previous::format_program(&imported.program);
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, schema) {
+                eprintln!("✗ Failed to write schema file '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+            eprintln!("✓ Imported {} resources into {}", imported.program.resources.len(), path.display());
+        }
+        None => print!("{}", schema),
+    }
+}
+
+fn docs_command(input: PathBuf, out: PathBuf, format: DocsFormatArg) {
+    let format = match format {
+        DocsFormatArg::Markdown => previous::DocsFormat::Markdown,
+        DocsFormatArg::Html => previous::DocsFormat::Html,
+    };
+
+    let output = match previous::compile_file_to_output(&input) {
+        Ok(output) => output,
+        Err(e) => {
+            eprintln!("✗ Documentation failed!");
+            eprintln!();
+            eprint!("{}", e.format());
+            process::exit(1);
+        }
+    };
+
+    let result = previous::DocsGenerator::new(&output.ir, format).and_then(|generator| {
+        let files = generator.generate();
+        previous::write_docs(&files, &out)?;
+        Ok(files)
+    });
+
+    match result {
+        Ok(files) => {
+            println!("✓ Documentation generated!");
+            println!();
+            for file in &files {
+                println!("  {}/{}", out.display(), file.path);
+            }
+        }
+        Err(e) => {
+            eprintln!("✗ Documentation failed!");
+            eprintln!();
+            eprint!("{}", e.format());
+            process::exit(1);
+        }
+    }
+}
+
+/// Read and resolve a schema file, cycles are allowed
+fn resolve_file(input: &Path) -> Result<previous::IRProgram, previous::CompileError> {
+    let source = std::fs::read_to_string(input).map_err(|e| {
+        previous::CompileError::from(previous::DiagnosticKind::Io {
+            operation: "read input file".to_string(),
+            path: input.to_path_buf(),
+            error: e.to_string(),
+        })
+    })?;
+    previous::resolve_schema(&source).map_err(|e| e.with_file(input.to_path_buf()))
+}
+
+fn graph_command(input: PathBuf, format: GraphFormatArg, output: Option<PathBuf>) {
+    let result = resolve_file(&input).and_then(|ir| previous::CycleDetector::build(&ir));
+
+    let graph = match result {
+        Ok(graph) => graph,
+        Err(e) => {
+            eprintln!("✗ Graph failed!");
+            eprintln!();
+            eprint!("{}", e.format());
+            process::exit(1);
+        }
+    };
+
+    let rendered = match format {
+        GraphFormatArg::Dot => graph.to_dot(),
+        GraphFormatArg::Mermaid => graph.to_mermaid(),
+    };
+
+    match output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(&path, rendered) {
+                eprintln!("✗ Failed to write graph file '{}': {}", path.display(), e);
+                process::exit(1);
+            }
+        }
+        None => print!("{}", rendered),
+    }
+}
+
+fn fmt_command(inputs: Vec<PathBuf>, check: bool) {
+    let mut unformatted = Vec::new();
+    let mut failed = false;
+
+    for input in &inputs {
+        let result = std::fs::read_to_string(input)
+            .map_err(|e| {
+                previous::CompileError::from(previous::DiagnosticKind::Io {
+                    operation: "read input file".to_string(),
+                    path: input.clone(),
+                    error: e.to_string(),
+                })
+            })
+            .and_then(|source| {
+                previous::format_schema(&source)
+                    .map(|formatted| (source, formatted))
+                    .map_err(|e| e.with_file(input.clone()))
+            });
+
+        match result {
+            Ok((source, formatted)) if source != formatted => {
+                if check {
+                    unformatted.push(input);
+                } else if let Err(e) = std::fs::write(input, formatted) {
+                    eprintln!("✗ Failed to write '{}': {}", input.display(), e);
+                    failed = true;
+                } else {
+                    println!("Formatted {}", input.display());
+                }
+            }
+            Ok(_) => {}
+            Err(e) => {
+                eprint!("{}", e.format());
+                failed = true;
+            }
+        }
+    }
+
+    if !unformatted.is_empty() {
+        eprintln!("The following files are not formatted:");
+        for input in &unformatted {
+            eprintln!("  {}", input.display());
+        }
+        process::exit(1);
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+fn lint_command(input: PathBuf, allow: Vec<String>, warn: Vec<String>, deny: Vec<String>, max_fields: Option<usize>) {
+    // Flags refine the project's `[lint]` settings
+    let mut config = match load_project_config(None) {
+        Some(project) => project.lint_config().unwrap_or_else(|e| exit_with(e)),
+        None => previous::LintConfig::default(),
+    };
+    if let Some(max_fields) = max_fields {
+        config.max_fields = max_fields;
+    }
+
+    let levels = [
+        (allow, previous::LintLevel::Allow),
+        (warn, previous::LintLevel::Warn),
+        (deny, previous::LintLevel::Deny),
+    ];
+    for (names, level) in levels {
+        for name in names {
+            match previous::LintRule::from_name(&name) {
+                Some(rule) => config.set_level(rule, level),
+                None => {
+                    let known: Vec<&str> = previous::LintRule::ALL.iter().map(|r| r.name()).collect();
+                    eprintln!("✗ Unknown lint rule '{}' (known rules: {})", name, known.join(", "));
+                    process::exit(1);
+                }
+            }
+        }
+    }
+
+    let ir = match resolve_file(&input) {
+        Ok(ir) => ir,
+        Err(e) => {
+            eprintln!("✗ Lint failed!");
+            eprintln!();
+            eprint!("{}", e.format());
+            process::exit(1);
+        }
+    };
+
+    let warnings = previous::lint(&ir, &config);
+    for warning in &warnings {
+        println!("{}", warning);
+    }
+
+    let errors = warnings.iter().filter(|w| w.severity == previous::Severity::Error).count();
+    if warnings.is_empty() {
+        println!("✓ No lint problems found");
+    } else {
+        println!();
+        println!("{} problems ({} errors, {} warnings)", warnings.len(), errors, warnings.len() - errors);
+    }
+
+    if errors > 0 {
+        process::exit(1);
+    }
+}
+
+fn mock_command(inputs: Vec<PathBuf>, port: u16, seed: u64, fixtures: Vec<PathBuf>) {
+    let result = previous::compile_inputs(&inputs).and_then(|output| {
+        let mut server = previous::MockServer::new(output.ir, seed);
+        for path in &fixtures {
+            server.load_fixtures(path)?;
+        }
+        Ok(server)
+    });
+    let server = match result {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("✗ Mock server failed!");
+            eprintln!();
+            eprint!("{}", e.format());
+            process::exit(e.exit_code());
+        }
+    };
+
+    let listener = match std::net::TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("✗ Failed to listen on 127.0.0.1:{}: {}", port, e);
+            process::exit(1);
+        }
+    };
+    let address = listener.local_addr().map(|a| a.to_string()).unwrap_or_default();
+    println!("✓ Mock server listening on http://{}", address);
+    for route in server.routes() {
+        println!("  {}", route);
+    }
+    // Tests read the address before sending requests
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+
+    if let Err(e) = server.serve(listener) {
+        eprintln!("✗ Mock server error: {}", e);
+        process::exit(1);
+    }
+}
+
+/// Compile the schema and find the payload's resource, exiting on errors
+fn payload_type(args: &PayloadArgs) -> (previous::IRProgram, previous::IRType) {
+    let result = previous::compile_inputs(&args.schema).and_then(|output| {
+        let idx = output.ir.get_resource_index(&args.type_name).ok_or_else(|| {
+            previous::CompileError::from(previous::DiagnosticKind::UndefinedType { name: args.type_name.clone() })
+        })?;
+        Ok((output.ir, previous::IRType::ResourceRef(idx)))
+    });
+    result.unwrap_or_else(|e| exit_with(e))
+}
+
+fn exit_with(e: previous::CompileError) -> ! {
+    eprint!("{}", e.format());
+    process::exit(e.exit_code());
+}
+
+fn read_stdin() -> Vec<u8> {
+    let mut input = Vec::new();
+    if let Err(e) = std::io::Read::read_to_end(&mut std::io::stdin(), &mut input) {
+        exit_with(stdin_error("read", e.to_string()));
+    }
+    input
+}
+
+fn stdin_error(operation: &str, error: String) -> previous::CompileError {
+    previous::CompileError::from(previous::DiagnosticKind::Io {
+        operation: operation.to_string(),
+        path: PathBuf::from("<stdin>"),
+        error,
+    })
+}
+
+fn encode_command(args: PayloadArgs) {
+    let (ir, ir_type) = payload_type(&args);
+    let json = serde_json::from_slice(&read_stdin())
+        .unwrap_or_else(|e| exit_with(stdin_error("parse JSON from", e.to_string())));
+    let value = previous::Value::from_json(json, &ir_type, &ir).unwrap_or_else(|e| exit_with(e));
+
+    let mut encoder = previous::BinaryEncoder::new();
+    if let Err(e) = encoder.encode_value(&value, &ir_type, &ir) {
+        exit_with(e);
+    }
+    let mut stdout = std::io::stdout();
+    let written = std::io::Write::write_all(&mut stdout, &encoder.finish());
+    if let Err(e) = written.and_then(|_| std::io::Write::flush(&mut stdout)) {
+        eprintln!("✗ Failed to write to stdout: {}", e);
+        process::exit(1);
+    }
+}
+
+fn decode_command(args: PayloadArgs) {
+    let (ir, ir_type) = payload_type(&args);
+    let bytes = read_stdin();
+    let mut decoder = previous::BinaryDecoder::new(&bytes);
+    let value = decoder
+        .decode_value(&ir_type, &ir)
+        .and_then(|value| decoder.finish().map(|_| value))
+        .unwrap_or_else(|e| exit_with(e));
+    println!("{}", serde_json::to_string_pretty(&value.to_json()).unwrap_or_default());
+}
+
+fn inspect_command(args: PayloadArgs) {
+    let (ir, ir_type) = payload_type(&args);
+    let bytes = read_stdin();
+    let inspection = previous::inspect_payload(&bytes, &ir_type, &ir);
+    print!("{}", inspection.hex_dump(&bytes));
+    if let Some(e) = inspection.error {
+        process::exit(e.exit_code());
     }
 }
 